@@ -0,0 +1,321 @@
+use crate::RouterState;
+use crate::{api::pipeline::ApiInputAttrData, database::base::client::DatabaseClient};
+use axum::{
+	extract::{Path, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use copper_itemdb::{
+	client::errors::{
+		class::GetClassError,
+		dataset::GetDatasetError,
+		item::{BulkSetItemAttrsError, FindItemsError},
+	},
+	filter::ItemFilter,
+	AttrData, AttributeId, ItemId, UniqueConflict,
+};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use sqlx::Acquire;
+use std::collections::BTreeMap;
+use tracing::error;
+use utoipa::ToSchema;
+
+/// The items a bulk edit changes
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "type")]
+pub(super) enum BulkEditSelection {
+	/// Edit exactly these items
+	Items {
+		#[schema(value_type = Vec<i64>)]
+		items: Vec<ItemId>,
+	},
+
+	/// Edit every item that matches this filter
+	Filter { filter: ItemFilter },
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(super) struct BulkEditRequest {
+	/// The items to edit
+	pub select: BulkEditSelection,
+
+	/// The attributes to change on every selected item.
+	/// Attributes set to `null` are cleared,
+	/// attributes that aren't given are left unchanged.
+	///
+	/// Blob attributes can't be set this way.
+	#[schema(value_type = BTreeMap<i64, ApiInputAttrData>)]
+	pub attributes: BTreeMap<AttributeId, Option<ApiInputAttrData>>,
+
+	/// If true, check this edit but don't apply it
+	#[serde(default)]
+	pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(super) struct BulkEditResponse {
+	/// If true, nothing was changed
+	pub dry_run: bool,
+
+	/// The items this edit changes
+	#[schema(value_type = Vec<i64>)]
+	pub items: Vec<ItemId>,
+
+	/// The `unique` constraints this edit violates.
+	/// If this is not empty, nothing was changed.
+	pub conflicts: Vec<UniqueConflict>,
+}
+
+/// Set attributes on many items of this class at once
+#[utoipa::path(
+	post,
+	path = "/{class_id}/items/edit",
+	params(
+		("class_id", description = "Class id"),
+	),
+	responses(
+		(status = 200, description = "Items edited successfully, or dry run passed", body = BulkEditResponse),
+		(status = 400, description = "Invalid selection or attributes", body = String),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Class not found"),
+		(status = 409, description = "This edit violates `unique` constraints, nothing was changed", body = BulkEditResponse),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+pub(super) async fn bulk_edit_items<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Path(class_id): Path<i64>,
+	Json(payload): Json<BulkEditRequest>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	let mut attributes = Vec::new();
+	for (attr_id, value) in payload.attributes {
+		let value = match value.map(AttrData::try_from).transpose() {
+			Ok(x) => x,
+			Err(()) => {
+				return (
+					StatusCode::BAD_REQUEST,
+					Json(format!(
+						"Attribute {} is a blob, which can't be set directly",
+						i64::from(attr_id)
+					)),
+				)
+					.into_response()
+			}
+		};
+
+		attributes.push((attr_id, value));
+	}
+
+	let mut conn = match state.itemdb_client.new_connection().await {
+		Ok(x) => x,
+		Err(error) => {
+			error!(message = "Error in itemdb client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	let mut trans = match conn.begin().await {
+		Ok(y) => y,
+		Err(error) => {
+			error!(message = "Error in itemdb client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	let class = match state
+		.itemdb_client
+		.get_class(&mut trans, class_id.into())
+		.await
+	{
+		Ok(x) => x,
+
+		Err(GetClassError::NotFound) => return StatusCode::NOT_FOUND.into_response(),
+
+		Err(GetClassError::DbError(error)) => {
+			error!(message = "Error in itemdb client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	match state
+		.itemdb_client
+		.get_dataset(&mut trans, class.dataset)
+		.await
+	{
+		Ok(x) => {
+			// We can only modify our own class
+			if x.owner != user.id {
+				return (StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response();
+			}
+		}
+
+		// In theory unreachable, but possible with unlucky timing
+		Err(GetDatasetError::NotFound) => {
+			return (StatusCode::NOT_FOUND, Json("Dataset not found")).into_response()
+		}
+
+		Err(GetDatasetError::DbError(error)) => {
+			error!(message = "Error in itemdb client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	let items = match payload.select {
+		BulkEditSelection::Items { items } => items,
+		BulkEditSelection::Filter { filter } => {
+			match state
+				.itemdb_client
+				.find_items(&mut trans, class.id, &filter)
+				.await
+			{
+				Ok(x) => x,
+
+				Err(FindItemsError::BadFilter(error)) => {
+					return (
+						StatusCode::BAD_REQUEST,
+						Json(format!("Invalid filter: {error}")),
+					)
+						.into_response()
+				}
+
+				Err(FindItemsError::DbError(error)) => {
+					error!(message = "Error in itemdb client", ?error);
+					return (
+						StatusCode::INTERNAL_SERVER_ERROR,
+						Json("Internal server error"),
+					)
+						.into_response();
+				}
+			}
+		}
+	};
+
+	let res = state
+		.itemdb_client
+		.bulk_set_item_attrs(&mut trans, class.id, &items, attributes)
+		.await;
+
+	let items = items.into_iter().unique().collect();
+	match res {
+		Ok(_) => {}
+
+		Err(BulkSetItemAttrsError::UniqueViolated { conflicts }) => {
+			return (
+				StatusCode::CONFLICT,
+				Json(BulkEditResponse {
+					dry_run: payload.dry_run,
+					items,
+					conflicts,
+				}),
+			)
+				.into_response()
+		}
+
+		Err(BulkSetItemAttrsError::BadItems { item_ids }) => {
+			return (
+				StatusCode::BAD_REQUEST,
+				Json(format!(
+					"Items {} don't exist or aren't in this class",
+					item_ids.iter().map(|x| i64::from(*x)).join(", ")
+				)),
+			)
+				.into_response()
+		}
+
+		Err(BulkSetItemAttrsError::RepeatedAttribute) => {
+			return (
+				StatusCode::BAD_REQUEST,
+				Json("Multiple values were provided for one attribute"),
+			)
+				.into_response()
+		}
+
+		Err(BulkSetItemAttrsError::ForeignAttribute) => {
+			return (
+				StatusCode::BAD_REQUEST,
+				Json("Tried to set an attribute from another class"),
+			)
+				.into_response()
+		}
+
+		Err(BulkSetItemAttrsError::AttributeDataTypeMismatch) => {
+			return (
+				StatusCode::BAD_REQUEST,
+				Json("Tried to set an attribute to a value of the wrong type"),
+			)
+				.into_response()
+		}
+
+		Err(BulkSetItemAttrsError::NoSuchReferencedItem) => {
+			return (
+				StatusCode::BAD_REQUEST,
+				Json("Tried to reference an item that doesn't exist"),
+			)
+				.into_response()
+		}
+
+		Err(BulkSetItemAttrsError::NotNullViolated) => {
+			return (
+				StatusCode::BAD_REQUEST,
+				Json("Tried to clear an attribute that may not be empty"),
+			)
+				.into_response()
+		}
+
+		Err(BulkSetItemAttrsError::DbError(error)) => {
+			error!(message = "Error in itemdb client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	}
+
+	// Dropping this transaction rolls it back
+	if !payload.dry_run {
+		if let Err(error) = trans.commit().await {
+			error!(message = "Error while committing transaction", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	}
+
+	return (
+		StatusCode::OK,
+		Json(BulkEditResponse {
+			dry_run: payload.dry_run,
+			items,
+			conflicts: Vec::new(),
+		}),
+	)
+		.into_response();
+}
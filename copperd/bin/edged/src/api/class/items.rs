@@ -15,6 +15,7 @@ use copper_itemdb::{
 		dataset::GetDatasetError,
 		item::{CountItemsError, GetItemError, ListItemsError},
 	},
+	filter::{ItemFilter, ItemSort},
 	AttrData, AttributeId, ClassId, ItemId,
 };
 use itertools::Itertools;
@@ -311,9 +312,19 @@ pub(super) struct ItemListResponse {
 }
 
 #[derive(Debug, Deserialize, IntoParams)]
-pub(super) struct PaginateParams {
+pub(super) struct ListItemsParams {
 	skip: i64,
 	count: usize,
+
+	/// Only list items that match this filter.
+	/// This is an `ItemFilter` encoded as json.
+	#[serde(default)]
+	filter: Option<String>,
+
+	/// Sort items by an attribute.
+	/// This is an `ItemSort` encoded as json.
+	#[serde(default)]
+	sort: Option<String>,
 }
 
 //
@@ -325,11 +336,12 @@ pub(super) struct PaginateParams {
 	get,
 	path = "/{class_id}/items",
 	params(
-		PaginateParams,
+		ListItemsParams,
 		("class_id", description = "Class id"),
 	),
 	responses(
 		(status = 200, description = "Class info", body = ItemListResponse),
+		(status = 400, description = "Invalid filter or sort", body = String),
 		(status = 401, description = "Unauthorized"),
 		(status = 404, description = "Class not found"),
 		(status = 500, description = "Internal server error"),
@@ -338,7 +350,7 @@ pub(super) struct PaginateParams {
 pub(super) async fn list_items<Client: DatabaseClient>(
 	jar: CookieJar,
 	State(state): State<RouterState<Client>>,
-	Query(paginate): Query<PaginateParams>,
+	Query(params): Query<ListItemsParams>,
 	Path(class_id): Path<i64>,
 ) -> Response {
 	let user = match state.auth.auth_or_logout(&state, &jar).await {
@@ -346,6 +358,30 @@ pub(super) async fn list_items<Client: DatabaseClient>(
 		Ok(user) => user,
 	};
 
+	let filter: ItemFilter = match params.filter.as_deref().map(serde_json::from_str) {
+		None => ItemFilter::All,
+		Some(Ok(x)) => x,
+		Some(Err(error)) => {
+			return (
+				StatusCode::BAD_REQUEST,
+				Json(format!("Invalid filter: {error}")),
+			)
+				.into_response()
+		}
+	};
+
+	let sort: Option<ItemSort> = match params.sort.as_deref().map(serde_json::from_str) {
+		None => None,
+		Some(Ok(x)) => Some(x),
+		Some(Err(error)) => {
+			return (
+				StatusCode::BAD_REQUEST,
+				Json(format!("Invalid sort: {error}")),
+			)
+				.into_response()
+		}
+	};
+
 	let mut conn = match state.itemdb_client.new_connection().await {
 		Ok(x) => x,
 		Err(error) => {
@@ -416,9 +452,21 @@ pub(super) async fn list_items<Client: DatabaseClient>(
 		}
 	};
 
-	let total = match state.itemdb_client.count_items(&mut trans, class.id).await {
+	let total = match state
+		.itemdb_client
+		.count_items(&mut trans, class.id, &filter)
+		.await
+	{
 		Ok(x) => x,
 
+		Err(CountItemsError::BadFilter(error)) => {
+			return (
+				StatusCode::BAD_REQUEST,
+				Json(format!("Invalid filter: {error}")),
+			)
+				.into_response()
+		}
+
 		// In theory unreachable, but possible with unlucky timing
 		Err(CountItemsError::ClassNotFound) => {
 			return (StatusCode::NOT_FOUND, Json("Class not found")).into_response()
@@ -436,7 +484,14 @@ pub(super) async fn list_items<Client: DatabaseClient>(
 
 	match state
 		.itemdb_client
-		.list_items(&mut trans, class.id, paginate.skip, paginate.count)
+		.list_items(
+			&mut trans,
+			class.id,
+			&filter,
+			sort.as_ref(),
+			params.skip,
+			params.count,
+		)
 		.await
 	{
 		Ok(x) => {
@@ -465,7 +520,7 @@ pub(super) async fn list_items<Client: DatabaseClient>(
 				StatusCode::OK,
 				Json(ItemListResponse {
 					total,
-					skip: paginate.skip,
+					skip: params.skip,
 					items,
 				}),
 			)
@@ -476,6 +531,14 @@ pub(super) async fn list_items<Client: DatabaseClient>(
 			return (StatusCode::NOT_FOUND, Json("Class not found")).into_response()
 		}
 
+		Err(ListItemsError::BadFilter(error)) => {
+			return (
+				StatusCode::BAD_REQUEST,
+				Json(format!("Invalid filter or sort: {error}")),
+			)
+				.into_response()
+		}
+
 		Err(ListItemsError::DbError(error)) => {
 			error!(message = "Error in itemdb client", ?error);
 			return (
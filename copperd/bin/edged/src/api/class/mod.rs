@@ -4,15 +4,21 @@ use axum::{
 	routing::{delete, get, patch, post},
 	Router,
 };
+use copper_itemdb::{
+	filter::{FilterValue, ItemFilter, ItemSort, SortDirection},
+	UniqueConflict,
+};
 use utoipa::OpenApi;
 
 mod add_attribute;
+mod bulk_edit;
 mod del;
 mod get;
 mod items;
 mod rename;
 
 use add_attribute::*;
+use bulk_edit::*;
 use del::*;
 use get::*;
 use items::*;
@@ -21,14 +27,29 @@ use rename::*;
 #[derive(OpenApi)]
 #[openapi(
 	tags(),
-	paths(rename_class, del_class, get_class, add_attribute, list_items),
+	paths(
+		rename_class,
+		del_class,
+		get_class,
+		add_attribute,
+		list_items,
+		bulk_edit_items
+	),
 	components(schemas(
 		RenameClassRequest,
 		NewAttributeRequest,
 		ItemlistItemInfo,
 		ItemAttrData,
 		PrimaryAttrData,
-		ItemListResponse
+		ItemListResponse,
+		BulkEditRequest,
+		BulkEditSelection,
+		BulkEditResponse,
+		UniqueConflict,
+		ItemFilter,
+		FilterValue,
+		ItemSort,
+		SortDirection
 	))
 )]
 pub(super) struct ClassApi;
@@ -37,6 +58,7 @@ pub(super) fn router<Client: DatabaseClient + 'static>() -> Router<RouterState<C
 	Router::new()
 		.route("/:class_id", get(get_class))
 		.route("/:class_id/items", get(list_items))
+		.route("/:class_id/items/edit", post(bulk_edit_items))
 		.route("/:class_id", delete(del_class))
 		.route("/:class_id", patch(rename_class))
 		//
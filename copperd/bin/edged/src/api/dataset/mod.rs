@@ -4,6 +4,7 @@ use axum::{
 	routing::{delete, get, patch, post},
 	Router,
 };
+use copper_itemdb::{ItemSearchResult, SnippetPart};
 use utoipa::OpenApi;
 
 mod add;
@@ -12,6 +13,7 @@ mod del;
 mod get;
 mod list;
 mod rename;
+mod search;
 
 use add::*;
 use add_class::*;
@@ -19,6 +21,7 @@ use del::*;
 use get::*;
 use list::*;
 use rename::*;
+use search::*;
 
 #[derive(OpenApi)]
 #[openapi(
@@ -29,9 +32,16 @@ use rename::*;
 		del_dataset,
 		get_dataset,
 		add_class,
-		list_datasets
+		list_datasets,
+		search_dataset
 	),
-	components(schemas(RenameDatasetRequest, NewDatasetRequest, NewClassRequest))
+	components(schemas(
+		RenameDatasetRequest,
+		NewDatasetRequest,
+		NewClassRequest,
+		ItemSearchResult,
+		SnippetPart
+	))
 )]
 pub(super) struct DatasetApi;
 
@@ -44,4 +54,5 @@ pub(super) fn router<Client: DatabaseClient + 'static>() -> Router<RouterState<C
 		//
 		.route("/list", get(list_datasets))
 		.route("/:dataset_id/class", post(add_class))
+		.route("/:dataset_id/search", get(search_dataset))
 }
@@ -0,0 +1,129 @@
+use crate::database::base::client::DatabaseClient;
+use crate::RouterState;
+use axum::{
+	extract::{Path, Query, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use copper_itemdb::client::errors::{dataset::GetDatasetError, search::SearchItemsError};
+use serde::Deserialize;
+use sqlx::Acquire;
+use tracing::error;
+use utoipa::IntoParams;
+
+fn default_count() -> usize {
+	20
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub(super) struct SearchParams {
+	/// The text to search for
+	query: String,
+
+	#[serde(default)]
+	skip: i64,
+
+	#[serde(default = "default_count")]
+	count: usize,
+}
+
+/// Search the text attributes of all items in a dataset
+#[utoipa::path(
+	get,
+	path = "/{dataset_id}/search",
+	params(
+		("dataset_id", description = "Dataset id"),
+		SearchParams,
+	),
+	responses(
+		(status = 200, description = "Matching items, best match first", body = Vec<ItemSearchResult>),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Dataset not found"),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+pub(super) async fn search_dataset<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Path(dataset_id): Path<i64>,
+	Query(params): Query<SearchParams>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	let mut conn = match state.itemdb_client.new_connection().await {
+		Ok(x) => x,
+		Err(error) => {
+			error!(message = "Error in itemdb client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	let mut trans = match conn.begin().await {
+		Ok(y) => y,
+		Err(error) => {
+			error!(message = "Error in itemdb client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	match state
+		.itemdb_client
+		.get_dataset(&mut trans, dataset_id.into())
+		.await
+	{
+		Ok(x) => {
+			if x.owner != user.id {
+				return (StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response();
+			}
+		}
+
+		Err(GetDatasetError::NotFound) => {
+			return (StatusCode::NOT_FOUND, Json("Dataset not found")).into_response()
+		}
+
+		Err(GetDatasetError::DbError(error)) => {
+			error!(message = "Error in itemdb client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	return match state
+		.itemdb_client
+		.search_items(
+			&mut trans,
+			dataset_id.into(),
+			&params.query,
+			params.skip,
+			params.count,
+		)
+		.await
+	{
+		Ok(x) => (StatusCode::OK, Json(x)).into_response(),
+
+		Err(SearchItemsError::DbError(error)) => {
+			error!(message = "Error in itemdb client", ?error);
+			(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response()
+		}
+	};
+}
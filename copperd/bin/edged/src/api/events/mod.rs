@@ -0,0 +1,22 @@
+use crate::database::base::client::DatabaseClient;
+use crate::events::{UploadEvent, UserEvent};
+use crate::RouterState;
+use axum::{routing::get, Router};
+use copper_jobqueue::info::{JobEvent, QueuedJobStateKind};
+use utoipa::OpenApi;
+
+mod stream;
+
+use stream::*;
+
+#[derive(OpenApi)]
+#[openapi(
+	tags(),
+	paths(stream_events),
+	components(schemas(UserEvent, UploadEvent, JobEvent, QueuedJobStateKind))
+)]
+pub(super) struct EventsApi;
+
+pub(super) fn router<Client: DatabaseClient + 'static>() -> Router<RouterState<Client>> {
+	Router::new().route("/stream", get(stream_events))
+}
@@ -0,0 +1,66 @@
+use crate::database::base::client::DatabaseClient;
+use crate::events::UserEvent;
+use crate::RouterState;
+use axum::{
+	extract::State,
+	response::{
+		sse::{Event, KeepAlive},
+		IntoResponse, Response, Sse,
+	},
+};
+use axum_extra::extract::CookieJar;
+use futures::stream;
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Stream events about this user's jobs and uploads.
+///
+/// This is a stream of server-sent events.
+/// Job events are named `job` and upload events are named `upload`,
+/// both carry a json-encoded `UserEvent`.
+///
+/// If this client falls behind, it will miss some events and receive
+/// a `lagged` event with the number of events it missed.
+/// Clients should re-fetch anything they display when this happens.
+#[utoipa::path(
+	get,
+	path = "/stream",
+	responses(
+		(status = 200, description = "A stream of events", body = UserEvent, content_type = "text/event-stream"),
+		(status = 401, description = "Unauthorized"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn stream_events<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	let receiver = state.events.subscribe(user.id);
+	let events = stream::unfold(receiver, move |mut receiver| async move {
+		let event = match receiver.recv().await {
+			Ok(event) => Event::default()
+				.event(match event {
+					UserEvent::Job(_) => "job",
+					UserEvent::Upload { .. } => "upload",
+				})
+				.json_data(&event)
+				.unwrap(),
+
+			Err(RecvError::Lagged(n)) => Event::default().event("lagged").data(n.to_string()),
+			Err(RecvError::Closed) => return None,
+		};
+
+		return Some((Ok::<_, Infallible>(event), receiver));
+	});
+
+	return Sse::new(events)
+		.keep_alive(KeepAlive::default())
+		.into_response();
+}
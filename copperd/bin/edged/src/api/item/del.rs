@@ -0,0 +1,109 @@
+use crate::database::base::client::DatabaseClient;
+use axum::{
+	extract::{Path, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use copper_itemdb::client::errors::item::DeleteItemError;
+use itertools::Itertools;
+use sqlx::Acquire;
+use tracing::error;
+
+use super::get_owned_item;
+use crate::api::RouterState;
+
+/// Delete an item
+#[utoipa::path(
+	delete,
+	path = "/{item_idx}",
+	params(
+		("item_idx", description = "Item id"),
+	),
+	responses(
+		(status = 200, description = "Item deleted successfully"),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Item not found"),
+		(status = 409, description = "Item is referenced by other items", body = String),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+pub(super) async fn del_item<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Path(item_id): Path<i64>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	let mut conn = match state.itemdb_client.new_connection().await {
+		Ok(x) => x,
+		Err(error) => {
+			error!(message = "Error in itemdb client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	let mut trans = match conn.begin().await {
+		Ok(y) => y,
+		Err(error) => {
+			error!(message = "Error in itemdb client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	let item = match get_owned_item(&state, &mut trans, user.id, item_id.into()).await {
+		Ok(x) => x,
+		Err(x) => return x,
+	};
+
+	let res = state.itemdb_client.del_item(&mut trans, item.id).await;
+
+	return match res {
+		Ok(()) => match trans.commit().await {
+			Ok(()) => StatusCode::OK.into_response(),
+			Err(error) => {
+				error!(message = "Error while committing transaction", ?error);
+				return (
+					StatusCode::INTERNAL_SERVER_ERROR,
+					Json("Internal server error"),
+				)
+					.into_response();
+			}
+		},
+
+		// In theory unreachable, but possible with unlucky timing
+		Err(DeleteItemError::NotFound) => {
+			(StatusCode::NOT_FOUND, Json("Item not found")).into_response()
+		}
+
+		Err(DeleteItemError::Referenced { referencing_ids }) => (
+			StatusCode::CONFLICT,
+			Json(format!(
+				"This item is referenced by items {}",
+				referencing_ids.iter().map(|x| i64::from(*x)).join(", ")
+			)),
+		)
+			.into_response(),
+
+		Err(DeleteItemError::DbError(error)) => {
+			error!(message = "Error in itemdb client", ?error);
+			(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response()
+		}
+	};
+}
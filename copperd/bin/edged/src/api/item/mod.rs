@@ -1,17 +1,102 @@
 use crate::database::base::client::DatabaseClient;
 use crate::RouterState;
-use axum::routing::get;
-use axum::Router;
+use axum::{
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	routing::{delete, get, patch},
+	Json, Router,
+};
+use copper_itemdb::{
+	client::errors::{class::GetClassError, dataset::GetDatasetError, item::GetItemError},
+	ItemId, ItemInfo, UserId,
+};
+use tracing::error;
 use utoipa::OpenApi;
 
 mod attr;
+mod del;
+mod update;
 
 use attr::*;
+use del::*;
+use update::*;
 
 #[derive(OpenApi)]
-#[openapi(tags(), paths(get_attr), components(schemas()))]
+#[openapi(
+	tags(),
+	paths(get_attr, update_item, del_item),
+	components(schemas(UpdateItemRequest))
+)]
 pub(super) struct ItemApi;
 
 pub(super) fn router<Client: DatabaseClient + 'static>() -> Router<RouterState<Client>> {
-	Router::new().route("/:item_idx/attr/:attr_idx", get(get_attr))
+	Router::new()
+		.route("/:item_idx/attr/:attr_idx", get(get_attr))
+		.route("/:item_idx", patch(update_item))
+		.route("/:item_idx", delete(del_item))
+}
+
+/// Get an item, making sure that `user_id` owns its dataset.
+/// Returns a response we should send to the user on failure.
+async fn get_owned_item<Client: DatabaseClient>(
+	state: &RouterState<Client>,
+	trans: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+	user_id: UserId,
+	item_id: ItemId,
+) -> Result<ItemInfo, Response> {
+	let item = match state.itemdb_client.get_item(trans, item_id).await {
+		Ok(x) => x,
+
+		Err(GetItemError::NotFound) => {
+			return Err((StatusCode::NOT_FOUND, Json("Item not found")).into_response())
+		}
+
+		Err(GetItemError::DbError(error)) => {
+			error!(message = "Error in itemdb client", ?error);
+			return Err((
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response());
+		}
+	};
+
+	let class = match state.itemdb_client.get_class(trans, item.class).await {
+		Ok(x) => x,
+
+		Err(GetClassError::NotFound) => return Err(StatusCode::NOT_FOUND.into_response()),
+
+		Err(GetClassError::DbError(error)) => {
+			error!(message = "Error in itemdb client", ?error);
+			return Err((
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response());
+		}
+	};
+
+	match state.itemdb_client.get_dataset(trans, class.dataset).await {
+		Ok(x) => {
+			// We can only modify our own items
+			if x.owner != user_id {
+				return Err((StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response());
+			}
+		}
+
+		Err(GetDatasetError::NotFound) => {
+			return Err((StatusCode::NOT_FOUND, Json("Dataset not found")).into_response())
+		}
+
+		Err(GetDatasetError::DbError(error)) => {
+			error!(message = "Error in itemdb client", ?error);
+			return Err((
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response());
+		}
+	};
+
+	return Ok(item);
 }
@@ -0,0 +1,184 @@
+use crate::{api::pipeline::ApiInputAttrData, database::base::client::DatabaseClient};
+use axum::{
+	extract::{Path, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use copper_itemdb::{client::errors::item::SetItemAttrsError, AttrData, AttributeId};
+use itertools::Itertools;
+use serde::Deserialize;
+use sqlx::Acquire;
+use std::collections::BTreeMap;
+use tracing::error;
+use utoipa::ToSchema;
+
+use super::get_owned_item;
+use crate::api::RouterState;
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub(super) struct UpdateItemRequest {
+	/// The attributes to change.
+	/// Attributes set to `null` are cleared,
+	/// attributes that aren't given are left unchanged.
+	///
+	/// Blob attributes can't be set this way.
+	#[schema(value_type = BTreeMap<i64, ApiInputAttrData>)]
+	pub attributes: BTreeMap<AttributeId, Option<ApiInputAttrData>>,
+}
+
+/// Change some of an item's attributes
+#[utoipa::path(
+	patch,
+	path = "/{item_idx}",
+	params(
+		("item_idx", description = "Item id"),
+	),
+	responses(
+		(status = 200, description = "Item updated successfully"),
+		(status = 400, description = "Invalid attributes", body = String),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Item not found"),
+		(status = 409, description = "New values violate a `unique` constraint", body = String),
+		(status = 500, description = "Internal server error"),
+	)
+)]
+pub(super) async fn update_item<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Path(item_id): Path<i64>,
+	Json(payload): Json<UpdateItemRequest>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	let mut attributes = Vec::new();
+	for (attr_id, value) in payload.attributes {
+		let value = match value.map(AttrData::try_from).transpose() {
+			Ok(x) => x,
+			Err(()) => {
+				return (
+					StatusCode::BAD_REQUEST,
+					Json(format!(
+						"Attribute {} is a blob, which can't be set directly",
+						i64::from(attr_id)
+					)),
+				)
+					.into_response()
+			}
+		};
+
+		attributes.push((attr_id, value));
+	}
+
+	let mut conn = match state.itemdb_client.new_connection().await {
+		Ok(x) => x,
+		Err(error) => {
+			error!(message = "Error in itemdb client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	let mut trans = match conn.begin().await {
+		Ok(y) => y,
+		Err(error) => {
+			error!(message = "Error in itemdb client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	let item = match get_owned_item(&state, &mut trans, user.id, item_id.into()).await {
+		Ok(x) => x,
+		Err(x) => return x,
+	};
+
+	let res = state
+		.itemdb_client
+		.set_item_attrs(&mut trans, item.id, attributes)
+		.await;
+
+	return match res {
+		Ok(()) => match trans.commit().await {
+			Ok(()) => StatusCode::OK.into_response(),
+			Err(error) => {
+				error!(message = "Error while committing transaction", ?error);
+				return (
+					StatusCode::INTERNAL_SERVER_ERROR,
+					Json("Internal server error"),
+				)
+					.into_response();
+			}
+		},
+
+		Err(error) => set_item_attrs_error_response(error),
+	};
+}
+
+/// Turn a [`SetItemAttrsError`] into a response for the user
+pub(super) fn set_item_attrs_error_response(error: SetItemAttrsError) -> Response {
+	return match error {
+		// In theory unreachable, but possible with unlucky timing
+		SetItemAttrsError::NotFound => {
+			(StatusCode::NOT_FOUND, Json("Item not found")).into_response()
+		}
+
+		SetItemAttrsError::RepeatedAttribute => (
+			StatusCode::BAD_REQUEST,
+			Json("Multiple values were provided for one attribute"),
+		)
+			.into_response(),
+
+		SetItemAttrsError::ForeignAttribute => (
+			StatusCode::BAD_REQUEST,
+			Json("Tried to set an attribute from another class"),
+		)
+			.into_response(),
+
+		SetItemAttrsError::AttributeDataTypeMismatch => (
+			StatusCode::BAD_REQUEST,
+			Json("Tried to set an attribute to a value of the wrong type"),
+		)
+			.into_response(),
+
+		SetItemAttrsError::NoSuchReferencedItem => (
+			StatusCode::BAD_REQUEST,
+			Json("Tried to reference an item that doesn't exist"),
+		)
+			.into_response(),
+
+		SetItemAttrsError::NotNullViolated => (
+			StatusCode::BAD_REQUEST,
+			Json("Tried to clear an attribute that may not be empty"),
+		)
+			.into_response(),
+
+		SetItemAttrsError::UniqueViolated { conflicting_ids } => (
+			StatusCode::CONFLICT,
+			Json(format!(
+				"New values conflict with items {}",
+				conflicting_ids.iter().map(|x| i64::from(*x)).join(", ")
+			)),
+		)
+			.into_response(),
+
+		SetItemAttrsError::DbError(error) => {
+			error!(message = "Error in itemdb client", ?error);
+			(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response()
+		}
+	};
+}
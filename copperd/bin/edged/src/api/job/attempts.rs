@@ -0,0 +1,82 @@
+use crate::database::base::client::DatabaseClient;
+use crate::RouterState;
+use axum::{
+	extract::{Path, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use copper_jobqueue::{
+	base::errors::{GetJobAttemptsError, GetJobShortError},
+	id::QueuedJobId,
+};
+use tracing::error;
+
+/// Get every finished attempt of a job, oldest first
+#[utoipa::path(
+	get,
+	path = "/{job_id}/attempts",
+	params(
+		("job_id", description = "Job id"),
+	),
+	responses(
+		(status = 200, description = "This job's attempts", body = Vec<JobAttempt>),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Job not found"),
+		(status = 500, description = "Internal server error"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn get_job_attempts<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Path(job_id): Path<String>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	let job_id: QueuedJobId = job_id.as_str().into();
+
+	match state.jobqueue_client.get_job_short(&job_id).await {
+		Ok(x) => {
+			if x.owned_by != user.id {
+				return (StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response();
+			}
+		}
+
+		Err(GetJobShortError::NotFound) => {
+			return (StatusCode::NOT_FOUND, Json("Job not found")).into_response()
+		}
+
+		Err(GetJobShortError::DbError(error)) => {
+			error!(message = "Error in jobqueue client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	return match state.jobqueue_client.get_job_attempts(&job_id).await {
+		Ok(x) => (StatusCode::OK, Json(x)).into_response(),
+
+		Err(GetJobAttemptsError::NotFound) => {
+			return (StatusCode::NOT_FOUND, Json("Job not found")).into_response()
+		}
+
+		Err(GetJobAttemptsError::DbError(error)) => {
+			error!(message = "Error in jobqueue client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+}
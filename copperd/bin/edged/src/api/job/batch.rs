@@ -0,0 +1,64 @@
+use crate::database::base::client::DatabaseClient;
+use crate::RouterState;
+use axum::{
+	extract::{Path, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use copper_jobqueue::{base::errors::GetBatchError, id::BatchId};
+use tracing::error;
+
+/// Get a batch of jobs and its progress
+#[utoipa::path(
+	get,
+	path = "/batch/{batch_id}",
+	params(
+		("batch_id", description = "Batch id"),
+	),
+	responses(
+		(status = 200, description = "This batch, with the number of its jobs in each state", body = BatchInfo),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Batch not found"),
+		(status = 500, description = "Internal server error"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn get_batch<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Path(batch_id): Path<String>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	let batch_id: BatchId = batch_id.as_str().into();
+
+	return match state.jobqueue_client.get_batch(&batch_id).await {
+		Ok(x) => {
+			if x.owned_by != user.id {
+				return (StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response();
+			}
+
+			(StatusCode::OK, Json(x)).into_response()
+		}
+
+		Err(GetBatchError::NotFound) => {
+			return (StatusCode::NOT_FOUND, Json("Batch not found")).into_response()
+		}
+
+		Err(GetBatchError::DbError(error)) => {
+			error!(message = "Error in jobqueue client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+}
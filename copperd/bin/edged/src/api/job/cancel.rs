@@ -0,0 +1,91 @@
+use crate::database::base::client::DatabaseClient;
+use crate::RouterState;
+use axum::{
+	extract::{Path, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use copper_jobqueue::{
+	base::errors::{CancelJobError, GetJobShortError},
+	id::QueuedJobId,
+};
+use tracing::error;
+
+/// Cancel a queued or running job
+#[utoipa::path(
+	post,
+	path = "/{job_id}/cancel",
+	params(
+		("job_id", description = "Job id"),
+	),
+	responses(
+		(status = 200, description = "Job cancelled successfully"),
+		(status = 400, description = "This job has already finished", body = String),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Job not found"),
+		(status = 500, description = "Internal server error"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn cancel_job<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Path(job_id): Path<String>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	let job_id: QueuedJobId = job_id.as_str().into();
+
+	match state.jobqueue_client.get_job_short(&job_id).await {
+		Ok(x) => {
+			if x.owned_by != user.id {
+				return (StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response();
+			}
+		}
+
+		Err(GetJobShortError::NotFound) => {
+			return (StatusCode::NOT_FOUND, Json("Job not found")).into_response()
+		}
+
+		Err(GetJobShortError::DbError(error)) => {
+			error!(message = "Error in jobqueue client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	return match state.jobqueue_client.cancel_job(&job_id).await {
+		Ok(()) => StatusCode::OK.into_response(),
+
+		Err(CancelJobError::NotFound) => {
+			return (StatusCode::NOT_FOUND, Json("Job not found")).into_response()
+		}
+
+		Err(CancelJobError::AlreadyFinished) => {
+			return (
+				StatusCode::BAD_REQUEST,
+				Json("This job has already finished"),
+			)
+				.into_response()
+		}
+
+		Err(CancelJobError::DbError(error)) => {
+			error!(message = "Error in jobqueue client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+}
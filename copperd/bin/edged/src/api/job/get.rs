@@ -0,0 +1,246 @@
+use crate::database::base::client::DatabaseClient;
+use crate::{uploader::UploadJobId, RouterState};
+use axum::{
+	extract::{Path, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use copper_itemdb::{AttrData, ClassId, ItemId, UserId};
+use copper_jobqueue::{
+	base::errors::GetJobError,
+	id::{BatchId, QueuedJobId},
+	info::QueuedJobState,
+	scheduling::JobPriority,
+};
+use copper_piper::{json::PipelineJson, retry::RetryPolicy};
+use copper_util::HashType;
+use serde::Serialize;
+use smartstring::{LazyCompact, SmartString};
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+use tracing::error;
+use utoipa::ToSchema;
+
+/// A job's input, as we show it to users.
+/// This is the same as `AttrData`, but never exposes object keys.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "type")]
+pub(super) enum JobInputAttrData {
+	/// A block of text
+	Text {
+		#[schema(value_type = String)]
+		value: SmartString<LazyCompact>,
+	},
+
+	/// An integer
+	Integer {
+		/// The integer
+		value: i64,
+
+		/// If true, this integer must be non-negative
+		is_non_negative: bool,
+	},
+
+	/// A float
+	Float {
+		/// The float
+		value: f64,
+
+		/// If true, this float must be non-negative
+		is_non_negative: bool,
+	},
+
+	/// A boolean
+	Boolean { value: bool },
+
+	/// A checksum
+	Hash {
+		/// The type of this hash
+		hash_type: HashType,
+
+		/// The hash data
+		data: Vec<u8>,
+	},
+
+	/// Binary data
+	Blob {
+		/// The upload this data came from,
+		/// or `None` if it wasn't uploaded by this job's owner.
+		/// This upload may no longer exist.
+		#[schema(value_type = Option<String>)]
+		upload_id: Option<UploadJobId>,
+	},
+
+	/// A reference to an item in another class
+	Reference {
+		/// The item class this reference points to
+		#[schema(value_type = i64)]
+		class: ClassId,
+
+		/// The item
+		#[schema(value_type = i64)]
+		item: ItemId,
+	},
+}
+
+/// Everything we know about a job
+#[derive(Debug, Serialize, ToSchema)]
+pub(super) struct JobInfo {
+	/// A unique id for this job
+	#[schema(value_type = String)]
+	job_id: QueuedJobId,
+
+	/// The user that owns this job
+	#[schema(value_type = i64)]
+	owned_by: UserId,
+
+	/// The id of the pipeline this job runs, if we know it
+	pipeline_id: Option<i64>,
+
+	/// The batch this job is part of, if any
+	#[schema(value_type = Option<String>)]
+	batch_id: Option<BatchId>,
+
+	/// The state of this job.
+	/// If this job failed, this contains the full error.
+	state: QueuedJobState,
+
+	/// The pipeline this job runs, as it was when this job was created
+	pipeline: PipelineJson,
+
+	/// This job's input
+	#[schema(value_type = BTreeMap<String, JobInputAttrData>)]
+	input: BTreeMap<SmartString<LazyCompact>, JobInputAttrData>,
+
+	#[schema(value_type = String)]
+	created_at: OffsetDateTime,
+
+	#[schema(value_type = Option<String>)]
+	started_at: Option<OffsetDateTime>,
+
+	#[schema(value_type = Option<String>)]
+	finished_at: Option<OffsetDateTime>,
+
+	/// How this job is retried if it fails
+	retry_policy: Option<RetryPolicy>,
+
+	/// The number of times this job has been started
+	attempt: u32,
+
+	/// If this job failed and is waiting to be retried,
+	/// it will not run before this time.
+	#[schema(value_type = Option<String>)]
+	next_attempt_at: Option<OffsetDateTime>,
+
+	/// This job's priority
+	priority: JobPriority,
+}
+
+/// Get everything we know about a job
+#[utoipa::path(
+	get,
+	path = "/{job_id}",
+	params(
+		("job_id", description = "Job id"),
+	),
+	responses(
+		(status = 200, description = "This job", body = JobInfo),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Job not found"),
+		(status = 500, description = "Internal server error"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn get_job<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Path(job_id): Path<String>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	let job = match state.jobqueue_client.get_job(&job_id.as_str().into()).await {
+		Ok(x) => x,
+
+		Err(GetJobError::NotFound) => {
+			return (StatusCode::NOT_FOUND, Json("Job not found")).into_response()
+		}
+
+		Err(GetJobError::DbError(error)) => {
+			error!(message = "Error in jobqueue client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	if job.owned_by != user.id {
+		return (StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response();
+	}
+
+	let input = job
+		.input
+		.into_iter()
+		.map(|(k, v)| {
+			let v = match v {
+				AttrData::Text { value } => JobInputAttrData::Text { value },
+				AttrData::Boolean { value } => JobInputAttrData::Boolean { value },
+				AttrData::Hash { hash_type, data } => JobInputAttrData::Hash { hash_type, data },
+				AttrData::Reference { class, item } => JobInputAttrData::Reference { class, item },
+
+				AttrData::Integer {
+					value,
+					is_non_negative,
+				} => JobInputAttrData::Integer {
+					value,
+					is_non_negative,
+				},
+
+				AttrData::Float {
+					value,
+					is_non_negative,
+				} => JobInputAttrData::Float {
+					value,
+					is_non_negative,
+				},
+
+				AttrData::Blob { bucket, key } => JobInputAttrData::Blob {
+					upload_id: state
+						.uploader
+						.upload_id_from_object(job.owned_by, &bucket, &key),
+				},
+			};
+
+			(k, v)
+		})
+		.collect();
+
+	return (
+		StatusCode::OK,
+		Json(JobInfo {
+			job_id: job.job_id,
+			owned_by: job.owned_by,
+			pipeline_id: job.pipeline_id,
+			batch_id: job.batch_id,
+			state: job.state,
+			pipeline: job.pipeline,
+			input,
+			created_at: job.created_at,
+			started_at: job.started_at,
+			finished_at: job.finished_at,
+			retry_policy: job.retry_policy,
+			attempt: job.attempt,
+			next_attempt_at: job.next_attempt_at,
+			priority: job.priority,
+		}),
+	)
+		.into_response();
+}
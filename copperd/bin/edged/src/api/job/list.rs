@@ -8,24 +8,91 @@ use axum::{
 	response::{IntoResponse, Response},
 };
 use axum_extra::extract::CookieJar;
-use copper_jobqueue::base::errors::GetUserJobsError;
+use copper_jobqueue::{
+	base::errors::GetUserJobsError,
+	filter::{JobFilter, JobSortBy, SortDirection},
+	info::QueuedJobStateKind,
+};
 use serde::Deserialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 use tracing::error;
 use utoipa::IntoParams;
 
 #[derive(Debug, Deserialize, IntoParams)]
-pub(super) struct PaginateParams {
+pub(super) struct ListJobsParams {
 	skip: i64,
 	count: i64,
+
+	/// Only list jobs in these states, separated by commas
+	/// (for example, `FailedRunning,BuildError`)
+	#[serde(default)]
+	state: Option<String>,
+
+	/// Only list jobs that run this pipeline
+	#[serde(default)]
+	pipeline: Option<i64>,
+
+	/// Only list jobs created at or after this time (RFC 3339)
+	#[serde(default)]
+	created_after: Option<String>,
+
+	/// Only list jobs created before this time (RFC 3339)
+	#[serde(default)]
+	created_before: Option<String>,
+
+	/// Only list jobs whose id starts with this string
+	#[serde(default)]
+	id_prefix: Option<String>,
+
+	/// The field to sort jobs by
+	#[serde(default)]
+	#[param(inline)]
+	sort_by: JobSortBy,
+
+	/// The order to list jobs in
+	#[serde(default)]
+	#[param(inline)]
+	direction: SortDirection,
+}
+
+impl ListJobsParams {
+	/// Make a [`JobFilter`] from these params.
+	/// Returns a message for the user if these params are invalid.
+	fn filter(&self) -> Result<JobFilter, String> {
+		let mut states = Vec::new();
+		for name in self.state.iter().flat_map(|x| x.split(',')) {
+			match QueuedJobStateKind::from_name(name.trim()) {
+				Some(x) => states.push(x),
+				None => return Err(format!("Invalid job state `{name}`")),
+			}
+		}
+
+		let parse_time = |name: &str, value: &Option<String>| {
+			value
+				.as_ref()
+				.map(|x| OffsetDateTime::parse(x, &Rfc3339))
+				.transpose()
+				.map_err(|e| format!("Invalid `{name}`: {e}"))
+		};
+
+		return Ok(JobFilter {
+			states,
+			pipeline_id: self.pipeline,
+			created_after: parse_time("created_after", &self.created_after)?,
+			created_before: parse_time("created_before", &self.created_before)?,
+			id_prefix: self.id_prefix.as_ref().map(|x| x.into()),
+		});
+	}
 }
 
 /// List the logged in user's jobs
 #[utoipa::path(
 	get,
 	path = "/list",
-	params(PaginateParams),
+	params(ListJobsParams),
 	responses(
-		(status = 200, description = "This user's jobs, ordered by age", body = QueuedJobInfoList),
+		(status = 200, description = "This user's jobs that match the given filter", body = QueuedJobInfoList),
+		(status = 400, description = "Invalid filter", body = String),
 		(status = 401, description = "Unauthorized"),
 	),
 	security(
@@ -35,16 +102,28 @@ pub(super) struct PaginateParams {
 pub(super) async fn list_jobs<Client: DatabaseClient>(
 	jar: CookieJar,
 	State(state): State<RouterState<Client>>,
-	Query(paginate): Query<PaginateParams>,
+	Query(params): Query<ListJobsParams>,
 ) -> Response {
 	let user = match state.auth.auth_or_logout(&state, &jar).await {
 		Err(x) => return x,
 		Ok(user) => user,
 	};
 
+	let filter = match params.filter() {
+		Ok(x) => x,
+		Err(message) => return (StatusCode::BAD_REQUEST, Json(message)).into_response(),
+	};
+
 	return match state
 		.jobqueue_client
-		.get_user_jobs(user.id, paginate.skip, paginate.count)
+		.get_user_jobs(
+			user.id,
+			&filter,
+			params.sort_by,
+			params.direction,
+			params.skip,
+			params.count,
+		)
 		.await
 	{
 		Ok(x) => (StatusCode::OK, Json(x)).into_response(),
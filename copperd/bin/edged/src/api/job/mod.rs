@@ -1,22 +1,82 @@
 use crate::database::base::client::DatabaseClient;
 use crate::RouterState;
-use axum::{routing::get, Router};
-use copper_jobqueue::info::{QueuedJobInfoList, QueuedJobInfoShort, QueuedJobState};
+use axum::{
+	routing::{get, post},
+	Router,
+};
+use copper_jobqueue::{
+	filter::{JobSortBy, SortDirection},
+	info::{
+		BatchInfo, JobAttempt, QueuedJobInfoList, QueuedJobInfoShort, QueuedJobState,
+		QueuedJobStateKind,
+	},
+	scheduling::JobPriority,
+};
+use copper_piper::{
+	data::PipeDataStub,
+	trace::{NodeTrace, NodeTraceStatus, PipeDataSummary},
+};
 use utoipa::OpenApi;
 
+mod attempts;
+mod batch;
+mod cancel;
+mod get;
 mod list;
+mod rerun;
+mod trace;
 
+use attempts::*;
+use batch::*;
+use cancel::*;
+use get::*;
 use list::*;
+use rerun::*;
+use trace::*;
 
 #[allow(non_camel_case_types)]
 #[derive(OpenApi)]
 #[openapi(
 	tags(),
-	paths(list_jobs),
-	components(schemas(QueuedJobInfoList, QueuedJobInfoShort, QueuedJobState))
+	paths(
+		list_jobs,
+		get_job,
+		get_job_trace,
+		get_job_attempts,
+		cancel_job,
+		rerun_job,
+		get_batch
+	),
+	components(schemas(
+		QueuedJobInfoList,
+		BatchInfo,
+		QueuedJobInfoShort,
+		JobInfo,
+		JobInputAttrData,
+		RerunJobRequest,
+		RerunJobResponse,
+		RerunPipeline,
+		QueuedJobState,
+		QueuedJobStateKind,
+		JobSortBy,
+		SortDirection,
+		JobAttempt,
+		JobPriority,
+		NodeTrace,
+		NodeTraceStatus,
+		PipeDataSummary,
+		PipeDataStub
+	))
 )]
 pub(super) struct JobApi;
 
 pub(super) fn router<Client: DatabaseClient + 'static>() -> Router<RouterState<Client>> {
-	Router::new().route("/list", get(list_jobs))
+	Router::new()
+		.route("/list", get(list_jobs))
+		.route("/batch/:batch_id", get(get_batch))
+		.route("/:job_id", get(get_job))
+		.route("/:job_id/trace", get(get_job_trace))
+		.route("/:job_id/attempts", get(get_job_attempts))
+		.route("/:job_id/cancel", post(cancel_job))
+		.route("/:job_id/rerun", post(rerun_job))
 }
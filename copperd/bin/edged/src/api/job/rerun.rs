@@ -0,0 +1,350 @@
+use axum::{
+	extract::{Path, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use copper_itemdb::{AttrData, UserId};
+use copper_jobqueue::{
+	base::errors::{GetJobError, GetJobShortError},
+	info::NewJob,
+	scheduling::JobPriority,
+};
+use copper_piper::retry::RetryPolicy;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use smartstring::{LazyCompact, SmartString};
+use std::collections::BTreeMap;
+use tracing::error;
+use utoipa::ToSchema;
+
+use crate::{
+	api::pipeline::{
+		add_job_error_response, check_job_id, check_retry_policy, convert_input, unassign_uploads,
+		ApiInputAttrData,
+	},
+	database::base::{client::DatabaseClient, errors::pipeline::GetPipelineError},
+	uploader::{errors::UploadReassignError, UploadJobId},
+	RouterState,
+};
+
+/// The length of the random suffix of generated job ids
+const RERUN_ID_LENGTH: usize = 8;
+
+/// Which pipeline a re-run job should run
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+pub(super) enum RerunPipeline {
+	/// Run the pipeline exactly as it was when
+	/// the original job was created
+	#[default]
+	Snapshot,
+
+	/// Run the current version of the original job's pipeline
+	Current,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub(super) struct RerunJobRequest {
+	/// A unique id for the new job.
+	/// This may not start with `schedule-`.
+	/// If this is `None`, we generate one.
+	#[schema(value_type = Option<String>)]
+	#[serde(default)]
+	pub job_id: Option<SmartString<LazyCompact>>,
+
+	/// Which pipeline the new job should run
+	#[serde(default)]
+	pub pipeline: RerunPipeline,
+
+	/// Inputs to replace.
+	/// Inputs that aren't given here are copied from the original job.
+	#[schema(value_type = BTreeMap<String, ApiInputAttrData>)]
+	#[serde(default)]
+	pub input: BTreeMap<SmartString<LazyCompact>, ApiInputAttrData>,
+
+	/// How the new job should be retried if it fails.
+	/// If this is `None`, we use the original job's retry policy
+	/// (or the current pipeline's, if `pipeline` is `Current`).
+	#[serde(default)]
+	pub retry: Option<RetryPolicy>,
+
+	/// The new job's priority.
+	/// If this is `None`, we use the original job's priority.
+	#[serde(default)]
+	pub priority: Option<JobPriority>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub(super) struct RerunJobResponse {
+	/// The new job's id
+	#[schema(value_type = String)]
+	pub job_id: SmartString<LazyCompact>,
+}
+
+/// Queue a new job with a finished job's pipeline and inputs.
+///
+/// Uploaded inputs are kept for a while after a job finishes, so that
+/// it may be re-run. These are only tracked in memory: after edged
+/// restarts, uploaded inputs can't be reused and must be given in `input`.
+#[utoipa::path(
+	post,
+	path = "/{job_id}/rerun",
+	params(
+		("job_id", description = "The job to re-run"),
+	),
+	responses(
+		(status = 200, description = "Job queued successfully", body = RerunJobResponse),
+		(status = 400, description = "Job isn't finished, its inputs are no longer available, or job id or retry policy is invalid", body = String),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Job or pipeline not found"),
+		(status = 409, description = "Job id already exists"),
+		(status = 429, description = "Too many jobs, try again after `Retry-After` seconds", body = String),
+		(status = 500, description = "Internal server error"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn rerun_job<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Path(job_id): Path<String>,
+	Json(payload): Json<RerunJobRequest>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	if let Some(x) = check_retry_policy(payload.retry.as_ref()) {
+		return x;
+	}
+
+	let job = match state.jobqueue_client.get_job(&job_id.as_str().into()).await {
+		Ok(x) => x,
+
+		Err(GetJobError::NotFound) => {
+			return (StatusCode::NOT_FOUND, Json("Job not found")).into_response()
+		}
+
+		Err(GetJobError::DbError(error)) => {
+			error!(message = "Error in jobqueue client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	if job.owned_by != user.id {
+		return (StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response();
+	}
+
+	// A job that may still run shouldn't share its inputs
+	if !job.state.is_finished() {
+		return (
+			StatusCode::BAD_REQUEST,
+			Json("Only finished jobs may be re-run"),
+		)
+			.into_response();
+	}
+
+	let (pipeline, retry_policy) = match payload.pipeline {
+		RerunPipeline::Snapshot => (job.pipeline, job.retry_policy),
+
+		RerunPipeline::Current => {
+			let pipeline_id = match job.pipeline_id {
+				Some(x) => x,
+				None => {
+					return (
+						StatusCode::BAD_REQUEST,
+						Json("This job's pipeline is unknown, re-run its snapshot instead"),
+					)
+						.into_response()
+				}
+			};
+
+			let pipe = match state.db_client.get_pipeline(pipeline_id.into()).await {
+				Ok(Some(pipe)) => pipe,
+				Ok(None) => {
+					return (StatusCode::NOT_FOUND, Json("Pipeline not found")).into_response()
+				}
+				Err(GetPipelineError::DbError(error)) => {
+					error!(
+						message = "Database error while getting pipeline",
+						?pipeline_id,
+						?error,
+					);
+					return (
+						StatusCode::INTERNAL_SERVER_ERROR,
+						Json("Internal server error"),
+					)
+						.into_response();
+				}
+			};
+
+			if pipe.owned_by != user.id {
+				return (StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response();
+			}
+
+			let retry = pipe.data.retry.clone();
+			(pipe.data, retry)
+		}
+	};
+
+	let new_job_id: SmartString<LazyCompact> = match payload.job_id {
+		Some(x) => {
+			if let Some(x) = check_job_id(&x) {
+				return x;
+			}
+			x
+		}
+		None => {
+			let suffix: String = rand::thread_rng()
+				.sample_iter(&Alphanumeric)
+				.take(RERUN_ID_LENGTH)
+				.map(char::from)
+				.collect();
+			format!("{}-rerun-{suffix}", job.job_id.as_str()).into()
+		}
+	};
+
+	// Don't move uploads to a job we can't create
+	match state
+		.jobqueue_client
+		.get_job_short(&new_job_id.as_str().into())
+		.await
+	{
+		Err(GetJobShortError::NotFound) => {}
+		Ok(_) => return StatusCode::CONFLICT.into_response(),
+		Err(GetJobShortError::DbError(error)) => {
+			error!(message = "Error in jobqueue client", ?error);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	}
+
+	// Convert new inputs first, so that bad input
+	// doesn't move the original job's uploads.
+	let mut assigned_uploads: Vec<UploadJobId> = Vec::new();
+	let mut converted_input = match convert_input(
+		&state,
+		user.id,
+		&new_job_id,
+		payload.input,
+		&mut assigned_uploads,
+	)
+	.await
+	{
+		Ok(x) => x,
+		Err(x) => {
+			unassign_uploads(&state, user.id, &assigned_uploads).await;
+			return x;
+		}
+	};
+
+	// Copy the inputs we weren't given, moving their uploads to the new job
+	let mut reassigned_uploads: Vec<(UploadJobId, SmartString<LazyCompact>)> = Vec::new();
+	for (k, v) in job.input {
+		if converted_input.contains_key(&k) {
+			continue;
+		}
+
+		if let AttrData::Blob { bucket, key } = &v {
+			let res = match state.uploader.upload_id_from_object(user.id, bucket, key) {
+				None => Err(UploadReassignError::BadUpload),
+				Some(upload_id) => state
+					.uploader
+					.reassign_job(user.id, &upload_id, &new_job_id)
+					.await
+					.map(|previous| reassigned_uploads.push((upload_id, previous))),
+			};
+
+			if let Err(error) = res {
+				unassign_uploads(&state, user.id, &assigned_uploads).await;
+				restore_uploads(&state, user.id, &reassigned_uploads).await;
+
+				let message = match error {
+					UploadReassignError::InUse => format!("Input {k} is in use by another job"),
+					UploadReassignError::BadUpload | UploadReassignError::NotMyUpload => {
+						format!("Input {k} is no longer available and must be replaced")
+					}
+					UploadReassignError::DbError(error) => {
+						error!(
+							message = "Database error while reassigning upload",
+							input = ?k,
+							?error
+						);
+						return (
+							StatusCode::INTERNAL_SERVER_ERROR,
+							Json("Internal server error"),
+						)
+							.into_response();
+					}
+				};
+
+				return (StatusCode::BAD_REQUEST, Json(message)).into_response();
+			}
+		}
+
+		converted_input.insert(k, v);
+	}
+
+	let res = state
+		.jobqueue_client
+		.add_job(
+			user.id,
+			NewJob {
+				job_id: new_job_id.as_str().into(),
+				pipeline_id: job.pipeline_id,
+				pipeline: &pipeline,
+				input: &converted_input,
+				retry_policy: payload.retry.as_ref().or(retry_policy.as_ref()),
+				priority: payload.priority.unwrap_or(job.priority),
+			},
+			&state.config.job_limits(),
+		)
+		.await;
+
+	return match res {
+		Ok(_) => (
+			StatusCode::OK,
+			Json(RerunJobResponse { job_id: new_job_id }),
+		)
+			.into_response(),
+		Err(error) => {
+			// If this job wasn't queued, give the original job its uploads back
+			unassign_uploads(&state, user.id, &assigned_uploads).await;
+			restore_uploads(&state, user.id, &reassigned_uploads).await;
+			add_job_error_response(error)
+		}
+	};
+}
+
+/// Give uploads moved by [`rerun_job`] back to the jobs they came from
+async fn restore_uploads<Client: DatabaseClient>(
+	state: &RouterState<Client>,
+	user_id: UserId,
+	reassigned_uploads: &[(UploadJobId, SmartString<LazyCompact>)],
+) {
+	for (upload_id, previous) in reassigned_uploads {
+		let res = state
+			.uploader
+			.reassign_job(user_id, upload_id, previous)
+			.await;
+
+		if let Err(error) = res {
+			error!(
+				message = "Could not restore reassigned upload",
+				?upload_id,
+				?previous,
+				?error
+			);
+		}
+	}
+}
@@ -7,6 +7,7 @@ use copper_itemdb::client::ItemdbClient;
 use copper_itemdb::{AttrDataStub, AttributeInfo, AttributeOptions, ClassInfo, DatasetInfo};
 use copper_jobqueue::base::client::JobQueueClient;
 use copper_jobqueue::info::QueuedJobCounts;
+use copper_piper::base::NodeDispatcher;
 use copper_util::s3client::S3Client;
 use copper_util::HashType;
 use std::net::SocketAddr;
@@ -18,18 +19,22 @@ use utoipa_swagger_ui::SwaggerUi;
 use crate::auth::AuthHelper;
 use crate::config::EdgedConfig;
 use crate::database::base::client::DatabaseClient;
+use crate::events::EventBus;
 use crate::uploader::Uploader;
 
 mod attribute;
 mod class;
 mod dataset;
+mod events;
 mod item;
 mod job;
 mod login;
 mod logout;
 mod pipeline;
+mod schedule;
 mod storage;
 mod user;
+mod webhook;
 
 use login::*;
 use logout::*;
@@ -57,6 +62,11 @@ pub struct RouterState<Client: DatabaseClient> {
 	pub auth: Arc<AuthHelper<Client>>,
 	pub s3_client: Arc<S3Client>,
 	pub uploader: Arc<Uploader>,
+	pub events: Arc<EventBus>,
+
+	/// The nodes pipelines may use.
+	/// This should contain the same nodes as piper's dispatcher.
+	pub dispatcher: Arc<NodeDispatcher>,
 }
 
 // We need to impl this manually, since `DatabaseClient`
@@ -71,6 +81,8 @@ impl<Client: DatabaseClient> Clone for RouterState<Client> {
 			jobqueue_client: self.jobqueue_client.clone(),
 			s3_client: self.s3_client.clone(),
 			uploader: self.uploader.clone(),
+			events: self.events.clone(),
+			dispatcher: self.dispatcher.clone(),
 		}
 	}
 }
@@ -84,9 +96,12 @@ impl<Client: DatabaseClient> Clone for RouterState<Client> {
 		(path = "/class", api = class::ClassApi),
 		(path = "/attribute", api = attribute::AttributeApi),
 		(path = "/pipeline", api = pipeline::PipelineApi),
+		(path = "/schedule", api = schedule::ScheduleApi),
 		(path = "/storage", api = storage::StorageApi),
 		(path = "/job", api = job::JobApi),
+		(path = "/events", api = events::EventsApi),
 		(path = "/item", api = item::ItemApi),
+		(path = "/webhook", api = webhook::WebhookApi),
 	),
 	tags(
 		(name = "Copper", description = "Copper edge daemon")
@@ -105,9 +120,12 @@ pub(super) fn router<Client: DatabaseClient + 'static>(state: RouterState<Client
 		.nest("/class", class::router())
 		.nest("/attribute", attribute::router())
 		.nest("/pipeline", pipeline::router())
+		.nest("/schedule", schedule::router())
 		.nest("/storage", storage::router())
 		.nest("/job", job::router())
+		.nest("/events", events::router())
 		.nest("/item", item::router())
+		.nest("/webhook", webhook::router())
 		//
 		.route("/login", post(try_login))
 		.route("/logout", post(logout))
@@ -8,6 +8,7 @@ use axum::{
 use axum_extra::extract::CookieJar;
 use copper_piper::json::PipelineJson;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use tracing::error;
 use utoipa::ToSchema;
 
@@ -26,6 +27,7 @@ pub(super) struct NewPipelineRequest {
 	responses(
 		(status = 200, description = "Pipeline created successfully", body = PipelineInfo),
 		(status = 400, description = "Bad request", body = String),
+		(status = 422, description = "Invalid pipeline", body = Vec<PipelineProblem>),
 		(status = 401, description = "Unauthorized"),
 		(status = 500, description = "Internal server error"),
 	),
@@ -44,6 +46,13 @@ pub(super) async fn add_pipeline<Client: DatabaseClient>(
 		Ok(user) => user,
 	};
 
+	let problems = payload
+		.pipeline
+		.validate(&state.dispatcher, &BTreeMap::new());
+	if !problems.is_empty() {
+		return (StatusCode::UNPROCESSABLE_ENTITY, Json(problems)).into_response();
+	}
+
 	let res = state
 		.db_client
 		.add_pipeline(user.id, &payload.name, &payload.pipeline)
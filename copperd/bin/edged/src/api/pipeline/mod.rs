@@ -5,9 +5,16 @@ use axum::{
 	Router,
 };
 use copper_edged::PipelineInfo;
+use copper_jobqueue::scheduling::JobPriority;
 use copper_piper::{
-	base::NodeParameterValue,
+	base::{
+		NodeInfo, NodeInputSpec, NodeInputs, NodeParameterSpec, NodeParameterType,
+		NodeParameterValue, NodePortType, NodePorts, RunNodeErrorKind,
+	},
+	data::PipeDataStub,
 	json::{EdgeJson, InputPort, NodeJson, NodeJsonPosition, OutputPort, PipelineJson},
+	retry::{RetryBackoff, RetryPolicy},
+	validate::PipelineProblem,
 };
 use utoipa::OpenApi;
 
@@ -15,15 +22,27 @@ mod add;
 mod del;
 mod get;
 mod list;
+mod nodes;
 mod run;
+mod run_batch;
 mod update;
+mod validate;
 
 use add::*;
 use del::*;
 use get::*;
 use list::*;
+use nodes::*;
 use run::*;
+use run_batch::*;
 use update::*;
+use validate::*;
+
+// Schedules and reruns take the same input as runs
+pub(super) use run::{
+	add_job_error_response, check_job_id, check_retry_policy, convert_input, unassign_uploads,
+	ApiInputAttrData,
+};
 
 #[allow(non_camel_case_types)]
 #[derive(OpenApi)]
@@ -36,6 +55,10 @@ use update::*;
 		get_pipeline,
 		list_pipelines,
 		run_pipeline,
+		run_batch,
+		list_nodes,
+		get_node_ports,
+		validate_pipeline,
 	),
 	components(schemas(
 		PipelineJson,
@@ -47,9 +70,25 @@ use update::*;
 		NewPipelineRequest,
 		UpdatePipelineRequest,
 		RunPipelineRequest,
+		RunBatchRequest,
+		RunBatchResponse,
 		NodeParameterValue,
 		PipelineInfo,
-		ApiInputAttrData
+		ApiInputAttrData,
+		NodeInfo,
+		NodeParameterSpec,
+		NodeParameterType,
+		NodePorts,
+		NodeInputs,
+		NodeInputSpec,
+		NodePortType,
+		PipeDataStub,
+		NodePortsRequest,
+		PipelineProblem,
+		RetryPolicy,
+		RetryBackoff,
+		RunNodeErrorKind,
+		JobPriority,
 	))
 )]
 pub(super) struct PipelineApi;
@@ -58,8 +97,12 @@ pub(super) fn router<Client: DatabaseClient + 'static>() -> Router<RouterState<C
 	Router::new()
 		.route("/", post(add_pipeline))
 		.route("/list", get(list_pipelines))
+		.route("/nodes", get(list_nodes))
+		.route("/nodes/ports", post(get_node_ports))
+		.route("/validate", post(validate_pipeline))
 		.route("/:pipeline_id", get(get_pipeline))
 		.route("/:pipeline_id", delete(del_pipeline))
 		.route("/:pipeline_id", patch(update_pipeline))
 		.route("/:pipeline_id/run", post(run_pipeline))
+		.route("/:pipeline_id/run_batch", post(run_batch))
 }
@@ -0,0 +1,97 @@
+use crate::database::base::client::DatabaseClient;
+use crate::RouterState;
+use axum::{
+	extract::State,
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use copper_piper::base::NodeParameterValue;
+use serde::Deserialize;
+use smartstring::{LazyCompact, SmartString};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+/// List all node types pipelines may use
+#[utoipa::path(
+	get,
+	path = "/nodes",
+	responses(
+		(status = 200, description = "All available node types", body = Vec<NodeInfo>),
+		(status = 401, description = "Unauthorized"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn list_nodes<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+) -> Response {
+	if let Err(x) = state.auth.auth_or_logout(&state, &jar).await {
+		return x;
+	}
+
+	return (StatusCode::OK, Json(state.dispatcher.describe_nodes())).into_response();
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub(super) struct NodePortsRequest {
+	/// The type of node to get ports for
+	#[schema(value_type = String)]
+	node_type: SmartString<LazyCompact>,
+
+	/// This node's parameters
+	#[schema(value_type = BTreeMap<String, NodeParameterValue>)]
+	params: BTreeMap<SmartString<LazyCompact>, NodeParameterValue>,
+}
+
+/// Get the ports of a node with the given parameters
+#[utoipa::path(
+	post,
+	path = "/nodes/ports",
+	responses(
+		(status = 200, description = "This node's ports", body = NodePorts),
+		(status = 400, description = "Bad node type or parameters", body = String),
+		(status = 401, description = "Unauthorized"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn get_node_ports<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Json(payload): Json<NodePortsRequest>,
+) -> Response {
+	if let Err(x) = state.auth.auth_or_logout(&state, &jar).await {
+		return x;
+	}
+
+	match state
+		.dispatcher
+		.check_params(&payload.node_type, &payload.params)
+	{
+		None => {
+			return (
+				StatusCode::BAD_REQUEST,
+				Json(format!("invalid node type `{}`", payload.node_type)),
+			)
+				.into_response()
+		}
+		Some(Err(e)) => {
+			return (StatusCode::BAD_REQUEST, Json(format!("{}", e))).into_response();
+		}
+		Some(Ok(())) => {}
+	}
+
+	return match state
+		.dispatcher
+		.node_ports(&payload.node_type, &payload.params)
+		.unwrap()
+	{
+		Ok(ports) => (StatusCode::OK, Json(ports)).into_response(),
+		Err(e) => (StatusCode::BAD_REQUEST, Json(format!("{}", e))).into_response(),
+	};
+}
@@ -1,29 +1,36 @@
 use axum::{
 	extract::{Path, State},
-	http::StatusCode,
-	response::{IntoResponse, Response},
+	http::{header::RETRY_AFTER, StatusCode},
+	response::{AppendHeaders, IntoResponse, Response},
 	Json,
 };
 use axum_extra::extract::CookieJar;
-use copper_itemdb::{AttrData, ClassId, ItemId};
-use copper_jobqueue::base::errors::AddJobError;
+use copper_itemdb::{AttrData, ClassId, ItemId, UserId};
+use copper_jobqueue::{base::errors::AddJobError, info::NewJob, scheduling::JobPriority};
+use copper_piper::retry::RetryPolicy;
 use copper_util::HashType;
 use serde::Deserialize;
 use smartstring::{LazyCompact, SmartString};
 use std::collections::BTreeMap;
+use std::time::Duration;
 use tracing::error;
 use utoipa::ToSchema;
 
 use crate::{
 	database::base::{client::DatabaseClient, errors::pipeline::GetPipelineError},
+	scheduler::SCHEDULED_JOB_PREFIX,
 	uploader::{errors::UploadAssignError, GotJobKey, UploadJobId},
 	RouterState,
 };
 
+/// The retry hint we send when a user has too many unfinished jobs.
+/// We can't know when one of their jobs will finish, so this is a guess.
+const TOO_MANY_JOBS_RETRY_AFTER: Duration = Duration::from_secs(60);
+
 /// Attribute data, provided by the user by api calls.
 #[derive(Debug, Clone, Deserialize, ToSchema)]
 #[serde(tag = "type")]
-pub(super) enum ApiInputAttrData {
+pub(in crate::api) enum ApiInputAttrData {
 	/// A block of text
 	Text {
 		#[schema(value_type = String)]
@@ -63,7 +70,8 @@ pub(super) enum ApiInputAttrData {
 	/// Binary data we uploaded previously
 	Blob {
 		/// The upload id. This must only be used once,
-		/// uploaded files are deleted once their job is done.
+		/// uploaded files are deleted some time after their job is done.
+		/// Until then, they may be used again by re-running that job.
 		///
 		/// Also, note that we _never_ send the S3 key to the
 		/// client---only the upload id as a proxy. This makes sure
@@ -126,12 +134,22 @@ impl TryFrom<ApiInputAttrData> for AttrData {
 
 #[derive(Deserialize, ToSchema, Debug)]
 pub(super) struct RunPipelineRequest {
-	/// A unique id for this job
+	/// A unique id for this job.
+	/// This may not start with `schedule-`.
 	#[schema(value_type = String)]
 	pub job_id: SmartString<LazyCompact>,
 
 	#[schema(value_type = BTreeMap<String, ApiInputAttrData>)]
 	pub input: BTreeMap<SmartString<LazyCompact>, ApiInputAttrData>,
+
+	/// How this job should be retried if it fails.
+	/// If this is `None`, we use the pipeline's retry policy.
+	#[serde(default)]
+	pub retry: Option<RetryPolicy>,
+
+	/// This job's priority, relative to this user's other jobs
+	#[serde(default)]
+	pub priority: JobPriority,
 }
 
 /// Start a pipeline job
@@ -143,9 +161,10 @@ pub(super) struct RunPipelineRequest {
 	),
 	responses(
 		(status = 200, description = "Job queued successfully"),
+		(status = 400, description = "Invalid input, job id, or retry policy", body = String),
 		(status = 401, description = "Unauthorized"),
 		(status = 409, description = "Job id already exists"),
-		(status = 429, description = "Job queue is full"),
+		(status = 429, description = "Too many jobs, try again after `Retry-After` seconds", body = String),
 	),
 	security(
 		("bearer" = []),
@@ -162,6 +181,14 @@ pub(super) async fn run_pipeline<Client: DatabaseClient>(
 		Ok(user) => user,
 	};
 
+	if let Some(x) = check_retry_policy(payload.retry.as_ref()) {
+		return x;
+	}
+
+	if let Some(x) = check_job_id(&payload.job_id) {
+		return x;
+	}
+
 	let pipe = match state.db_client.get_pipeline(pipeline_id.into()).await {
 		Ok(Some(pipe)) => pipe,
 		Ok(None) => return StatusCode::NOT_FOUND.into_response(),
@@ -184,8 +211,67 @@ pub(super) async fn run_pipeline<Client: DatabaseClient>(
 		return (StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response();
 	}
 
+	let mut assigned_uploads: Vec<UploadJobId> = Vec::new();
+	let converted_input = match convert_input(
+		&state,
+		user.id,
+		&payload.job_id,
+		payload.input,
+		&mut assigned_uploads,
+	)
+	.await
+	{
+		Ok(x) => x,
+		Err(x) => {
+			unassign_uploads(&state, user.id, &assigned_uploads).await;
+			return x;
+		}
+	};
+
+	let res = state
+		.jobqueue_client
+		.add_job(
+			user.id,
+			NewJob {
+				job_id: payload.job_id.as_str().into(),
+				pipeline_id: Some(i64::from(pipe.id)),
+				pipeline: &pipe.data,
+				input: &converted_input,
+				retry_policy: payload.retry.as_ref().or(pipe.data.retry.as_ref()),
+				priority: payload.priority,
+			},
+			&state.config.job_limits(),
+		)
+		.await;
+
+	return match res {
+		Ok(_) => StatusCode::OK.into_response(),
+		Err(error) => {
+			// If this job wasn't queued, its uploads may be used again
+			unassign_uploads(&state, user.id, &assigned_uploads).await;
+			add_job_error_response(error)
+		}
+	};
+}
+
+//
+// MARK: Helpers
+//
+
+/// Convert user-provided input to pipeline input.
+///
+/// Every upload this input references is assigned to `job_id` and added to `assigned_uploads`,
+/// even if this function returns an error. If this job isn't queued, these
+/// uploads should be freed with [`unassign_uploads`].
+pub(in crate::api) async fn convert_input<Client: DatabaseClient>(
+	state: &RouterState<Client>,
+	user_id: UserId,
+	job_id: &str,
+	input: BTreeMap<SmartString<LazyCompact>, ApiInputAttrData>,
+	assigned_uploads: &mut Vec<UploadJobId>,
+) -> Result<BTreeMap<SmartString<LazyCompact>, AttrData>, Response> {
 	let mut converted_input: BTreeMap<SmartString<LazyCompact>, AttrData> = BTreeMap::new();
-	for (k, v) in payload.input {
+	for (k, v) in input {
 		// If we can automatically convert, do so
 		if let Ok(x) = AttrData::try_from(&v) {
 			converted_input.insert(k, x);
@@ -195,36 +281,36 @@ pub(super) async fn run_pipeline<Client: DatabaseClient>(
 		// Some types need manual conversion
 		if let Some(x) = match &v {
 			ApiInputAttrData::Blob { upload_id } => {
-				let res = state.uploader.get_job_object_key(user.id, upload_id).await;
+				let res = state.uploader.get_job_object_key(user_id, upload_id).await;
 				let key = match res {
 					GotJobKey::NoSuchJob => {
-						return (
+						return Err((
 							StatusCode::BAD_REQUEST,
 							Json(format!(
 								"Invalid input: input {k} references a job that does not exist"
 							)),
 						)
-							.into_response();
+							.into_response());
 					}
 
 					GotJobKey::JobNotDone => {
-						return (
+						return Err((
 							StatusCode::BAD_REQUEST,
 							Json(format!(
 								"Invalid input: input {k} references a job that is not finished"
 							)),
 						)
-							.into_response();
+							.into_response());
 					}
 
 					GotJobKey::JobIsAssigned => {
-						return (
+						return Err((
 							StatusCode::BAD_REQUEST,
 							Json(format!(
-								"Invalid input: input {k} references a job that has been assigned to a pipeline"
-							)),
+							"Invalid input: input {k} references a job that has been assigned to a pipeline"
+						)),
 						)
-							.into_response();
+							.into_response());
 					}
 
 					GotJobKey::HereYouGo(key) => key,
@@ -232,7 +318,7 @@ pub(super) async fn run_pipeline<Client: DatabaseClient>(
 
 				let res = state
 					.uploader
-					.assign_job_to_pipeline(user.id, upload_id, &payload.job_id)
+					.assign_job_to_pipeline(user_id, upload_id, job_id)
 					.await;
 
 				match res {
@@ -240,10 +326,13 @@ pub(super) async fn run_pipeline<Client: DatabaseClient>(
 					Err(UploadAssignError::BadUpload) => unreachable!(),
 					Err(UploadAssignError::NotMyUpload) => unreachable!(),
 
-					Ok(()) => Some(AttrData::Blob {
-						bucket: (&state.config.edged_objectstore_upload_bucket).into(),
-						key,
-					}),
+					Ok(()) => {
+						assigned_uploads.push(upload_id.clone());
+						Some(AttrData::Blob {
+							bucket: (&state.config.edged_objectstore_upload_bucket).into(),
+							key,
+						})
+					}
 				}
 			}
 
@@ -256,30 +345,91 @@ pub(super) async fn run_pipeline<Client: DatabaseClient>(
 		unreachable!("User-provided data {v:?} could not be converted automatically, but was not caught by the manual conversion `match`.")
 	}
 
-	let res = state
-		.jobqueue_client
-		.add_job(
-			payload.job_id.as_str().into(),
-			user.id,
-			&pipe.data,
-			&converted_input,
+	return Ok(converted_input);
+}
+
+/// Free uploads assigned by [`convert_input`], so that they may be used again
+pub(in crate::api) async fn unassign_uploads<Client: DatabaseClient>(
+	state: &RouterState<Client>,
+	user_id: UserId,
+	assigned_uploads: &[UploadJobId],
+) {
+	for upload_id in assigned_uploads {
+		state.uploader.unassign_job(user_id, upload_id).await;
+	}
+}
+
+/// Make sure a user-provided retry policy is within our limits.
+/// Returns an error response if it isn't.
+pub(in crate::api) fn check_retry_policy(policy: Option<&RetryPolicy>) -> Option<Response> {
+	let error = policy?.check().err()?;
+	return Some(
+		(
+			StatusCode::BAD_REQUEST,
+			Json(format!("Invalid retry policy: {error}")),
 		)
-		.await;
+			.into_response(),
+	);
+}
 
-	return match res {
-		Ok(_) => StatusCode::OK.into_response(),
+/// Make sure a user-provided job id is one users may choose.
+/// Returns an error response if it isn't.
+pub(in crate::api) fn check_job_id(job_id: &str) -> Option<Response> {
+	if !job_id.starts_with(SCHEDULED_JOB_PREFIX) {
+		return None;
+	}
 
-		Err(AddJobError::DbError(error)) => {
-			error!(message = "DB error while queueing job", ?error, ?payload.job_id);
-			return (
+	return Some(
+		(
+			StatusCode::BAD_REQUEST,
+			Json(format!(
+				"Job ids may not start with `{SCHEDULED_JOB_PREFIX}`"
+			)),
+		)
+			.into_response(),
+	);
+}
+
+/// Turn an error we got while queueing jobs into a response
+pub(in crate::api) fn add_job_error_response(error: AddJobError) -> Response {
+	return match error {
+		AddJobError::DbError(error) => {
+			error!(message = "DB error while queueing job", ?error);
+			(
 				StatusCode::INTERNAL_SERVER_ERROR,
 				Json("Internal server error"),
 			)
-				.into_response();
+				.into_response()
 		}
 
-		Err(AddJobError::AlreadyExists) => {
-			return StatusCode::CONFLICT.into_response();
+		AddJobError::AlreadyExists => StatusCode::CONFLICT.into_response(),
+
+		AddJobError::TooManyJobs { limit } => (
+			StatusCode::TOO_MANY_REQUESTS,
+			AppendHeaders([(RETRY_AFTER, TOO_MANY_JOBS_RETRY_AFTER.as_secs().to_string())]),
+			Json(format!(
+				"You may not have more than {limit} unfinished jobs"
+			)),
+		)
+			.into_response(),
+
+		AddJobError::RateLimited { limit, retry_after } => {
+			// Round up, so that clients don't retry too early
+			let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+			(
+				StatusCode::TOO_MANY_REQUESTS,
+				AppendHeaders([(RETRY_AFTER, secs.to_string())]),
+				Json(format!(
+					"You may not submit more than {limit} jobs per minute"
+				)),
+			)
+				.into_response()
 		}
+
+		AddJobError::BatchTooLarge { limit } => (
+			StatusCode::BAD_REQUEST,
+			Json(format!("A batch may not have more than {limit} jobs")),
+		)
+			.into_response(),
 	};
 }
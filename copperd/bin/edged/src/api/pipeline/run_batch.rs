@@ -0,0 +1,189 @@
+use axum::{
+	extract::{Path, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use copper_itemdb::AttrData;
+use copper_jobqueue::{
+	base::errors::AddJobError, id::BatchId, info::NewJob, limits::MAX_BATCH_SIZE,
+	scheduling::JobPriority,
+};
+use copper_piper::retry::RetryPolicy;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use smartstring::{LazyCompact, SmartString};
+use std::collections::BTreeMap;
+use tracing::error;
+use utoipa::ToSchema;
+
+use super::run::{
+	add_job_error_response, check_retry_policy, convert_input, unassign_uploads, ApiInputAttrData,
+};
+use crate::{
+	database::base::{client::DatabaseClient, errors::pipeline::GetPipelineError},
+	uploader::UploadJobId,
+	RouterState,
+};
+
+const BATCH_ID_LENGTH: usize = 16;
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub(super) struct RunBatchRequest {
+	/// The input of each job in this batch.
+	/// One job is created for each entry.
+	///
+	/// A batch may have at most 10 000 jobs.
+	#[schema(value_type = Vec<BTreeMap<String, ApiInputAttrData>>)]
+	pub inputs: Vec<BTreeMap<SmartString<LazyCompact>, ApiInputAttrData>>,
+
+	/// How these jobs should be retried if they fail.
+	/// If this is `None`, we use the pipeline's retry policy.
+	#[serde(default)]
+	pub retry: Option<RetryPolicy>,
+
+	/// The priority of every job in this batch
+	#[serde(default)]
+	pub priority: JobPriority,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub(super) struct RunBatchResponse {
+	/// The new batch's id
+	#[schema(value_type = String)]
+	pub batch_id: SmartString<LazyCompact>,
+
+	/// The id of each job in this batch,
+	/// in the same order as `inputs`.
+	#[schema(value_type = Vec<String>)]
+	pub job_ids: Vec<SmartString<LazyCompact>>,
+}
+
+/// Start one pipeline job for each of many inputs
+#[utoipa::path(
+	post,
+	path = "/{pipeline_id}/run_batch",
+	params(
+		("pipeline_id", description = "Pipeline id"),
+	),
+	responses(
+		(status = 200, description = "Batch queued successfully", body = RunBatchResponse),
+		(status = 400, description = "Invalid input or retry policy, or batch is too large", body = String),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Pipeline not found"),
+		(status = 429, description = "Too many jobs, try again after `Retry-After` seconds", body = String),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn run_batch<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Path(pipeline_id): Path<i64>,
+	Json(payload): Json<RunBatchRequest>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	if let Some(x) = check_retry_policy(payload.retry.as_ref()) {
+		return x;
+	}
+
+	if payload.inputs.is_empty() {
+		return (
+			StatusCode::BAD_REQUEST,
+			Json("A batch must have at least one job"),
+		)
+			.into_response();
+	}
+
+	// Checked again when the batch is added,
+	// but we don't want to convert a huge input first.
+	if payload.inputs.len() > MAX_BATCH_SIZE as usize {
+		return add_job_error_response(AddJobError::BatchTooLarge {
+			limit: MAX_BATCH_SIZE,
+		});
+	}
+
+	let pipe = match state.db_client.get_pipeline(pipeline_id.into()).await {
+		Ok(Some(pipe)) => pipe,
+		Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+		Err(GetPipelineError::DbError(error)) => {
+			error!(
+				message = "Database error while getting pipeline",
+				?pipeline_id,
+				?error,
+			);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	// Users can only get pipelines they own
+	if pipe.owned_by != user.id {
+		return (StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response();
+	}
+
+	let batch_id: SmartString<LazyCompact> = rand::thread_rng()
+		.sample_iter(&Alphanumeric)
+		.take(BATCH_ID_LENGTH)
+		.map(char::from)
+		.collect();
+
+	let mut assigned_uploads: Vec<UploadJobId> = Vec::new();
+	let mut job_ids: Vec<SmartString<LazyCompact>> = Vec::new();
+	let mut converted_inputs: Vec<BTreeMap<SmartString<LazyCompact>, AttrData>> = Vec::new();
+	for (i, input) in payload.inputs.into_iter().enumerate() {
+		let job_id: SmartString<LazyCompact> = format!("{batch_id}-{i}").into();
+
+		match convert_input(&state, user.id, &job_id, input, &mut assigned_uploads).await {
+			Ok(x) => converted_inputs.push(x),
+			Err(x) => {
+				unassign_uploads(&state, user.id, &assigned_uploads).await;
+				return x;
+			}
+		};
+
+		job_ids.push(job_id);
+	}
+
+	let retry_policy = payload.retry.as_ref().or(pipe.data.retry.as_ref());
+	let jobs: Vec<NewJob<'_>> = job_ids
+		.iter()
+		.zip(converted_inputs.iter())
+		.map(|(job_id, input)| NewJob {
+			job_id: job_id.as_str().into(),
+			pipeline_id: Some(i64::from(pipe.id)),
+			pipeline: &pipe.data,
+			input,
+			retry_policy,
+			priority: payload.priority,
+		})
+		.collect();
+
+	let res = state
+		.jobqueue_client
+		.add_jobs(
+			user.id,
+			&BatchId::from(batch_id.as_str()),
+			&jobs,
+			&state.config.job_limits(),
+		)
+		.await;
+
+	return match res {
+		Ok(()) => (StatusCode::OK, Json(RunBatchResponse { batch_id, job_ids })).into_response(),
+		Err(error) => {
+			// If this batch wasn't queued, its uploads may be used again
+			unassign_uploads(&state, user.id, &assigned_uploads).await;
+			add_job_error_response(error)
+		}
+	};
+}
@@ -11,6 +11,7 @@ use axum::{
 use axum_extra::extract::CookieJar;
 use copper_piper::json::PipelineJson;
 use serde::Deserialize;
+use std::collections::BTreeMap;
 use tracing::error;
 use utoipa::ToSchema;
 
@@ -32,6 +33,7 @@ pub(super) struct UpdatePipelineRequest {
 	responses(
 		(status = 200, description = "Pipeline updated successfully", body = PipelineInfo),
 		(status = 400, description = "Invalid request", body = String),
+		(status = 422, description = "Invalid pipeline", body = Vec<PipelineProblem>),
 		(status = 401, description = "Unauthorized"),
 		(status = 500, description = "Internal server error"),
 	),
@@ -76,6 +78,11 @@ pub(super) async fn update_pipeline<Client: DatabaseClient>(
 	}
 
 	if let Some(data) = payload.new_data {
+		let problems = data.validate(&state.dispatcher, &BTreeMap::new());
+		if !problems.is_empty() {
+			return (StatusCode::UNPROCESSABLE_ENTITY, Json(problems)).into_response();
+		}
+
 		pipe.data = data
 	}
 
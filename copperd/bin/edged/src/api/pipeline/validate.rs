@@ -0,0 +1,38 @@
+use crate::database::base::client::DatabaseClient;
+use crate::RouterState;
+use axum::{
+	extract::State,
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use copper_piper::json::PipelineJson;
+use std::collections::BTreeMap;
+
+/// Check a pipeline for problems without saving it.
+///
+/// Returns an empty list if this pipeline is valid.
+#[utoipa::path(
+	post,
+	path = "/validate",
+	responses(
+		(status = 200, description = "Problems we found in this pipeline", body = Vec<PipelineProblem>),
+		(status = 401, description = "Unauthorized"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn validate_pipeline<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Json(payload): Json<PipelineJson>,
+) -> Response {
+	if let Err(x) = state.auth.auth_or_logout(&state, &jar).await {
+		return x;
+	}
+
+	let problems = payload.validate(&state.dispatcher, &BTreeMap::new());
+	return (StatusCode::OK, Json(problems)).into_response();
+}
@@ -0,0 +1,136 @@
+use crate::database::base::{
+	client::DatabaseClient,
+	errors::{pipeline::GetPipelineError, schedule::AddScheduleError},
+};
+use axum::{
+	extract::State,
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use copper_edged::schedule::ScheduleTrigger;
+use copper_itemdb::AttrData;
+use serde::Deserialize;
+use smartstring::{LazyCompact, SmartString};
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+use tracing::error;
+use utoipa::ToSchema;
+
+use crate::api::{pipeline::ApiInputAttrData, RouterState};
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub(super) struct NewScheduleRequest {
+	/// The pipeline to run
+	pipeline: i64,
+
+	/// When to run it
+	trigger: ScheduleTrigger,
+
+	/// The input to give the pipeline every time it runs.
+	/// This may not contain blobs, since uploads can only be used once.
+	#[schema(value_type = BTreeMap<String, ApiInputAttrData>)]
+	input: BTreeMap<SmartString<LazyCompact>, ApiInputAttrData>,
+}
+
+/// Create a new schedule
+#[utoipa::path(
+	post,
+	path = "",
+	responses(
+		(status = 200, description = "Schedule created successfully", body = ScheduleInfo),
+		(status = 400, description = "Bad request", body = String),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Pipeline not found"),
+		(status = 500, description = "Internal server error"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn add_schedule<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Json(payload): Json<NewScheduleRequest>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	let pipe = match state.db_client.get_pipeline(payload.pipeline.into()).await {
+		Ok(Some(pipe)) => pipe,
+		Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+		Err(GetPipelineError::DbError(error)) => {
+			error!(
+				message = "Database error while getting pipeline",
+				pipeline_id = payload.pipeline,
+				?error,
+			);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	// Users can only schedule pipelines they own
+	if pipe.owned_by != user.id {
+		return (StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response();
+	}
+
+	if let Err(e) = payload.trigger.validate() {
+		return (
+			StatusCode::BAD_REQUEST,
+			Json(format!("Invalid trigger: {e}")),
+		)
+			.into_response();
+	}
+
+	let next_run_at = match payload.trigger.next_after(OffsetDateTime::now_utc()) {
+		Some(x) => x,
+		None => {
+			return (
+				StatusCode::BAD_REQUEST,
+				Json("Invalid trigger: this trigger never fires"),
+			)
+				.into_response();
+		}
+	};
+
+	let mut input: BTreeMap<SmartString<LazyCompact>, AttrData> = BTreeMap::new();
+	for (k, v) in payload.input {
+		match AttrData::try_from(v) {
+			Ok(x) => input.insert(k, x),
+			Err(()) => {
+				// Blobs are the only input we can't convert automatically
+				return (
+					StatusCode::BAD_REQUEST,
+					Json(format!(
+						"Invalid input: input {k} is a blob, which schedules may not use"
+					)),
+				)
+					.into_response();
+			}
+		};
+	}
+
+	let res = state
+		.db_client
+		.add_schedule(user.id, pipe.id, &payload.trigger, &input, next_run_at)
+		.await;
+
+	return match res {
+		Ok(x) => (StatusCode::OK, Json(x)).into_response(),
+		Err(AddScheduleError::DbError(error)) => {
+			error!(message = "Database error while adding schedule", ?error);
+			(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response()
+		}
+	};
+}
@@ -0,0 +1,80 @@
+use crate::database::base::{
+	client::DatabaseClient,
+	errors::schedule::{DeleteScheduleError, GetScheduleError},
+};
+use axum::{
+	extract::{Path, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use tracing::error;
+
+use crate::api::RouterState;
+
+/// Delete a schedule
+#[utoipa::path(
+	delete,
+	path = "/{schedule_id}",
+	params(
+		("schedule_id", description = "Schedule id"),
+	),
+	responses(
+		(status = 200, description = "Schedule deleted successfully"),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Schedule not found"),
+		(status = 500, description = "Internal server error"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn del_schedule<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Path(schedule_id): Path<i64>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	let schedule = match state.db_client.get_schedule(schedule_id.into()).await {
+		Ok(Some(x)) => x,
+		Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+		Err(GetScheduleError::DbError(error)) => {
+			error!(
+				message = "Database error while getting schedule",
+				schedule_id,
+				?error,
+			);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	// Users can only delete schedules they own
+	if schedule.owned_by != user.id {
+		return (StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response();
+	}
+
+	return match state.db_client.del_schedule(schedule.id).await {
+		Ok(()) => StatusCode::OK.into_response(),
+		Err(DeleteScheduleError::DbError(error)) => {
+			error!(
+				message = "Database error while deleting schedule",
+				schedule_id,
+				?error,
+			);
+			(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response()
+		}
+	};
+}
@@ -0,0 +1,50 @@
+use crate::database::base::client::DatabaseClient;
+use crate::database::base::errors::schedule::ListScheduleError;
+use crate::RouterState;
+use axum::Json;
+use axum::{
+	extract::State,
+	http::StatusCode,
+	response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+use tracing::error;
+
+/// List the logged in user's schedules
+#[utoipa::path(
+	get,
+	path = "/list",
+	responses(
+		(status = 200, description = "This user's schedules", body = Vec<ScheduleInfo>),
+		(status = 401, description = "Unauthorized"),
+		(status = 500, description = "Internal server error"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn list_schedules<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	match state.db_client.list_schedules(user.id).await {
+		Ok(schedules) => return (StatusCode::OK, Json(schedules)).into_response(),
+		Err(ListScheduleError::DbError(error)) => {
+			error!(
+				message = "Database error while listing schedules",
+				user_id = ?user.id,
+				?error,
+			);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+}
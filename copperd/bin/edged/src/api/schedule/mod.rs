@@ -0,0 +1,42 @@
+use crate::database::base::client::DatabaseClient;
+use crate::RouterState;
+use axum::{
+	routing::{delete, get, post},
+	Router,
+};
+use copper_edged::{schedule::ScheduleTrigger, ScheduleInfo};
+use utoipa::OpenApi;
+
+mod add;
+mod del;
+mod list;
+mod pause;
+
+use add::*;
+use del::*;
+use list::*;
+use pause::*;
+
+#[allow(non_camel_case_types)]
+#[derive(OpenApi)]
+#[openapi(
+	tags(),
+	paths(
+		list_schedules,
+		add_schedule,
+		del_schedule,
+		pause_schedule,
+		resume_schedule
+	),
+	components(schemas(ScheduleInfo, ScheduleTrigger, NewScheduleRequest))
+)]
+pub(super) struct ScheduleApi;
+
+pub(super) fn router<Client: DatabaseClient + 'static>() -> Router<RouterState<Client>> {
+	Router::new()
+		.route("/", post(add_schedule))
+		.route("/list", get(list_schedules))
+		.route("/:schedule_id", delete(del_schedule))
+		.route("/:schedule_id/pause", post(pause_schedule))
+		.route("/:schedule_id/resume", post(resume_schedule))
+}
@@ -0,0 +1,140 @@
+use crate::database::base::{
+	client::DatabaseClient,
+	errors::schedule::{GetScheduleError, PauseScheduleError},
+};
+use axum::{
+	extract::{Path, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use time::OffsetDateTime;
+use tracing::error;
+
+use crate::api::RouterState;
+
+/// Pause a schedule
+#[utoipa::path(
+	post,
+	path = "/{schedule_id}/pause",
+	params(
+		("schedule_id", description = "Schedule id"),
+	),
+	responses(
+		(status = 200, description = "Schedule paused successfully"),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Schedule not found"),
+		(status = 500, description = "Internal server error"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn pause_schedule<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Path(schedule_id): Path<i64>,
+) -> Response {
+	return set_paused(jar, state, schedule_id, true).await;
+}
+
+/// Resume a paused schedule.
+///
+/// Runs missed while this schedule was paused are skipped.
+#[utoipa::path(
+	post,
+	path = "/{schedule_id}/resume",
+	params(
+		("schedule_id", description = "Schedule id"),
+	),
+	responses(
+		(status = 200, description = "Schedule resumed successfully"),
+		(status = 400, description = "This schedule's trigger never fires again", body = String),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Schedule not found"),
+		(status = 500, description = "Internal server error"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn resume_schedule<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Path(schedule_id): Path<i64>,
+) -> Response {
+	return set_paused(jar, state, schedule_id, false).await;
+}
+
+async fn set_paused<Client: DatabaseClient>(
+	jar: CookieJar,
+	state: RouterState<Client>,
+	schedule_id: i64,
+	paused: bool,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	let schedule = match state.db_client.get_schedule(schedule_id.into()).await {
+		Ok(Some(x)) => x,
+		Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+		Err(GetScheduleError::DbError(error)) => {
+			error!(
+				message = "Database error while getting schedule",
+				schedule_id,
+				?error,
+			);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	// Users can only change schedules they own
+	if schedule.owned_by != user.id {
+		return (StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response();
+	}
+
+	// When resuming, always start counting from now
+	// so we don't immediately start a missed run.
+	let next_run_at = if paused {
+		schedule.next_run_at
+	} else {
+		match schedule.trigger.next_after(OffsetDateTime::now_utc()) {
+			Some(x) => x,
+			None => {
+				return (
+					StatusCode::BAD_REQUEST,
+					Json("This schedule's trigger never fires again"),
+				)
+					.into_response();
+			}
+		}
+	};
+
+	let res = state
+		.db_client
+		.set_schedule_paused(schedule.id, paused, next_run_at)
+		.await;
+
+	return match res {
+		Ok(()) => StatusCode::OK.into_response(),
+		Err(PauseScheduleError::DbError(error)) => {
+			error!(
+				message = "Database error while updating schedule",
+				schedule_id,
+				?error,
+			);
+			(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response()
+		}
+	};
+}
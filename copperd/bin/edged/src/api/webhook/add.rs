@@ -0,0 +1,120 @@
+use crate::database::base::{client::DatabaseClient, errors::webhook::AddWebhookError};
+use axum::{
+	extract::State,
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use copper_edged::{webhook::WebhookEventKind, WebhookInfo};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use smartstring::{LazyCompact, SmartString};
+use tracing::error;
+use url::Url;
+use utoipa::ToSchema;
+
+use crate::api::RouterState;
+
+const WEBHOOK_SECRET_LENGTH: usize = 32;
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub(super) struct NewWebhookRequest {
+	/// The url to send events to.
+	/// This must be an `http` or `https` url.
+	#[schema(value_type = String)]
+	url: SmartString<LazyCompact>,
+
+	/// The events to send
+	events: Vec<WebhookEventKind>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub(super) struct NewWebhookResponse {
+	/// The new webhook
+	webhook: WebhookInfo,
+
+	/// The key we sign this webhook's requests with.
+	/// This is never shown again.
+	#[schema(value_type = String)]
+	secret: SmartString<LazyCompact>,
+}
+
+/// Create a new webhook
+#[utoipa::path(
+	post,
+	path = "",
+	responses(
+		(status = 200, description = "Webhook created successfully", body = NewWebhookResponse),
+		(status = 400, description = "Bad request", body = String),
+		(status = 401, description = "Unauthorized"),
+		(status = 500, description = "Internal server error"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn add_webhook<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Json(mut payload): Json<NewWebhookRequest>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	match Url::parse(&payload.url) {
+		Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+		Ok(_) => {
+			return (
+				StatusCode::BAD_REQUEST,
+				Json("Webhook url must use http or https"),
+			)
+				.into_response()
+		}
+		Err(error) => {
+			return (
+				StatusCode::BAD_REQUEST,
+				Json(format!("Invalid webhook url: {error}")),
+			)
+				.into_response()
+		}
+	}
+
+	payload.events.sort();
+	payload.events.dedup();
+	if payload.events.is_empty() {
+		return (
+			StatusCode::BAD_REQUEST,
+			Json("A webhook must subscribe to at least one event"),
+		)
+			.into_response();
+	}
+
+	let secret: SmartString<LazyCompact> = rand::thread_rng()
+		.sample_iter(&Alphanumeric)
+		.take(WEBHOOK_SECRET_LENGTH)
+		.map(char::from)
+		.collect();
+
+	let res = state
+		.db_client
+		.add_webhook(user.id, &payload.url, &payload.events, &secret)
+		.await;
+
+	return match res {
+		Ok(webhook) => {
+			(StatusCode::OK, Json(NewWebhookResponse { webhook, secret })).into_response()
+		}
+
+		Err(AddWebhookError::DbError(error)) => {
+			error!(message = "Database error while creating webhook", ?error);
+			(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response()
+		}
+	};
+}
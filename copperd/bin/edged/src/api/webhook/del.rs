@@ -0,0 +1,80 @@
+use crate::database::base::{
+	client::DatabaseClient,
+	errors::webhook::{DeleteWebhookError, GetWebhookError},
+};
+use axum::{
+	extract::{Path, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use tracing::error;
+
+use crate::api::RouterState;
+
+/// Delete a webhook and its delivery log
+#[utoipa::path(
+	delete,
+	path = "/{webhook_id}",
+	params(
+		("webhook_id", description = "Webhook id"),
+	),
+	responses(
+		(status = 200, description = "Webhook deleted successfully"),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Webhook not found"),
+		(status = 500, description = "Internal server error"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn del_webhook<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Path(webhook_id): Path<i64>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	let webhook = match state.db_client.get_webhook(webhook_id.into()).await {
+		Ok(Some(x)) => x,
+		Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+		Err(GetWebhookError::DbError(error)) => {
+			error!(
+				message = "Database error while getting webhook",
+				webhook_id,
+				?error,
+			);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	// Users can only delete webhooks they own
+	if webhook.owned_by != user.id {
+		return (StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response();
+	}
+
+	return match state.db_client.del_webhook(webhook.id).await {
+		Ok(()) => StatusCode::OK.into_response(),
+		Err(DeleteWebhookError::DbError(error)) => {
+			error!(
+				message = "Database error while deleting webhook",
+				webhook_id,
+				?error,
+			);
+			(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response()
+		}
+	};
+}
@@ -0,0 +1,109 @@
+use crate::database::base::{
+	client::DatabaseClient,
+	errors::webhook::{GetWebhookError, ListWebhookDeliveriesError},
+};
+use crate::RouterState;
+use axum::{
+	extract::{Path, Query, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+use tracing::error;
+use utoipa::IntoParams;
+
+/// The largest number of deliveries we return at once
+const MAX_DELIVERY_COUNT: i64 = 500;
+
+fn default_count() -> i64 {
+	50
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub(super) struct ListDeliveriesParams {
+	/// How many deliveries to list (at most 500)
+	#[serde(default = "default_count")]
+	count: i64,
+}
+
+/// List a webhook's most recent deliveries, newest first
+#[utoipa::path(
+	get,
+	path = "/{webhook_id}/deliveries",
+	params(
+		("webhook_id", description = "Webhook id"),
+		ListDeliveriesParams,
+	),
+	responses(
+		(status = 200, description = "This webhook's deliveries", body = Vec<WebhookDeliveryInfo>),
+		(status = 400, description = "Invalid count", body = String),
+		(status = 401, description = "Unauthorized"),
+		(status = 404, description = "Webhook not found"),
+		(status = 500, description = "Internal server error"),
+	),
+	security(
+		("bearer" = []),
+	)
+)]
+pub(super) async fn list_webhook_deliveries<Client: DatabaseClient>(
+	jar: CookieJar,
+	State(state): State<RouterState<Client>>,
+	Path(webhook_id): Path<i64>,
+	Query(params): Query<ListDeliveriesParams>,
+) -> Response {
+	let user = match state.auth.auth_or_logout(&state, &jar).await {
+		Err(x) => return x,
+		Ok(user) => user,
+	};
+
+	if !(1..=MAX_DELIVERY_COUNT).contains(&params.count) {
+		return (
+			StatusCode::BAD_REQUEST,
+			Json(format!("count must be between 1 and {MAX_DELIVERY_COUNT}")),
+		)
+			.into_response();
+	}
+
+	let webhook = match state.db_client.get_webhook(webhook_id.into()).await {
+		Ok(Some(x)) => x,
+		Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+		Err(GetWebhookError::DbError(error)) => {
+			error!(
+				message = "Database error while getting webhook",
+				webhook_id,
+				?error,
+			);
+			return (
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response();
+		}
+	};
+
+	if webhook.owned_by != user.id {
+		return (StatusCode::UNAUTHORIZED, Json("Unauthorized")).into_response();
+	}
+
+	return match state
+		.db_client
+		.list_webhook_deliveries(webhook.id, params.count)
+		.await
+	{
+		Ok(deliveries) => (StatusCode::OK, Json(deliveries)).into_response(),
+		Err(ListWebhookDeliveriesError::DbError(error)) => {
+			error!(
+				message = "Database error while listing webhook deliveries",
+				webhook_id,
+				?error,
+			);
+			(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				Json("Internal server error"),
+			)
+				.into_response()
+		}
+	};
+}
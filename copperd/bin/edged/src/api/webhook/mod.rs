@@ -0,0 +1,46 @@
+use crate::database::base::client::DatabaseClient;
+use crate::RouterState;
+use axum::{
+	routing::{delete, get, post},
+	Router,
+};
+use copper_edged::{
+	webhook::{WebhookEvent, WebhookEventKind},
+	WebhookDeliveryInfo, WebhookDeliveryState, WebhookInfo,
+};
+use utoipa::OpenApi;
+
+mod add;
+mod del;
+mod deliveries;
+mod list;
+
+use add::*;
+use del::*;
+use deliveries::*;
+use list::*;
+
+#[allow(non_camel_case_types)]
+#[derive(OpenApi)]
+#[openapi(
+	tags(),
+	paths(list_webhooks, add_webhook, del_webhook, list_webhook_deliveries),
+	components(schemas(
+		WebhookInfo,
+		WebhookEvent,
+		WebhookEventKind,
+		WebhookDeliveryInfo,
+		WebhookDeliveryState,
+		NewWebhookRequest,
+		NewWebhookResponse
+	))
+)]
+pub(super) struct WebhookApi;
+
+pub(super) fn router<Client: DatabaseClient + 'static>() -> Router<RouterState<Client>> {
+	Router::new()
+		.route("/", post(add_webhook))
+		.route("/list", get(list_webhooks))
+		.route("/:webhook_id", delete(del_webhook))
+		.route("/:webhook_id/deliveries", get(list_webhook_deliveries))
+}
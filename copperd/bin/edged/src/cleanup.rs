@@ -0,0 +1,118 @@
+//! Deletes (and optionally archives) old jobs
+
+use copper_jobqueue::{base::client::JobQueueClient, info::ArchivedJob};
+use copper_util::{s3client::S3Client, MimeType};
+use rand::{distributions::Alphanumeric, Rng};
+use std::{sync::Arc, time::Duration};
+use time::OffsetDateTime;
+use tracing::{debug, error, info};
+
+use crate::config::EdgedConfig;
+
+/// The maximum number of jobs we delete at once.
+/// When archiving, this is also the maximum number of jobs in one archive object.
+const CLEANUP_BATCH_SIZE: i64 = 500;
+
+/// Periodically delete jobs our retention policy no longer keeps.
+/// This never returns.
+pub async fn run_job_cleanup(
+	config: Arc<EdgedConfig>,
+	jobqueue_client: Arc<dyn JobQueueClient>,
+	s3_client: Arc<S3Client>,
+) {
+	let retention = config.job_retention();
+	if retention.keeps_everything() {
+		debug!(message = "No job retention policy, not starting job cleanup");
+		return;
+	}
+
+	// Included in archive keys, so that edged instances
+	// that clean up at the same time never overwrite each other's archives.
+	let instance_id: String = rand::thread_rng()
+		.sample_iter(&Alphanumeric)
+		.take(8)
+		.map(char::from)
+		.collect();
+
+	let interval = Duration::from_secs(config.edged_job_cleanup_interval_secs);
+	loop {
+		tokio::time::sleep(interval).await;
+
+		let started_at = OffsetDateTime::now_utc();
+		let mut n_deleted = 0;
+		let mut part = 0;
+		loop {
+			// These jobs stay locked until we delete them,
+			// so other instances won't archive them twice.
+			let claim = match jobqueue_client
+				.claim_expired_jobs(&retention, CLEANUP_BATCH_SIZE)
+				.await
+			{
+				Ok(x) => x,
+				Err(error) => {
+					error!(message = "Could not get expired jobs", ?error);
+					break;
+				}
+			};
+
+			let n_claimed = claim.jobs.len();
+			if n_claimed == 0 {
+				break;
+			}
+
+			// Never delete jobs we couldn't archive
+			if let Some(bucket) = &config.edged_job_archive_bucket {
+				let key = format!(
+					"jobs/{}-{instance_id}-{part}.ndjson",
+					started_at.unix_timestamp()
+				);
+				part += 1;
+
+				let res = s3_client
+					.put_object(
+						bucket,
+						&key,
+						MimeType::Other("application/x-ndjson".into()),
+						to_ndjson(&claim.jobs),
+					)
+					.await;
+
+				if let Err(error) = res {
+					error!(
+						message = "Could not archive expired jobs",
+						bucket,
+						key,
+						?error
+					);
+					break;
+				}
+			}
+
+			match jobqueue_client.delete_expired_jobs(claim).await {
+				Ok(n) => n_deleted += n,
+				Err(error) => {
+					error!(message = "Could not delete expired jobs", ?error);
+					break;
+				}
+			}
+
+			if i64::try_from(n_claimed).unwrap() < CLEANUP_BATCH_SIZE {
+				break;
+			}
+		}
+
+		if n_deleted != 0 {
+			info!(message = "Deleted expired jobs", n_deleted);
+		}
+	}
+}
+
+/// Serialize jobs as newline-delimited json
+fn to_ndjson(jobs: &[ArchivedJob]) -> Vec<u8> {
+	let mut out = Vec::new();
+	for job in jobs {
+		serde_json::to_writer(&mut out, job).unwrap();
+		out.push(b'\n');
+	}
+	return out;
+}
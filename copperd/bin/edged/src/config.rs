@@ -1,6 +1,8 @@
+use copper_jobqueue::{limits::JobLimits, retention::JobRetention};
 use copper_util::logging::LoggingPreset;
 use serde::Deserialize;
 use smartstring::{LazyCompact, SmartString};
+use std::time::Duration;
 use tracing::error;
 
 /// Note that the field of this struct are not capitalized.
@@ -51,6 +53,65 @@ pub struct EdgedConfig {
 	#[serde(default = "EdgedConfig::default_upload_job_timeout")]
 	pub edged_upload_job_timeout: u64,
 
+	/// How long to keep the uploads a job used after it finishes, in seconds.
+	/// A finished job may only be re-run while its uploads are kept.
+	#[serde(default = "EdgedConfig::default_upload_retention_secs")]
+	pub edged_upload_retention_secs: u64,
+
+	/// The maximum number of unfinished (queued or running) jobs one user may have.
+	/// If unset, there is no limit.
+	#[serde(default)]
+	pub edged_max_unfinished_jobs: Option<u32>,
+
+	/// The maximum number of jobs one user may start per minute.
+	/// If unset, there is no limit.
+	#[serde(default)]
+	pub edged_max_jobs_per_minute: Option<u32>,
+
+	/// How often to check for scheduled runs, in seconds
+	#[serde(default = "EdgedConfig::default_scheduler_interval_secs")]
+	pub edged_scheduler_interval_secs: u64,
+
+	/// How long to keep successful jobs after they finish, in seconds.
+	/// If unset, successful jobs are kept forever.
+	#[serde(default)]
+	pub edged_keep_successful_jobs_secs: Option<u64>,
+
+	/// How long to keep failed jobs after they finish, in seconds.
+	/// If unset, failed jobs are kept forever.
+	#[serde(default)]
+	pub edged_keep_failed_jobs_secs: Option<u64>,
+
+	/// How long to keep cancelled jobs after they finish, in seconds.
+	/// If unset, cancelled jobs are kept forever.
+	#[serde(default)]
+	pub edged_keep_cancelled_jobs_secs: Option<u64>,
+
+	/// If set, expired jobs are written to this bucket before they are deleted.
+	#[serde(default)]
+	pub edged_job_archive_bucket: Option<String>,
+
+	/// How often to delete expired jobs, in seconds
+	#[serde(default = "EdgedConfig::default_job_cleanup_interval_secs")]
+	pub edged_job_cleanup_interval_secs: u64,
+
+	/// How often to send pending webhook deliveries, in seconds
+	#[serde(default = "EdgedConfig::default_webhook_interval_secs")]
+	pub edged_webhook_interval_secs: u64,
+
+	/// How long to wait for a webhook to respond, in seconds
+	#[serde(default = "EdgedConfig::default_webhook_timeout_secs")]
+	pub edged_webhook_timeout_secs: u64,
+
+	/// How many times we try to deliver each webhook event before giving up
+	#[serde(default = "EdgedConfig::default_webhook_max_attempts")]
+	pub edged_webhook_max_attempts: u32,
+
+	/// If true, webhooks may send events to private, loopback, and link-local addresses.
+	/// Only enable this if every user may make requests to your internal network.
+	#[serde(default)]
+	pub edged_webhook_allow_private_addresses: bool,
+
 	/// If both of the following are set, create a user with the given name & email on startup.
 	#[serde(default)]
 	pub edged_init_user_email: Option<String>,
@@ -67,6 +128,51 @@ impl EdgedConfig {
 		300
 	}
 
+	fn default_upload_retention_secs() -> u64 {
+		86400
+	}
+
+	fn default_scheduler_interval_secs() -> u64 {
+		10
+	}
+
+	fn default_job_cleanup_interval_secs() -> u64 {
+		3600
+	}
+
+	fn default_webhook_interval_secs() -> u64 {
+		5
+	}
+
+	fn default_webhook_timeout_secs() -> u64 {
+		10
+	}
+
+	fn default_webhook_max_attempts() -> u32 {
+		8
+	}
+
+	/// The limits on the jobs one user may queue.
+	/// Limits on running jobs are enforced by piper, and are not set here.
+	pub fn job_limits(&self) -> JobLimits {
+		JobLimits {
+			max_unfinished_jobs: self.edged_max_unfinished_jobs,
+			max_jobs_per_minute: self.edged_max_jobs_per_minute,
+			..Default::default()
+		}
+	}
+
+	/// How long finished jobs are kept
+	pub fn job_retention(&self) -> JobRetention {
+		JobRetention {
+			successful: self
+				.edged_keep_successful_jobs_secs
+				.map(Duration::from_secs),
+			failed: self.edged_keep_failed_jobs_secs.map(Duration::from_secs),
+			cancelled: self.edged_keep_cancelled_jobs_secs.map(Duration::from_secs),
+		}
+	}
+
 	/// Validate this config, logging and fixing errors.
 	pub fn validate(mut self) -> Self {
 		// Enforce minimum request body limit
@@ -81,6 +187,46 @@ impl EdgedConfig {
 			self.edged_request_body_limit = 6_000_000;
 		}
 
+		if self.edged_job_cleanup_interval_secs == 0 {
+			error!(
+				message = "EDGED_JOB_CLEANUP_INTERVAL_SECS must be positive, setting minimum",
+				value = self.edged_job_cleanup_interval_secs,
+				minimum = 1
+			);
+
+			self.edged_job_cleanup_interval_secs = 1;
+		}
+
+		if self.edged_webhook_interval_secs == 0 {
+			error!(
+				message = "EDGED_WEBHOOK_INTERVAL_SECS must be positive, setting minimum",
+				value = self.edged_webhook_interval_secs,
+				minimum = 1
+			);
+
+			self.edged_webhook_interval_secs = 1;
+		}
+
+		if self.edged_webhook_max_attempts == 0 {
+			error!(
+				message = "EDGED_WEBHOOK_MAX_ATTEMPTS must be positive, setting minimum",
+				value = self.edged_webhook_max_attempts,
+				minimum = 1
+			);
+
+			self.edged_webhook_max_attempts = 1;
+		}
+
+		if self.edged_scheduler_interval_secs == 0 {
+			error!(
+				message = "EDGED_SCHEDULER_INTERVAL_SECS must be positive, setting minimum",
+				value = self.edged_scheduler_interval_secs,
+				minimum = 1
+			);
+
+			self.edged_scheduler_interval_secs = 1;
+		}
+
 		return self;
 	}
 }
@@ -1,16 +1,36 @@
 //! The database client api
 
 use async_trait::async_trait;
-use copper_edged::{PipelineId, PipelineInfo, UserInfo, UserPassword};
-use copper_itemdb::UserId;
+use copper_edged::{
+	schedule::ScheduleTrigger,
+	webhook::{WebhookEvent, WebhookEventKind},
+	PipelineId, PipelineInfo, ScheduleId, ScheduleInfo, UserInfo, UserPassword, WebhookDeliveryId,
+	WebhookDeliveryInfo, WebhookDeliveryState, WebhookId, WebhookInfo,
+};
+use copper_itemdb::{AttrData, UserId};
 use copper_piper::json::PipelineJson;
+use smartstring::{LazyCompact, SmartString};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	time::Duration,
+};
+use time::OffsetDateTime;
 
 use super::errors::{
 	pipeline::{
 		AddPipelineError, DeletePipelineError, GetPipelineError, ListPipelineError,
 		UpdatePipelineError,
 	},
+	schedule::{
+		AddScheduleError, ClaimSchedulesError, DeleteScheduleError, GetScheduleError,
+		ListScheduleError, PauseScheduleError,
+	},
 	user::{AddUserError, DeleteUserError, GetUserError, UpdateUserError},
+	webhook::{
+		AddWebhookError, ClaimWebhookDeliveriesError, DeleteWebhookError, GetWebhookError,
+		ListWebhookDeliveriesError, ListWebhookError, QueueWebhookEventError,
+		RecordWebhookAttemptError, WebhookCursorError,
+	},
 };
 
 /// A generic database client
@@ -75,4 +95,140 @@ where
 
 	/// Delete a pipeline
 	async fn del_pipeline(&self, pipeline: PipelineId) -> Result<(), DeletePipelineError>;
+
+	//
+	// MARK: Schedules
+	//
+
+	/// Get all a user's schedules
+	async fn list_schedules(
+		&self,
+		for_user: UserId,
+	) -> Result<Vec<ScheduleInfo>, ListScheduleError>;
+
+	/// Create a new schedule, which first runs at `next_run_at`
+	async fn add_schedule(
+		&self,
+		for_user: UserId,
+		pipeline: PipelineId,
+		trigger: &ScheduleTrigger,
+		input: &BTreeMap<SmartString<LazyCompact>, AttrData>,
+		next_run_at: OffsetDateTime,
+	) -> Result<ScheduleInfo, AddScheduleError>;
+
+	/// Get a schedule by id
+	async fn get_schedule(
+		&self,
+		schedule: ScheduleId,
+	) -> Result<Option<ScheduleInfo>, GetScheduleError>;
+
+	/// Pause or resume a schedule.
+	/// If it isn't paused, it next runs at `next_run_at`.
+	async fn set_schedule_paused(
+		&self,
+		schedule: ScheduleId,
+		paused: bool,
+		next_run_at: OffsetDateTime,
+	) -> Result<(), PauseScheduleError>;
+
+	/// Delete a schedule
+	async fn del_schedule(&self, schedule: ScheduleId) -> Result<(), DeleteScheduleError>;
+
+	/// Find all unpaused schedules that are due at `now`, and move each
+	/// one's `next_run_at` to the first time its trigger fires after `now`.
+	/// Schedules that will never fire again are paused.
+	///
+	/// Returns the claimed schedules as they were before this update,
+	/// so `next_run_at` is the time each one was due.
+	/// A schedule is never returned by two calls, even if they run concurrently.
+	async fn claim_due_schedules(
+		&self,
+		now: OffsetDateTime,
+	) -> Result<Vec<ScheduleInfo>, ClaimSchedulesError>;
+
+	/// Undo the claim of one run of `schedule`, so that the next call to
+	/// [`DatabaseClient::claim_due_schedules`] claims it again.
+	/// This should be called if we could not start that run.
+	///
+	/// `schedule` must be a schedule returned by a call to `claim_due_schedules`
+	/// with the given `now`. If that schedule has changed since, this does nothing.
+	async fn unclaim_schedule(
+		&self,
+		schedule: &ScheduleInfo,
+		now: OffsetDateTime,
+	) -> Result<(), ClaimSchedulesError>;
+
+	//
+	// MARK: Webhooks
+	//
+
+	/// Get all a user's webhooks
+	async fn list_webhooks(&self, for_user: UserId) -> Result<Vec<WebhookInfo>, ListWebhookError>;
+
+	/// Create a new webhook
+	async fn add_webhook(
+		&self,
+		for_user: UserId,
+		url: &str,
+		events: &[WebhookEventKind],
+		secret: &str,
+	) -> Result<WebhookInfo, AddWebhookError>;
+
+	/// Get a webhook by id
+	async fn get_webhook(&self, webhook: WebhookId)
+		-> Result<Option<WebhookInfo>, GetWebhookError>;
+
+	/// Delete a webhook and all its deliveries
+	async fn del_webhook(&self, webhook: WebhookId) -> Result<(), DeleteWebhookError>;
+
+	/// Get every user that has at least one webhook
+	async fn list_webhook_users(&self) -> Result<BTreeSet<UserId>, ListWebhookError>;
+
+	/// Queue a delivery of `event` to each of `for_user`'s webhooks that subscribe to it.
+	/// A webhook never receives two deliveries of the same event
+	/// (see [`WebhookEvent::key`]), so this may safely be called more than once.
+	///
+	/// Returns the number of deliveries we queued.
+	async fn queue_webhook_event(
+		&self,
+		for_user: UserId,
+		event: &WebhookEvent,
+	) -> Result<u64, QueueWebhookEventError>;
+
+	/// Get the finish time of the newest job we've queued webhook events for
+	async fn get_webhook_cursor(&self) -> Result<OffsetDateTime, WebhookCursorError>;
+
+	/// Record that we've queued webhook events for every job that finished at or before `to`.
+	/// The cursor never moves backwards, so this may safely be called concurrently.
+	async fn advance_webhook_cursor(&self, to: OffsetDateTime) -> Result<(), WebhookCursorError>;
+
+	/// Claim at most `count` pending deliveries that are due at `now`,
+	/// and start a new attempt of each one.
+	///
+	/// Claimed deliveries aren't claimed again until `lease` passes,
+	/// so the caller must record the result of each attempt before then.
+	async fn claim_webhook_deliveries(
+		&self,
+		now: OffsetDateTime,
+		lease: Duration,
+		count: i64,
+	) -> Result<Vec<(WebhookInfo, WebhookDeliveryInfo)>, ClaimWebhookDeliveriesError>;
+
+	/// Save the result of a delivery attempt.
+	/// If `state` is `Pending`, this delivery is attempted again at `next_attempt_at`.
+	async fn record_webhook_attempt(
+		&self,
+		delivery: WebhookDeliveryId,
+		state: WebhookDeliveryState,
+		status: Option<u16>,
+		error: Option<&str>,
+		next_attempt_at: Option<OffsetDateTime>,
+	) -> Result<(), RecordWebhookAttemptError>;
+
+	/// Get a webhook's `count` most recent deliveries, newest first
+	async fn list_webhook_deliveries(
+		&self,
+		webhook: WebhookId,
+		count: i64,
+	) -> Result<Vec<WebhookDeliveryInfo>, ListWebhookDeliveriesError>;
 }
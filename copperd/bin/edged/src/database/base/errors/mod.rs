@@ -1,4 +1,6 @@
 //! Errors produced by database operations
 
 pub mod pipeline;
+pub mod schedule;
 pub mod user;
+pub mod webhook;
@@ -0,0 +1,52 @@
+//! Errors we can encounter when operating on schedules
+
+use thiserror::Error;
+
+/// An error we can encounter when creating a schedule
+#[derive(Debug, Error)]
+pub enum AddScheduleError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when getting a schedule
+#[derive(Debug, Error)]
+pub enum GetScheduleError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when listing a user's schedules
+#[derive(Debug, Error)]
+pub enum ListScheduleError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when pausing or resuming a schedule
+#[derive(Debug, Error)]
+pub enum PauseScheduleError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when deleting a schedule
+#[derive(Debug, Error)]
+pub enum DeleteScheduleError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when claiming due schedules,
+/// or releasing a claim
+#[derive(Debug, Error)]
+pub enum ClaimSchedulesError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
@@ -0,0 +1,75 @@
+//! Errors we can encounter when operating on webhooks
+
+use thiserror::Error;
+
+/// An error we can encounter when creating a webhook
+#[derive(Debug, Error)]
+pub enum AddWebhookError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when getting a webhook
+#[derive(Debug, Error)]
+pub enum GetWebhookError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when listing a user's webhooks
+#[derive(Debug, Error)]
+pub enum ListWebhookError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when deleting a webhook
+#[derive(Debug, Error)]
+pub enum DeleteWebhookError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when queueing deliveries for an event
+#[derive(Debug, Error)]
+pub enum QueueWebhookEventError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when claiming due deliveries
+#[derive(Debug, Error)]
+pub enum ClaimWebhookDeliveriesError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when saving the result of a delivery attempt
+#[derive(Debug, Error)]
+pub enum RecordWebhookAttemptError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when listing a webhook's deliveries
+#[derive(Debug, Error)]
+pub enum ListWebhookDeliveriesError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when reading or moving the webhook event cursor
+#[derive(Debug, Error)]
+pub enum WebhookCursorError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
@@ -1,9 +1,19 @@
 use async_trait::async_trait;
-use copper_edged::{PipelineId, PipelineInfo, UserInfo, UserPassword};
-use copper_itemdb::UserId;
+use copper_edged::{
+	schedule::ScheduleTrigger,
+	webhook::{WebhookEvent, WebhookEventKind},
+	PipelineId, PipelineInfo, ScheduleId, ScheduleInfo, UserInfo, UserPassword, WebhookDeliveryId,
+	WebhookDeliveryInfo, WebhookDeliveryState, WebhookId, WebhookInfo,
+};
+use copper_itemdb::{AttrData, UserId};
 use copper_piper::json::PipelineJson;
 use copper_util::names::check_name;
-use sqlx::{Connection, Row};
+use smartstring::{LazyCompact, SmartString};
+use sqlx::{postgres::PgRow, types::time::OffsetDateTime, Connection, Row};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	time::Duration,
+};
 
 use super::PgDatabaseClient;
 use crate::database::base::{
@@ -13,10 +23,60 @@ use crate::database::base::{
 			AddPipelineError, DeletePipelineError, GetPipelineError, ListPipelineError,
 			UpdatePipelineError,
 		},
+		schedule::{
+			AddScheduleError, ClaimSchedulesError, DeleteScheduleError, GetScheduleError,
+			ListScheduleError, PauseScheduleError,
+		},
 		user::{AddUserError, DeleteUserError, GetUserError, UpdateUserError},
+		webhook::{
+			AddWebhookError, ClaimWebhookDeliveriesError, DeleteWebhookError, GetWebhookError,
+			ListWebhookDeliveriesError, ListWebhookError, QueueWebhookEventError,
+			RecordWebhookAttemptError, WebhookCursorError,
+		},
 	},
 };
 
+fn schedule_from_row(row: &PgRow) -> ScheduleInfo {
+	ScheduleInfo {
+		id: row.get::<i64, _>("id").into(),
+		owned_by: row.get::<i64, _>("owned_by").into(),
+		pipeline: row.get::<i64, _>("pipeline").into(),
+		trigger: serde_json::from_str(row.get::<&str, _>("trigger")).unwrap(),
+		input: serde_json::from_str(row.get::<&str, _>("input")).unwrap(),
+		paused: row.get("paused"),
+		next_run_at: row.get("next_run_at"),
+		last_run_at: row.get("last_run_at"),
+	}
+}
+
+fn webhook_from_row(row: &PgRow) -> WebhookInfo {
+	WebhookInfo {
+		id: row.get::<i64, _>("id").into(),
+		owned_by: row.get::<i64, _>("owned_by").into(),
+		url: row.get::<&str, _>("url").into(),
+		events: serde_json::from_str(row.get::<&str, _>("events")).unwrap(),
+		secret: row.get::<&str, _>("secret").into(),
+		created_at: row.get("created_at"),
+	}
+}
+
+fn delivery_from_row(row: &PgRow) -> WebhookDeliveryInfo {
+	WebhookDeliveryInfo {
+		id: row.get::<i64, _>("id").into(),
+		webhook: row.get::<i64, _>("webhook").into(),
+		event: serde_json::from_str(row.get::<&str, _>("event")).unwrap(),
+		state: serde_json::from_str(row.get::<&str, _>("state")).unwrap(),
+		attempt: u32::try_from(row.get::<i32, _>("attempt")).unwrap(),
+		last_status: row
+			.get::<Option<i32>, _>("last_status")
+			.map(|x| u16::try_from(x).unwrap()),
+		last_error: row.get("last_error"),
+		created_at: row.get("created_at"),
+		next_attempt_at: row.get("next_attempt_at"),
+		delivered_at: row.get("delivered_at"),
+	}
+}
+
 #[async_trait]
 impl DatabaseClient for PgDatabaseClient {
 	//
@@ -312,4 +372,440 @@ impl DatabaseClient for PgDatabaseClient {
 
 		return Ok(());
 	}
+
+	//
+	// MARK: Schedule
+	//
+
+	async fn list_schedules(
+		&self,
+		for_user: UserId,
+	) -> Result<Vec<ScheduleInfo>, ListScheduleError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let res = sqlx::query("SELECT * FROM schedules WHERE owned_by=$1 ORDER BY id;")
+			.bind(i64::from(for_user))
+			.fetch_all(&mut *conn)
+			.await?;
+
+		return Ok(res.iter().map(schedule_from_row).collect());
+	}
+
+	async fn add_schedule(
+		&self,
+		for_user: UserId,
+		pipeline: PipelineId,
+		trigger: &ScheduleTrigger,
+		input: &BTreeMap<SmartString<LazyCompact>, AttrData>,
+		next_run_at: OffsetDateTime,
+	) -> Result<ScheduleInfo, AddScheduleError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let res = sqlx::query(
+			"
+			INSERT INTO schedules (owned_by, pipeline, trigger, input, paused, next_run_at)
+			VALUES ($1, $2, $3, $4, FALSE, $5)
+			RETURNING *;
+			",
+		)
+		.bind(i64::from(for_user))
+		.bind(i64::from(pipeline))
+		.bind(serde_json::to_string(trigger).unwrap())
+		.bind(serde_json::to_string(input).unwrap())
+		.bind(next_run_at)
+		.fetch_one(&mut *conn)
+		.await?;
+
+		return Ok(schedule_from_row(&res));
+	}
+
+	async fn get_schedule(
+		&self,
+		schedule: ScheduleId,
+	) -> Result<Option<ScheduleInfo>, GetScheduleError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let res = sqlx::query("SELECT * FROM schedules WHERE id=$1;")
+			.bind(i64::from(schedule))
+			.fetch_one(&mut *conn)
+			.await;
+
+		return match res {
+			Err(sqlx::Error::RowNotFound) => Ok(None),
+			Err(e) => Err(e.into()),
+			Ok(res) => Ok(Some(schedule_from_row(&res))),
+		};
+	}
+
+	async fn set_schedule_paused(
+		&self,
+		schedule: ScheduleId,
+		paused: bool,
+		next_run_at: OffsetDateTime,
+	) -> Result<(), PauseScheduleError> {
+		let mut conn = self.pool.acquire().await?;
+
+		sqlx::query("UPDATE schedules SET paused=$1, next_run_at=$2 WHERE id=$3;")
+			.bind(paused)
+			.bind(next_run_at)
+			.bind(i64::from(schedule))
+			.execute(&mut *conn)
+			.await?;
+
+		return Ok(());
+	}
+
+	async fn del_schedule(&self, schedule: ScheduleId) -> Result<(), DeleteScheduleError> {
+		let mut conn = self.pool.acquire().await?;
+
+		sqlx::query("DELETE FROM schedules WHERE id=$1;")
+			.bind(i64::from(schedule))
+			.execute(&mut *conn)
+			.await?;
+
+		return Ok(());
+	}
+
+	async fn claim_due_schedules(
+		&self,
+		now: OffsetDateTime,
+	) -> Result<Vec<ScheduleInfo>, ClaimSchedulesError> {
+		let mut conn = self.pool.acquire().await?;
+		let mut t = conn.begin().await?;
+
+		// Skip locked rows, so that concurrent
+		// schedulers never claim the same run.
+		let res = sqlx::query(
+			"
+			SELECT * FROM schedules
+			WHERE paused = FALSE
+			AND next_run_at <= $1
+			FOR UPDATE SKIP LOCKED;
+			",
+		)
+		.bind(now)
+		.fetch_all(&mut *t)
+		.await?;
+
+		let mut out = Vec::new();
+		for row in res {
+			let schedule = schedule_from_row(&row);
+
+			// Always schedule the next run after `now`, so that
+			// runs we missed (for example, while edged was down)
+			// are skipped instead of all firing at once.
+			let next_run_at = schedule.trigger.next_after(now);
+
+			sqlx::query(
+				"
+				UPDATE schedules
+				SET next_run_at=$1, paused=$2, last_run_at=$3
+				WHERE id=$4;
+				",
+			)
+			.bind(next_run_at.unwrap_or(schedule.next_run_at))
+			.bind(next_run_at.is_none())
+			.bind(now)
+			.bind(i64::from(schedule.id))
+			.execute(&mut *t)
+			.await?;
+
+			out.push(schedule);
+		}
+
+		t.commit().await?;
+
+		return Ok(out);
+	}
+
+	async fn unclaim_schedule(
+		&self,
+		schedule: &ScheduleInfo,
+		now: OffsetDateTime,
+	) -> Result<(), ClaimSchedulesError> {
+		let mut conn = self.pool.acquire().await?;
+
+		// The values `claim_due_schedules` set
+		let next_run_at = schedule.trigger.next_after(now);
+
+		sqlx::query(
+			"
+			UPDATE schedules
+			SET next_run_at=$1, paused=FALSE, last_run_at=$2
+			WHERE id=$3
+			AND next_run_at=$4 AND paused=$5 AND last_run_at=$6;
+			",
+		)
+		.bind(schedule.next_run_at)
+		.bind(schedule.last_run_at)
+		.bind(i64::from(schedule.id))
+		.bind(next_run_at.unwrap_or(schedule.next_run_at))
+		.bind(next_run_at.is_none())
+		.bind(now)
+		.execute(&mut *conn)
+		.await?;
+
+		return Ok(());
+	}
+
+	//
+	// MARK: Webhook
+	//
+
+	async fn list_webhooks(&self, for_user: UserId) -> Result<Vec<WebhookInfo>, ListWebhookError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let res = sqlx::query("SELECT * FROM webhooks WHERE owned_by=$1 ORDER BY id;")
+			.bind(i64::from(for_user))
+			.fetch_all(&mut *conn)
+			.await?;
+
+		return Ok(res.iter().map(webhook_from_row).collect());
+	}
+
+	async fn add_webhook(
+		&self,
+		for_user: UserId,
+		url: &str,
+		events: &[WebhookEventKind],
+		secret: &str,
+	) -> Result<WebhookInfo, AddWebhookError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let res = sqlx::query(
+			"
+			INSERT INTO webhooks (owned_by, url, events, secret, created_at)
+			VALUES ($1, $2, $3, $4, $5)
+			RETURNING *;
+			",
+		)
+		.bind(i64::from(for_user))
+		.bind(url)
+		.bind(serde_json::to_string(events).unwrap())
+		.bind(secret)
+		.bind(OffsetDateTime::now_utc())
+		.fetch_one(&mut *conn)
+		.await?;
+
+		return Ok(webhook_from_row(&res));
+	}
+
+	async fn get_webhook(
+		&self,
+		webhook: WebhookId,
+	) -> Result<Option<WebhookInfo>, GetWebhookError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let res = sqlx::query("SELECT * FROM webhooks WHERE id=$1;")
+			.bind(i64::from(webhook))
+			.fetch_one(&mut *conn)
+			.await;
+
+		return match res {
+			Err(sqlx::Error::RowNotFound) => Ok(None),
+			Err(e) => Err(e.into()),
+			Ok(res) => Ok(Some(webhook_from_row(&res))),
+		};
+	}
+
+	async fn del_webhook(&self, webhook: WebhookId) -> Result<(), DeleteWebhookError> {
+		let mut conn = self.pool.acquire().await?;
+
+		sqlx::query("DELETE FROM webhooks WHERE id=$1;")
+			.bind(i64::from(webhook))
+			.execute(&mut *conn)
+			.await?;
+
+		return Ok(());
+	}
+
+	async fn list_webhook_users(&self) -> Result<BTreeSet<UserId>, ListWebhookError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let res = sqlx::query("SELECT DISTINCT owned_by FROM webhooks;")
+			.fetch_all(&mut *conn)
+			.await?;
+
+		return Ok(res
+			.iter()
+			.map(|row| row.get::<i64, _>("owned_by").into())
+			.collect());
+	}
+
+	async fn queue_webhook_event(
+		&self,
+		for_user: UserId,
+		event: &WebhookEvent,
+	) -> Result<u64, QueueWebhookEventError> {
+		let mut conn = self.pool.acquire().await?;
+		let now = OffsetDateTime::now_utc();
+
+		let res = sqlx::query("SELECT * FROM webhooks WHERE owned_by=$1;")
+			.bind(i64::from(for_user))
+			.fetch_all(&mut *conn)
+			.await?;
+
+		let mut n_queued = 0;
+		for webhook in res.iter().map(webhook_from_row) {
+			if !webhook.events.contains(&event.kind()) {
+				continue;
+			}
+
+			let res = sqlx::query(
+				"
+				INSERT INTO webhook_deliveries (
+					webhook, event_key, event, state, created_at, next_attempt_at
+				)
+				VALUES ($1, $2, $3, $4, $5, $5)
+				ON CONFLICT (webhook, event_key) DO NOTHING;
+				",
+			)
+			.bind(i64::from(webhook.id))
+			.bind(event.key())
+			.bind(serde_json::to_string(event).unwrap())
+			.bind(serde_json::to_string(&WebhookDeliveryState::Pending).unwrap())
+			.bind(now)
+			.execute(&mut *conn)
+			.await?;
+
+			n_queued += res.rows_affected();
+		}
+
+		return Ok(n_queued);
+	}
+
+	async fn get_webhook_cursor(&self) -> Result<OffsetDateTime, WebhookCursorError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let res = sqlx::query("SELECT finished_at FROM webhook_cursor;")
+			.fetch_one(&mut *conn)
+			.await?;
+
+		return Ok(res.get("finished_at"));
+	}
+
+	async fn advance_webhook_cursor(&self, to: OffsetDateTime) -> Result<(), WebhookCursorError> {
+		let mut conn = self.pool.acquire().await?;
+
+		sqlx::query("UPDATE webhook_cursor SET finished_at = GREATEST(finished_at, $1);")
+			.bind(to)
+			.execute(&mut *conn)
+			.await?;
+
+		return Ok(());
+	}
+
+	async fn claim_webhook_deliveries(
+		&self,
+		now: OffsetDateTime,
+		lease: Duration,
+		count: i64,
+	) -> Result<Vec<(WebhookInfo, WebhookDeliveryInfo)>, ClaimWebhookDeliveriesError> {
+		let mut conn = self.pool.acquire().await?;
+		let mut t = conn.begin().await?;
+
+		// Moving `next_attempt_at` past the lease keeps other
+		// senders from claiming these deliveries while we try them.
+		let res = sqlx::query(
+			"
+			UPDATE webhook_deliveries
+			SET attempt = attempt + 1, next_attempt_at = $1
+			WHERE id IN (
+				SELECT id FROM webhook_deliveries
+				WHERE state = $2
+				AND next_attempt_at <= $3
+				ORDER BY next_attempt_at
+				LIMIT $4
+				FOR UPDATE SKIP LOCKED
+			)
+			RETURNING *;
+			",
+		)
+		.bind(now + lease)
+		.bind(serde_json::to_string(&WebhookDeliveryState::Pending).unwrap())
+		.bind(now)
+		.bind(count)
+		.fetch_all(&mut *t)
+		.await?;
+
+		let deliveries: Vec<WebhookDeliveryInfo> = res.iter().map(delivery_from_row).collect();
+		let webhook_ids: Vec<i64> = deliveries.iter().map(|x| i64::from(x.webhook)).collect();
+
+		let res = sqlx::query("SELECT * FROM webhooks WHERE id = ANY($1);")
+			.bind(&webhook_ids)
+			.fetch_all(&mut *t)
+			.await?;
+
+		let webhooks: BTreeMap<WebhookId, WebhookInfo> = res
+			.iter()
+			.map(webhook_from_row)
+			.map(|x| (x.id, x))
+			.collect();
+
+		t.commit().await?;
+
+		// Deliveries are deleted with their webhook,
+		// so every delivery's webhook should exist.
+		return Ok(deliveries
+			.into_iter()
+			.filter_map(|d| webhooks.get(&d.webhook).map(|w| (w.clone(), d)))
+			.collect());
+	}
+
+	async fn record_webhook_attempt(
+		&self,
+		delivery: WebhookDeliveryId,
+		state: WebhookDeliveryState,
+		status: Option<u16>,
+		error: Option<&str>,
+		next_attempt_at: Option<OffsetDateTime>,
+	) -> Result<(), RecordWebhookAttemptError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let delivered_at = match state {
+			WebhookDeliveryState::Delivered => Some(OffsetDateTime::now_utc()),
+			_ => None,
+		};
+
+		sqlx::query(
+			"
+			UPDATE webhook_deliveries
+			SET state=$1, last_status=$2, last_error=$3, next_attempt_at=$4, delivered_at=$5
+			WHERE id=$6;
+			",
+		)
+		.bind(serde_json::to_string(&state).unwrap())
+		.bind(status.map(i32::from))
+		.bind(error)
+		.bind(next_attempt_at)
+		.bind(delivered_at)
+		.bind(i64::from(delivery))
+		.execute(&mut *conn)
+		.await?;
+
+		return Ok(());
+	}
+
+	async fn list_webhook_deliveries(
+		&self,
+		webhook: WebhookId,
+		count: i64,
+	) -> Result<Vec<WebhookDeliveryInfo>, ListWebhookDeliveriesError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let res = sqlx::query(
+			"
+			SELECT * FROM webhook_deliveries
+			WHERE webhook=$1
+			ORDER BY created_at DESC, id DESC
+			LIMIT $2;
+			",
+		)
+		.bind(i64::from(webhook))
+		.bind(count)
+		.fetch_all(&mut *conn)
+		.await?;
+
+		return Ok(res.iter().map(delivery_from_row).collect());
+	}
 }
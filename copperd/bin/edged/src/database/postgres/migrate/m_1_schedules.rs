@@ -0,0 +1,44 @@
+use copper_migrate::Migration;
+use sqlx::Connection;
+
+pub(super) struct MigrationStep {}
+
+#[async_trait::async_trait]
+impl Migration for MigrationStep {
+	fn name(&self) -> &str {
+		"m_1_schedules"
+	}
+
+	async fn up(&self, conn: &mut sqlx::PgConnection) -> Result<(), sqlx::Error> {
+		let mut t = conn.begin().await?;
+
+		sqlx::query(
+			"
+			CREATE TABLE schedules (
+				id BIGSERIAL PRIMARY KEY,
+				owned_by BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+				pipeline BIGINT NOT NULL REFERENCES pipelines(id) ON DELETE CASCADE,
+				trigger TEXT NOT NULL,
+				input TEXT NOT NULL,
+				paused BOOLEAN NOT NULL,
+				next_run_at TIMESTAMPTZ NOT NULL,
+				last_run_at TIMESTAMPTZ
+			);
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		sqlx::query("CREATE INDEX schedule_owned_by on schedules(owned_by);")
+			.execute(&mut *t)
+			.await?;
+
+		sqlx::query("CREATE INDEX schedule_next_run_at on schedules(next_run_at);")
+			.execute(&mut *t)
+			.await?;
+
+		t.commit().await?;
+
+		return Ok(());
+	}
+}
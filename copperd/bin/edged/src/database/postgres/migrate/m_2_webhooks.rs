@@ -0,0 +1,73 @@
+use copper_migrate::Migration;
+use sqlx::Connection;
+
+pub(super) struct MigrationStep {}
+
+#[async_trait::async_trait]
+impl Migration for MigrationStep {
+	fn name(&self) -> &str {
+		"m_2_webhooks"
+	}
+
+	async fn up(&self, conn: &mut sqlx::PgConnection) -> Result<(), sqlx::Error> {
+		let mut t = conn.begin().await?;
+
+		sqlx::query(
+			"
+			CREATE TABLE webhooks (
+				id BIGSERIAL PRIMARY KEY,
+				owned_by BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+				url TEXT NOT NULL,
+				events TEXT NOT NULL,
+				secret TEXT NOT NULL,
+				created_at TIMESTAMPTZ NOT NULL
+			);
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		sqlx::query("CREATE INDEX webhook_owned_by on webhooks(owned_by);")
+			.execute(&mut *t)
+			.await?;
+
+		// `event_key` is unique per webhook, so that every
+		// edged instance may queue the same event safely.
+		sqlx::query(
+			"
+			CREATE TABLE webhook_deliveries (
+				id BIGSERIAL PRIMARY KEY,
+				webhook BIGINT NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+				event_key TEXT NOT NULL,
+				event TEXT NOT NULL,
+				state TEXT NOT NULL,
+				attempt INTEGER NOT NULL DEFAULT 0,
+				last_status INTEGER,
+				last_error TEXT,
+				created_at TIMESTAMPTZ NOT NULL,
+				next_attempt_at TIMESTAMPTZ,
+				delivered_at TIMESTAMPTZ,
+				UNIQUE (webhook, event_key)
+			);
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		sqlx::query(
+			"CREATE INDEX webhook_delivery_due on webhook_deliveries(state, next_attempt_at);",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		sqlx::query(
+			"CREATE INDEX webhook_delivery_log on webhook_deliveries(webhook, created_at);",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		t.commit().await?;
+
+		return Ok(());
+	}
+}
@@ -0,0 +1,37 @@
+use copper_migrate::Migration;
+use sqlx::Connection;
+
+pub(super) struct MigrationStep {}
+
+#[async_trait::async_trait]
+impl Migration for MigrationStep {
+	fn name(&self) -> &str {
+		"m_3_webhook_cursor"
+	}
+
+	async fn up(&self, conn: &mut sqlx::PgConnection) -> Result<(), sqlx::Error> {
+		let mut t = conn.begin().await?;
+
+		// How far we've queued webhook events for finished jobs.
+		// This table always has exactly one row.
+		sqlx::query(
+			"
+			CREATE TABLE webhook_cursor (
+				id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+				finished_at TIMESTAMPTZ NOT NULL
+			);
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		// Don't send events for jobs that finished before webhooks were queued this way
+		sqlx::query("INSERT INTO webhook_cursor (finished_at) VALUES (NOW());")
+			.execute(&mut *t)
+			.await?;
+
+		t.commit().await?;
+
+		return Ok(());
+	}
+}
@@ -1,5 +1,13 @@
 use copper_migrate::Migration;
 
 mod m_0_init;
+mod m_1_schedules;
+mod m_2_webhooks;
+mod m_3_webhook_cursor;
 
-pub const MIGRATE_STEPS: &[&'static dyn Migration] = &[&m_0_init::MigrationStep {}];
+pub const MIGRATE_STEPS: &[&'static dyn Migration] = &[
+	&m_0_init::MigrationStep {},
+	&m_1_schedules::MigrationStep {},
+	&m_2_webhooks::MigrationStep {},
+	&m_3_webhook_cursor::MigrationStep {},
+];
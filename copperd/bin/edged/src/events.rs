@@ -0,0 +1,175 @@
+//! Delivers job and upload events to connected users
+
+use copper_itemdb::UserId;
+use copper_jobqueue::{info::JobEvent, postgres::PgJobQueueClient};
+use serde::Serialize;
+use std::{
+	collections::BTreeMap,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+use tokio::sync::broadcast;
+use tracing::error;
+use utoipa::ToSchema;
+
+use crate::uploader::UploadJobId;
+
+/// How many events we buffer for each user's subscribers.
+/// Subscribers that fall further behind miss events.
+const EVENT_BUFFER_SIZE: usize = 1024;
+
+/// How long to wait before listening again
+/// if we lose our job event listener's connection.
+const LISTEN_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Something that happened to an upload
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "upload_event")]
+pub enum UploadEvent {
+	/// A part of this upload was received
+	PartUploaded {
+		/// The number of parts received so far
+		n_parts: usize,
+
+		/// The number of bytes received so far
+		n_bytes: u64,
+	},
+
+	/// This upload is finished, and may be used by a job
+	Finished,
+}
+
+/// Something that happened to one of a user's jobs or uploads
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "type")]
+pub enum UserEvent {
+	/// A job changed state
+	Job(JobEvent),
+
+	/// An upload made progress
+	Upload {
+		#[schema(value_type = String)]
+		upload_id: UploadJobId,
+
+		#[serde(skip)]
+		owner: UserId,
+
+		/// What happened
+		event: UploadEvent,
+	},
+}
+
+impl UserEvent {
+	/// The user this event should be delivered to
+	pub fn user(&self) -> UserId {
+		match self {
+			Self::Job(x) => x.owned_by,
+			Self::Upload { owner, .. } => *owner,
+		}
+	}
+}
+
+/// Fans events out to everyone who is listening.
+///
+/// Each user's events go through that user's own channel,
+/// so a burst of events for one user never makes
+/// another user's subscribers fall behind.
+pub struct EventBus {
+	/// One channel per user with subscribers
+	users: Mutex<BTreeMap<UserId, broadcast::Sender<UserEvent>>>,
+}
+
+impl EventBus {
+	pub fn new() -> Self {
+		Self {
+			users: Mutex::new(BTreeMap::new()),
+		}
+	}
+
+	/// Send an event to all current subscribers
+	pub fn send(&self, event: UserEvent) {
+		let user = event.user();
+		let mut users = self.users.lock().unwrap();
+
+		if let Some(sender) = users.get(&user) {
+			// This only fails if nobody is listening,
+			// in which case we don't need this channel anymore.
+			if sender.send(event).is_err() {
+				users.remove(&user);
+			}
+		}
+	}
+
+	/// Receive every event for `user` sent after this call
+	pub fn subscribe(&self, user: UserId) -> broadcast::Receiver<UserEvent> {
+		let mut users = self.users.lock().unwrap();
+
+		// Forget users nobody is listening to
+		users.retain(|_, x| x.receiver_count() != 0);
+
+		users
+			.entry(user)
+			.or_insert_with(|| broadcast::channel(EVENT_BUFFER_SIZE).0)
+			.subscribe()
+	}
+}
+
+/// Forward job events from the job queue to `bus`.
+/// This never returns.
+pub async fn forward_job_events(bus: Arc<EventBus>, jobqueue_client: Arc<PgJobQueueClient>) {
+	loop {
+		let mut listener = match jobqueue_client.listen_events().await {
+			Ok(x) => x,
+			Err(error) => {
+				error!(message = "Could not listen for job events", ?error);
+				tokio::time::sleep(LISTEN_RETRY_DELAY).await;
+				continue;
+			}
+		};
+
+		loop {
+			match listener.recv().await {
+				Ok(event) => bus.send(UserEvent::Job(event)),
+				Err(error) => {
+					error!(message = "Lost job event listener", ?error);
+					tokio::time::sleep(LISTEN_RETRY_DELAY).await;
+					break;
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn upload_event(owner: i64) -> UserEvent {
+		UserEvent::Upload {
+			upload_id: UploadJobId::new(),
+			owner: owner.into(),
+			event: UploadEvent::Finished,
+		}
+	}
+
+	#[test]
+	fn busy_user_does_not_lag_others() {
+		let bus = EventBus::new();
+		let mut busy = bus.subscribe(1.into());
+		let mut quiet = bus.subscribe(2.into());
+
+		for _ in 0..EVENT_BUFFER_SIZE * 2 {
+			bus.send(upload_event(1));
+		}
+		bus.send(upload_event(2));
+
+		assert!(matches!(
+			busy.try_recv(),
+			Err(broadcast::error::TryRecvError::Lagged(_))
+		));
+
+		let event = quiet.try_recv().unwrap();
+		assert_eq!(event.user(), 2.into());
+		assert!(quiet.try_recv().is_err());
+	}
+}
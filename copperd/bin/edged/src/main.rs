@@ -7,11 +7,13 @@ use config::EdgedConfig;
 use copper_edged::UserPassword;
 use copper_itemdb::client::{ItemdbClient, ItemdbOpenError};
 use copper_jobqueue::postgres::{PgJobQueueClient, PgJobQueueOpenError};
+use copper_piper::base::NodeDispatcher;
 use copper_util::{load_env, s3client::S3Client, LoadedEnv};
 use database::{
 	base::client::DatabaseClient,
 	postgres::{PgDatabaseClient, PgDatabaseOpenError},
 };
+use events::EventBus;
 use std::sync::Arc;
 use tracing::{error, info, trace, warn};
 use uploader::Uploader;
@@ -21,7 +23,11 @@ mod config;
 mod database;
 
 mod auth;
+mod cleanup;
+mod events;
+mod scheduler;
 mod uploader;
+mod webhooks;
 
 async fn make_app(config: Arc<EdgedConfig>, s3_client: Arc<S3Client>) -> Router {
 	// Connect to database
@@ -115,17 +121,59 @@ async fn make_app(config: Arc<EdgedConfig>, s3_client: Arc<S3Client>) -> Router
 		)
 	}
 
+	// Register nodes, so we can check pipelines
+	let mut dispatcher = NodeDispatcher::new();
+
+	if let Err((module, error)) = nodes_all::register_all(&mut dispatcher) {
+		error!(message = "Could not register nodes", module, ?error);
+		std::process::exit(1);
+	}
+
+	let db = Arc::new(db);
+
+	// Start scheduled jobs
+	tokio::spawn(scheduler::run_scheduler(
+		config.clone(),
+		db.clone(),
+		jobqueue_client.clone(),
+	));
+
+	// Deliver job events to connected users
+	let events = Arc::new(EventBus::new());
+	tokio::spawn(events::forward_job_events(
+		events.clone(),
+		jobqueue_client.clone(),
+	));
+
+	// Notify users' webhooks when jobs finish
+	tokio::spawn(webhooks::run_webhook_queuer(
+		config.clone(),
+		db.clone(),
+		jobqueue_client.clone(),
+	));
+	tokio::spawn(webhooks::run_webhook_sender(config.clone(), db.clone()));
+
+	// Delete expired jobs
+	tokio::spawn(cleanup::run_job_cleanup(
+		config.clone(),
+		jobqueue_client.clone(),
+		s3_client.clone(),
+	));
+
 	// Create app
 	return api::router(RouterState {
 		config: config.clone(),
-		db_client: Arc::new(db),
+		db_client: db,
 		auth: Arc::new(AuthHelper::new()),
 		uploader: Arc::new(Uploader::new(
 			config.clone(),
 			s3_client.clone(),
 			jobqueue_client.clone(),
+			events.clone(),
 		)),
+		events,
 
+		dispatcher: Arc::new(dispatcher),
 		jobqueue_client,
 		itemdb_client,
 		s3_client,
@@ -205,6 +253,26 @@ async fn main() {
 		}
 	}
 
+	// Create job archive bucket if it doesn't exist
+	if let Some(bucket) = &config.edged_job_archive_bucket {
+		match client.create_bucket(bucket).await {
+			Ok(false) => {}
+			Ok(true) => {
+				info!(
+					message = "Created job archive bucket because it didn't exist",
+					bucket
+				);
+			}
+			Err(error) => {
+				error!(
+					message = "Error while creating job archive bucket",
+					bucket,
+					?error
+				);
+			}
+		}
+	}
+
 	let listener = match tokio::net::TcpListener::bind(config.edged_server_addr.to_string()).await {
 		Ok(x) => x,
 		Err(e) => {
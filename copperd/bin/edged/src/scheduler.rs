@@ -0,0 +1,201 @@
+//! Starts jobs for scheduled pipeline runs
+
+use copper_edged::ScheduleInfo;
+use copper_jobqueue::{
+	base::{
+		client::JobQueueClient,
+		errors::{AddJobError, GetJobShortError},
+	},
+	info::NewJob,
+	scheduling::JobPriority,
+};
+use std::{sync::Arc, time::Duration};
+use time::OffsetDateTime;
+use tracing::{debug, error, info, warn};
+
+use crate::{config::EdgedConfig, database::base::client::DatabaseClient};
+
+/// Runs that were due more than this long ago are skipped.
+/// If we check schedules less often than this, the grace
+/// period is twice our check interval instead.
+///
+/// This keeps us from starting stale jobs after edged has been down,
+/// or has been too busy to check schedules.
+const MIN_MISSED_RUN_GRACE: Duration = Duration::from_secs(60);
+
+/// The ids of all jobs we start begin with this.
+/// Users may not choose job ids that start with this,
+/// so that nobody can take the id of a run before it starts.
+pub const SCHEDULED_JOB_PREFIX: &str = "schedule-";
+
+/// Periodically start jobs for all due schedules.
+/// This never returns.
+pub async fn run_scheduler<Client: DatabaseClient>(
+	config: Arc<EdgedConfig>,
+	db_client: Arc<Client>,
+	jobqueue_client: Arc<dyn JobQueueClient>,
+) {
+	let interval = Duration::from_secs(config.edged_scheduler_interval_secs);
+	let grace = MIN_MISSED_RUN_GRACE.max(interval.saturating_mul(2));
+
+	loop {
+		tokio::time::sleep(interval).await;
+
+		let now = OffsetDateTime::now_utc();
+		let due = match db_client.claim_due_schedules(now).await {
+			Ok(x) => x,
+			Err(error) => {
+				error!(message = "Could not get due schedules", ?error);
+				continue;
+			}
+		};
+
+		for schedule in due {
+			if now - schedule.next_run_at > grace {
+				info!(
+					message = "Skipping missed scheduled run",
+					schedule = ?schedule.id,
+					due_at = ?schedule.next_run_at
+				);
+				continue;
+			}
+
+			let started =
+				start_scheduled_job(&config, &*db_client, &*jobqueue_client, &schedule).await;
+
+			// Try again next time, until this run is out of its grace period
+			if !started {
+				if let Err(error) = db_client.unclaim_schedule(&schedule, now).await {
+					error!(
+						message = "Could not release scheduled run, it will be skipped",
+						schedule = ?schedule.id,
+						?error
+					);
+				}
+			}
+		}
+	}
+}
+
+/// Queue the job for one run of a schedule.
+///
+/// Returns `false` if we could not queue this job,
+/// but may be able to if we try again later.
+async fn start_scheduled_job<Client: DatabaseClient>(
+	config: &EdgedConfig,
+	db_client: &Client,
+	jobqueue_client: &dyn JobQueueClient,
+	schedule: &ScheduleInfo,
+) -> bool {
+	let pipe = match db_client.get_pipeline(schedule.pipeline).await {
+		Ok(Some(pipe)) => pipe,
+		Ok(None) => {
+			// Schedules are deleted with their pipeline,
+			// so this should only happen if we race a delete.
+			warn!(
+				message = "Scheduled pipeline does not exist",
+				schedule = ?schedule.id,
+				pipeline = ?schedule.pipeline
+			);
+			return true;
+		}
+		Err(error) => {
+			error!(
+				message = "Database error while getting scheduled pipeline",
+				schedule = ?schedule.id,
+				?error
+			);
+			return false;
+		}
+	};
+
+	// Job ids are derived from the run's due time,
+	// so a run can never be queued twice.
+	let job_id = format!(
+		"{SCHEDULED_JOB_PREFIX}{}-{}",
+		i64::from(schedule.id),
+		schedule.next_run_at.unix_timestamp()
+	);
+
+	let res = jobqueue_client
+		.add_job(
+			schedule.owned_by,
+			NewJob {
+				job_id: job_id.as_str().into(),
+				pipeline_id: Some(i64::from(pipe.id)),
+				pipeline: &pipe.data,
+				input: &schedule.input,
+				retry_policy: pipe.data.retry.as_ref(),
+				priority: JobPriority::Normal,
+			},
+			&config.job_limits(),
+		)
+		.await;
+
+	return match res {
+		Ok(_) => {
+			debug!(message = "Queued scheduled job", schedule = ?schedule.id, job_id);
+			true
+		}
+
+		// Another instance queued this run, or someone else took its id
+		Err(AddJobError::AlreadyExists) => {
+			match jobqueue_client.get_job_short(&job_id.as_str().into()).await {
+				Ok(job)
+					if job.owned_by == schedule.owned_by
+						&& job.pipeline_id == Some(i64::from(pipe.id)) =>
+				{
+					debug!(message = "Scheduled job already exists", schedule = ?schedule.id, job_id);
+				}
+
+				Ok(job) => {
+					error!(
+						message = "Scheduled job id is taken by another job, skipping this run",
+						schedule = ?schedule.id,
+						job_id,
+						owned_by = ?job.owned_by,
+						pipeline_id = ?job.pipeline_id,
+					);
+				}
+
+				// We can't tell if this run was queued, try again later.
+				Err(error @ (GetJobShortError::DbError(_) | GetJobShortError::NotFound)) => {
+					warn!(
+						message = "Could not check existing scheduled job, will retry",
+						schedule = ?schedule.id,
+						job_id,
+						?error
+					);
+					return false;
+				}
+			}
+
+			true
+		}
+
+		// These may go away later
+		Err(
+			error @ (AddJobError::DbError(_)
+			| AddJobError::TooManyJobs { .. }
+			| AddJobError::RateLimited { .. }),
+		) => {
+			warn!(
+				message = "Could not queue scheduled job, will retry",
+				schedule = ?schedule.id,
+				job_id,
+				?error
+			);
+			false
+		}
+
+		Err(error @ AddJobError::BatchTooLarge { .. }) => {
+			warn!(
+				message = "Could not queue scheduled job",
+				schedule = ?schedule.id,
+				job_id,
+				?error
+			);
+			true
+		}
+	};
+}
@@ -51,3 +51,22 @@ pub enum UploadAssignError {
 	#[error("tried to finish an upload that we don't own")]
 	NotMyUpload,
 }
+
+#[derive(Debug, Error)]
+pub enum UploadReassignError {
+	/// We tried to reassign an upload that doesn't exist or isn't assigned
+	#[error("tried to reassign an upload that doesn't exist")]
+	BadUpload,
+
+	/// We tried to reassign an upload we don't own
+	#[error("tried to reassign an upload that we don't own")]
+	NotMyUpload,
+
+	/// We tried to reassign an upload whose job hasn't finished
+	#[error("tried to reassign an upload that is in use")]
+	InUse,
+
+	/// We could not check if this upload's job has finished
+	#[error("database error while checking upload job")]
+	DbError(#[from] sqlx::Error),
+}
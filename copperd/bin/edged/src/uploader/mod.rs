@@ -1,10 +1,13 @@
 use copper_itemdb::UserId;
-use copper_jobqueue::{base::client::JobQueueClient, info::QueuedJobState};
+use copper_jobqueue::base::client::JobQueueClient;
+use copper_jobqueue::base::errors::GetJobShortError;
 use copper_util::{
 	s3client::{MultipartUpload, S3Client},
 	MimeType,
 };
-use errors::{NewUploadError, UploadAssignError, UploadFinishError, UploadFragmentError};
+use errors::{
+	NewUploadError, UploadAssignError, UploadFinishError, UploadFragmentError, UploadReassignError,
+};
 use rand::{distributions::Alphanumeric, Rng};
 use serde::{Deserialize, Serialize};
 use smartstring::{LazyCompact, SmartString};
@@ -12,7 +15,10 @@ use std::{collections::BTreeMap, fmt::Display, sync::Arc, time::Duration};
 use time::OffsetDateTime;
 use tracing::{debug, error, info};
 
-use crate::config::EdgedConfig;
+use crate::{
+	config::EdgedConfig,
+	events::{EventBus, UploadEvent, UserEvent},
+};
 
 pub mod errors;
 
@@ -82,6 +88,9 @@ pub struct UploadJob {
 	last_activity: OffsetDateTime,
 	state: UploadJobState,
 
+	/// The number of bytes we've received
+	n_bytes: u64,
+
 	pub owner: UserId,
 	pub mime: MimeType,
 }
@@ -91,6 +100,7 @@ pub struct Uploader {
 	jobs: tokio::sync::Mutex<BTreeMap<UploadJobId, UploadJob>>,
 	objectstore_client: Arc<S3Client>,
 	jobqueue_client: Arc<dyn JobQueueClient>,
+	events: Arc<EventBus>,
 }
 
 impl Uploader {
@@ -99,12 +109,14 @@ impl Uploader {
 		config: Arc<EdgedConfig>,
 		objectstore_client: Arc<S3Client>,
 		jobqueue_client: Arc<dyn JobQueueClient>,
+		events: Arc<EventBus>,
 	) -> Self {
 		Self {
 			config,
 			jobs: tokio::sync::Mutex::new(BTreeMap::new()),
 			jobqueue_client,
 			objectstore_client,
+			events,
 		}
 	}
 
@@ -141,6 +153,7 @@ impl Uploader {
 		let mut jobs = self.jobs.lock().await;
 		let now = OffsetDateTime::now_utc();
 		let offset = Duration::from_secs(self.config.edged_upload_job_timeout);
+		let retention = Duration::from_secs(self.config.edged_upload_retention_secs);
 
 		let mut to_remove = Vec::new();
 		for (k, j) in jobs.iter() {
@@ -168,19 +181,19 @@ impl Uploader {
 							.jobqueue_client
 							.get_job_short(&pipeline_job.as_str().into())
 							.await;
-						if info.is_err() {
-							reason = "assigned job error";
-							true
-						} else {
-							reason = "assigned job finished";
-
-							match info.unwrap().state {
-								QueuedJobState::FailedRunning { .. } => true,
-								QueuedJobState::BuildError { .. } => true,
-								QueuedJobState::Success => true,
-
-								QueuedJobState::Queued => false,
-								QueuedJobState::Running => false,
+						match info {
+							Err(_) => {
+								reason = "assigned job error";
+								true
+							}
+
+							Ok(info) => {
+								reason = "assigned job finished";
+
+								// Keep finished jobs' uploads for a while,
+								// so that these jobs may be re-run.
+								let finished_at = info.finished_at.unwrap_or(j.last_activity);
+								info.state.is_finished() && finished_at + retention < now
 							}
 						}
 					}
@@ -225,6 +238,33 @@ impl Uploader {
 }
 
 impl Uploader {
+	/// The key we store the data of `owner`'s upload `id` under
+	fn object_key(owner: UserId, id: &UploadJobId) -> String {
+		format!("{}/{id}", i64::from(owner))
+	}
+
+	/// Find the upload that produced an object owned by `owner`.
+	/// Returns `None` if this object isn't one of their uploads.
+	///
+	/// The upload this returns may no longer exist.
+	pub fn upload_id_from_object(
+		&self,
+		owner: UserId,
+		bucket: &str,
+		key: &str,
+	) -> Option<UploadJobId> {
+		if bucket != self.config.edged_objectstore_upload_bucket {
+			return None;
+		}
+
+		let (key_owner, id) = key.split_once('/')?;
+		if key_owner != i64::from(owner).to_string() {
+			return None;
+		}
+
+		return Some(UploadJobId { id: id.into() });
+	}
+
 	/// Create a new upload job owned by the given user
 	/// and return its id.
 	pub async fn new_job(
@@ -252,12 +292,13 @@ impl Uploader {
 				owner,
 				started_at: now,
 				last_activity: now,
+				n_bytes: 0,
 				mime: mime.clone(),
 				state: UploadJobState::Pending(
 					self.objectstore_client
 						.create_multipart_upload(
 							&self.config.edged_objectstore_upload_bucket,
-							format!("{}/{id}", i64::from(owner)).as_str(),
+							&Self::object_key(owner, &id),
 							mime,
 						)
 						.await?,
@@ -316,12 +357,25 @@ impl Uploader {
 		);
 
 		// TODO: queue this future. CAREFUL WITH PART NUMBERS!
-		match &mut job.state {
-			UploadJobState::Pending(uj) => uj.upload_part(data, part_number).await?,
+		let n_parts = match &mut job.state {
+			UploadJobState::Pending(uj) => {
+				uj.upload_part(data, part_number).await?;
+				uj.n_completed_parts()
+			}
 			UploadJobState::Done(_) => unreachable!(),
 			UploadJobState::Assigned { .. } => unreachable!(),
 		};
 
+		job.n_bytes += u64::try_from(data.len()).unwrap();
+		self.events.send(UserEvent::Upload {
+			upload_id: job_id.clone(),
+			owner: job.owner,
+			event: UploadEvent::PartUploaded {
+				n_parts,
+				n_bytes: job.n_bytes,
+			},
+		});
+
 		return Ok(());
 	}
 
@@ -362,6 +416,12 @@ impl Uploader {
 			UploadJobState::Assigned { .. } => unreachable!(),
 		};
 
+		self.events.send(UserEvent::Upload {
+			upload_id: job_id.clone(),
+			owner: job.owner,
+			event: UploadEvent::Finished,
+		});
+
 		debug!(
 			message = "Finished upload",
 			job_id = ?job_id,
@@ -413,4 +473,98 @@ impl Uploader {
 
 		return Ok(());
 	}
+
+	/// Move an assigned upload to another pipeline job.
+	/// Returns the id of the pipeline job this upload was assigned to.
+	///
+	/// This is used when a job is re-run: its uploads are kept
+	/// as long as the newest job that uses them.
+	///
+	/// Uploads are only tracked in memory, so an upload
+	/// can't be reassigned after edged restarts.
+	pub async fn reassign_job(
+		&self,
+		as_user: UserId,
+		job_id: &UploadJobId,
+		to_pipeline_job: &str,
+	) -> Result<SmartString<LazyCompact>, UploadReassignError> {
+		self.check_jobs().await;
+
+		let previous = {
+			let jobs = self.jobs.lock().await;
+			let job = jobs.get(job_id).ok_or(UploadReassignError::BadUpload)?;
+
+			if job.owner != as_user {
+				return Err(UploadReassignError::NotMyUpload);
+			}
+
+			match &job.state {
+				UploadJobState::Assigned { pipeline_job, .. } => pipeline_job.clone(),
+				_ => return Err(UploadReassignError::BadUpload),
+			}
+		};
+
+		// Don't take an upload away from a job that may still use it.
+		// We don't hold `jobs` while we wait for the job queue.
+		let res = self
+			.jobqueue_client
+			.get_job_short(&previous.as_str().into())
+			.await;
+
+		match res {
+			Ok(info) => {
+				if !info.state.is_finished() {
+					return Err(UploadReassignError::InUse);
+				}
+			}
+
+			// This happens if the job was deleted,
+			// or was never queued (see `restore_uploads` in `rerun_job`).
+			Err(GetJobShortError::NotFound) => {}
+
+			Err(GetJobShortError::DbError(e)) => return Err(e.into()),
+		}
+
+		let mut jobs = self.jobs.lock().await;
+		let job = jobs.get_mut(job_id).ok_or(UploadReassignError::BadUpload)?;
+		match &mut job.state {
+			UploadJobState::Assigned { pipeline_job, .. } if *pipeline_job == previous => {
+				*pipeline_job = to_pipeline_job.into();
+			}
+
+			// Someone else reassigned this upload while we checked
+			UploadJobState::Assigned { .. } => return Err(UploadReassignError::InUse),
+
+			_ => return Err(UploadReassignError::BadUpload),
+		}
+		job.last_activity = OffsetDateTime::now_utc();
+
+		debug!(
+			message = "Reassigned upload job",
+			job_id = ?job_id,
+			from_pipeline_job = ?previous,
+			to_pipeline_job
+		);
+
+		return Ok(previous);
+	}
+
+	/// Undo [`Self::assign_job_to_pipeline`].
+	///
+	/// This should be called if the pipeline job an upload was
+	/// assigned to could not be queued, so that the upload may
+	/// be used again.
+	pub async fn unassign_job(&self, as_user: UserId, job_id: &UploadJobId) {
+		let mut jobs = self.jobs.lock().await;
+		let job = match jobs.get_mut(job_id) {
+			Some(job) if job.owner == as_user => job,
+			_ => return,
+		};
+
+		if let UploadJobState::Assigned { key, .. } = &job.state {
+			job.last_activity = OffsetDateTime::now_utc();
+			job.state = UploadJobState::Done(key.clone());
+			debug!(message = "Unassigned upload job", job_id = ?job_id);
+		}
+	}
 }
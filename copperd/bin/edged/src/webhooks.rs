@@ -0,0 +1,635 @@
+//! Sends job notifications to users' webhooks
+
+use copper_edged::{
+	webhook::{retry_delay, sign_payload, WebhookEvent, DELIVERY_HEADER, SIGNATURE_HEADER},
+	WebhookDeliveryInfo, WebhookDeliveryState, WebhookInfo,
+};
+use copper_jobqueue::{
+	base::{client::JobQueueClient, errors::GetBatchError},
+	info::{JobEvent, QueuedJobStateKind},
+};
+use futures::future::join_all;
+use reqwest::{
+	dns::{Addrs, Name, Resolve, Resolving},
+	header::CONTENT_TYPE,
+	redirect,
+};
+use std::{
+	net::{IpAddr, Ipv4Addr, Ipv6Addr},
+	sync::Arc,
+	time::Duration,
+};
+use time::OffsetDateTime;
+use tracing::{debug, error};
+use url::{Host, Url};
+
+use crate::{config::EdgedConfig, database::base::client::DatabaseClient};
+
+/// The maximum number of deliveries we send at once
+const DELIVERY_BATCH_SIZE: i64 = 32;
+
+/// How long a sender may hold a delivery, in addition to the request timeout.
+/// If it doesn't record a result in time, another sender may try again.
+const DELIVERY_LEASE_MARGIN: Duration = Duration::from_secs(60);
+
+/// The maximum number of finished jobs we read at once
+const FINISHED_JOB_BATCH_SIZE: i64 = 500;
+
+/// How far behind our cursor we look for finished jobs.
+///
+/// A job is committed a little while after it finishes, and runners' clocks
+/// may disagree, so jobs don't always appear in the order they finished.
+const FINISHED_JOB_LOOKBACK: Duration = Duration::from_secs(120);
+
+//
+// MARK: Queueing
+//
+
+/// Periodically queue webhook deliveries for jobs that finished since our last pass.
+///
+/// We read finished jobs from the job queue instead of listening for job events,
+/// since events sent while no edged instance is listening are lost.
+/// Every edged instance does this, so each job is queued many times.
+/// This is fine, see [`DatabaseClient::queue_webhook_event`].
+///
+/// This never returns.
+pub async fn run_webhook_queuer<Client: DatabaseClient>(
+	config: Arc<EdgedConfig>,
+	db_client: Arc<Client>,
+	jobqueue_client: Arc<dyn JobQueueClient>,
+) {
+	let interval = Duration::from_secs(config.edged_webhook_interval_secs);
+
+	loop {
+		tokio::time::sleep(interval).await;
+		queue_finished_jobs(&*db_client, &*jobqueue_client).await;
+	}
+}
+
+/// Queue webhook deliveries for every job that finished after our cursor.
+/// The cursor only moves if every one of these jobs was queued.
+async fn queue_finished_jobs<Client: DatabaseClient>(
+	db_client: &Client,
+	jobqueue_client: &dyn JobQueueClient,
+) {
+	let cursor = match db_client.get_webhook_cursor().await {
+		Ok(x) => x,
+		Err(error) => {
+			error!(message = "Could not get webhook cursor", ?error);
+			return;
+		}
+	};
+
+	// Most users don't have webhooks, there's no need to look at their jobs
+	let users = match db_client.list_webhook_users().await {
+		Ok(x) => x,
+		Err(error) => {
+			error!(message = "Could not list webhook users", ?error);
+			return;
+		}
+	};
+
+	let mut finished_after = cursor - FINISHED_JOB_LOOKBACK;
+	let mut after_job = None;
+	let mut newest = cursor;
+	loop {
+		let jobs = match jobqueue_client
+			.get_finished_jobs(finished_after, after_job.as_ref(), FINISHED_JOB_BATCH_SIZE)
+			.await
+		{
+			Ok(x) => x,
+			Err(error) => {
+				error!(message = "Could not get finished jobs", ?error);
+				return;
+			}
+		};
+
+		for job in jobs.iter().filter(|x| users.contains(&x.event.owned_by)) {
+			let webhook_events = match webhook_events(jobqueue_client, &job.event).await {
+				Ok(x) => x,
+				Err(error) => {
+					error!(message = "Could not get batch", batch_id = ?job.event.batch_id, ?error);
+					return;
+				}
+			};
+
+			for webhook_event in webhook_events {
+				let res = db_client
+					.queue_webhook_event(job.event.owned_by, &webhook_event)
+					.await;
+
+				match res {
+					Ok(n) => {
+						debug!(message = "Queued webhook deliveries", event = ?webhook_event, n);
+					}
+					Err(error) => {
+						error!(message = "Could not queue webhook deliveries", ?error);
+						return;
+					}
+				}
+			}
+		}
+
+		let last = match jobs.last() {
+			Some(x) => x,
+			None => break,
+		};
+
+		newest = newest.max(last.finished_at);
+		if i64::try_from(jobs.len()).unwrap() < FINISHED_JOB_BATCH_SIZE {
+			break;
+		}
+
+		finished_after = last.finished_at;
+		after_job = Some(last.event.job_id.clone());
+	}
+
+	if let Err(error) = db_client.advance_webhook_cursor(newest).await {
+		error!(message = "Could not advance webhook cursor", ?error);
+	}
+}
+
+/// Get all webhook events caused by a job event
+async fn webhook_events(
+	jobqueue_client: &dyn JobQueueClient,
+	event: &JobEvent,
+) -> Result<Vec<WebhookEvent>, GetBatchError> {
+	let mut out = Vec::new();
+
+	match event.state {
+		QueuedJobStateKind::Success => out.push(WebhookEvent::JobSuccess {
+			job_id: event.job_id.as_str().into(),
+		}),
+
+		QueuedJobStateKind::BuildError | QueuedJobStateKind::FailedRunning => {
+			out.push(WebhookEvent::JobFailure {
+				job_id: event.job_id.as_str().into(),
+				state: event.state.as_str().into(),
+			})
+		}
+
+		QueuedJobStateKind::Queued
+		| QueuedJobStateKind::Running
+		| QueuedJobStateKind::Cancelled => {}
+	}
+
+	// A batch is complete when its last job finishes
+	if let (true, Some(batch_id)) = (event.state.is_finished(), &event.batch_id) {
+		match jobqueue_client.get_batch(batch_id).await {
+			Ok(batch) => {
+				if batch.counts.queued_jobs == 0 && batch.counts.running_jobs == 0 {
+					out.push(WebhookEvent::BatchComplete {
+						batch_id: batch_id.as_str().into(),
+						total_jobs: batch.counts.total_jobs,
+						successful_jobs: batch.counts.successful_jobs,
+					})
+				}
+			}
+
+			// This batch's jobs have expired since we read this one
+			Err(GetBatchError::NotFound) => {}
+			Err(error) => return Err(error),
+		}
+	}
+
+	return Ok(out);
+}
+
+//
+// MARK: Sending
+//
+
+/// Periodically send all due webhook deliveries.
+/// This never returns.
+pub async fn run_webhook_sender<Client: DatabaseClient>(
+	config: Arc<EdgedConfig>,
+	db_client: Arc<Client>,
+) {
+	let timeout = Duration::from_secs(config.edged_webhook_timeout_secs);
+	let interval = Duration::from_secs(config.edged_webhook_interval_secs);
+	let delivery_client =
+		DeliveryClient::new(timeout, config.edged_webhook_allow_private_addresses);
+
+	loop {
+		tokio::time::sleep(interval).await;
+
+		loop {
+			let claimed = match db_client
+				.claim_webhook_deliveries(
+					OffsetDateTime::now_utc(),
+					timeout + DELIVERY_LEASE_MARGIN,
+					DELIVERY_BATCH_SIZE,
+				)
+				.await
+			{
+				Ok(x) => x,
+				Err(error) => {
+					error!(message = "Could not claim webhook deliveries", ?error);
+					break;
+				}
+			};
+
+			let n_claimed = claimed.len();
+			join_all(claimed.iter().map(|(webhook, delivery)| {
+				attempt_delivery(&config, &*db_client, &delivery_client, webhook, delivery)
+			}))
+			.await;
+
+			if i64::try_from(n_claimed).unwrap() < DELIVERY_BATCH_SIZE {
+				break;
+			}
+		}
+	}
+}
+
+/// Try to send a delivery once, and save the result
+async fn attempt_delivery<Client: DatabaseClient>(
+	config: &EdgedConfig,
+	db_client: &Client,
+	delivery_client: &DeliveryClient,
+	webhook: &WebhookInfo,
+	delivery: &WebhookDeliveryInfo,
+) {
+	let res = delivery_client.send(webhook, delivery).await;
+
+	let res = match res {
+		Ok(status) => {
+			db_client
+				.record_webhook_attempt(
+					delivery.id,
+					WebhookDeliveryState::Delivered,
+					Some(status),
+					None,
+					None,
+				)
+				.await
+		}
+
+		Err(failure) => {
+			debug!(
+				message = "Webhook delivery failed",
+				webhook = ?webhook.id,
+				delivery = ?delivery.id,
+				attempt = delivery.attempt,
+				?failure
+			);
+
+			let (state, next_attempt_at) = if delivery.attempt >= config.edged_webhook_max_attempts
+			{
+				(WebhookDeliveryState::Failed, None)
+			} else {
+				(
+					WebhookDeliveryState::Pending,
+					Some(OffsetDateTime::now_utc() + retry_delay(delivery.attempt)),
+				)
+			};
+
+			db_client
+				.record_webhook_attempt(
+					delivery.id,
+					state,
+					failure.status,
+					Some(&failure.message),
+					next_attempt_at,
+				)
+				.await
+		}
+	};
+
+	if let Err(error) = res {
+		error!(
+			message = "Could not record webhook delivery attempt",
+			delivery = ?delivery.id,
+			?error
+		);
+	}
+}
+
+/// Why a delivery attempt failed
+#[derive(Debug)]
+pub struct DeliveryFailure {
+	/// The status the receiver responded with, if it responded
+	pub status: Option<u16>,
+
+	/// A description of this failure.
+	/// This is shown to the webhook's owner, so it never includes
+	/// details about the network between us and the receiver.
+	pub message: String,
+}
+
+/// Sends webhook deliveries
+pub struct DeliveryClient {
+	http_client: reqwest::Client,
+
+	/// If false, we refuse to send deliveries to addresses that aren't public.
+	/// See [`is_public_ip`].
+	allow_private: bool,
+}
+
+impl DeliveryClient {
+	/// Make a new delivery client.
+	///
+	/// Unless `allow_private` is set, this client never connects to
+	/// private, loopback, or link-local addresses, so that webhooks
+	/// can't be used to send requests to our internal network.
+	pub fn new(timeout: Duration, allow_private: bool) -> Self {
+		let mut builder = reqwest::Client::builder()
+			.timeout(timeout)
+			// A redirect could send us anywhere
+			.redirect(redirect::Policy::none())
+			// A proxy would resolve names for us
+			.no_proxy();
+
+		if !allow_private {
+			builder = builder.dns_resolver(Arc::new(PublicResolver));
+		}
+
+		return Self {
+			http_client: builder.build().unwrap(),
+			allow_private,
+		};
+	}
+
+	/// Send one delivery to a webhook.
+	/// Returns the http status of the receiver's response.
+	pub async fn send(
+		&self,
+		webhook: &WebhookInfo,
+		delivery: &WebhookDeliveryInfo,
+	) -> Result<u16, DeliveryFailure> {
+		// Names are checked by our resolver, but addresses are never resolved
+		if !self.allow_private {
+			let host = Url::parse(&webhook.url).ok().and_then(|x| match x.host() {
+				Some(Host::Ipv4(ip)) => Some(IpAddr::V4(ip)),
+				Some(Host::Ipv6(ip)) => Some(IpAddr::V6(ip)),
+				Some(Host::Domain(_)) | None => None,
+			});
+
+			if let Some(ip) = host {
+				if !is_public_ip(ip) {
+					return Err(DeliveryFailure {
+						status: None,
+						message: "receiver address is not allowed".into(),
+					});
+				}
+			}
+		}
+
+		let body = serde_json::to_vec(&delivery.event).unwrap();
+		let timestamp = OffsetDateTime::now_utc().unix_timestamp();
+
+		let res = self
+			.http_client
+			.post(webhook.url.as_str())
+			.header(CONTENT_TYPE, "application/json")
+			.header(
+				SIGNATURE_HEADER,
+				sign_payload(&webhook.secret, timestamp, &body),
+			)
+			.header(DELIVERY_HEADER, i64::from(delivery.id).to_string())
+			.body(body)
+			.send()
+			.await;
+
+		let res = match res {
+			Ok(x) => x,
+			Err(error) => {
+				debug!(message = "Webhook request failed", webhook = ?webhook.id, ?error);
+
+				// Errors may describe our network, so we don't show them to users
+				let message = if error.is_timeout() {
+					"request timed out"
+				} else if error.is_connect() {
+					"could not connect to receiver"
+				} else {
+					"request failed"
+				};
+
+				return Err(DeliveryFailure {
+					status: None,
+					message: message.into(),
+				});
+			}
+		};
+
+		let status = res.status();
+		if !status.is_success() {
+			return Err(DeliveryFailure {
+				status: Some(status.as_u16()),
+				message: format!("receiver responded with {status}"),
+			});
+		}
+
+		return Ok(status.as_u16());
+	}
+}
+
+/// Resolves names like the system resolver,
+/// but never returns addresses that aren't public.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+	fn resolve(&self, name: Name) -> Resolving {
+		let name = name.as_str().to_owned();
+		Box::pin(async move {
+			let addrs: Vec<_> = tokio::net::lookup_host((name.as_str(), 0))
+				.await?
+				.filter(|x| is_public_ip(x.ip()))
+				.collect();
+
+			if addrs.is_empty() {
+				return Err(format!("{name} has no public addresses").into());
+			}
+
+			return Ok(Box::new(addrs.into_iter()) as Addrs);
+		})
+	}
+}
+
+/// Returns false if `ip` is a private, loopback, link-local,
+/// or otherwise special-purpose address that we shouldn't send deliveries to.
+fn is_public_ip(ip: IpAddr) -> bool {
+	return match ip {
+		IpAddr::V4(ip) => is_public_ipv4(ip),
+		IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+			Some(ip) => is_public_ipv4(ip),
+			None => is_public_ipv6(ip),
+		},
+	};
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+	let [a, b, ..] = ip.octets();
+
+	return !(ip.is_private()
+		|| ip.is_loopback()
+		|| ip.is_link_local()
+		|| ip.is_unspecified()
+		|| ip.is_broadcast()
+		|| ip.is_multicast()
+		|| ip.is_documentation()
+		// "This network", 0.0.0.0/8
+		|| a == 0
+		// Shared address space, 100.64.0.0/10
+		|| (a == 100 && (b & 0xc0) == 64));
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+	let first = ip.segments()[0];
+
+	return !(ip.is_loopback()
+		|| ip.is_unspecified()
+		|| ip.is_multicast()
+		// Unique local, fc00::/7
+		|| (first & 0xfe00) == 0xfc00
+		// Link-local, fe80::/10
+		|| (first & 0xffc0) == 0xfe80);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use axum::{extract::State, http::HeaderMap, http::StatusCode, routing::post, Router};
+	use copper_edged::{WebhookDeliveryInfo, WebhookInfo};
+	use tokio::sync::mpsc;
+
+	/// A local http server that records every request it receives,
+	/// and responds with `status`.
+	async fn stub(status: StatusCode) -> (String, mpsc::UnboundedReceiver<(HeaderMap, Vec<u8>)>) {
+		let (send, recv) = mpsc::unbounded_channel();
+
+		let app = Router::new()
+			.route(
+				"/hook",
+				post(
+					move |State(send): State<mpsc::UnboundedSender<(HeaderMap, Vec<u8>)>>,
+					      headers: HeaderMap,
+					      body: axum::body::Bytes| async move {
+						send.send((headers, body.to_vec())).unwrap();
+						status
+					},
+				),
+			)
+			.with_state(send);
+
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+		return (format!("http://{addr}/hook"), recv);
+	}
+
+	/// A client that may send deliveries to our stub
+	fn client() -> DeliveryClient {
+		DeliveryClient::new(Duration::from_secs(5), true)
+	}
+
+	fn webhook(url: &str) -> WebhookInfo {
+		WebhookInfo {
+			id: 1.into(),
+			owned_by: 1.into(),
+			url: url.into(),
+			events: vec![],
+			secret: "secret".into(),
+			created_at: OffsetDateTime::now_utc(),
+		}
+	}
+
+	fn delivery() -> WebhookDeliveryInfo {
+		WebhookDeliveryInfo {
+			id: 7.into(),
+			webhook: 1.into(),
+			event: WebhookEvent::JobSuccess {
+				job_id: "job-1".into(),
+			},
+			state: WebhookDeliveryState::Pending,
+			attempt: 1,
+			last_status: None,
+			last_error: None,
+			created_at: OffsetDateTime::now_utc(),
+			next_attempt_at: None,
+			delivered_at: None,
+		}
+	}
+
+	#[tokio::test]
+	async fn delivers_signed_payload() {
+		let (url, mut recv) = stub(StatusCode::NO_CONTENT).await;
+
+		let res = client().send(&webhook(&url), &delivery()).await;
+		assert_eq!(res.unwrap(), 204);
+
+		let (headers, body) = recv.recv().await.unwrap();
+		assert_eq!(headers.get(DELIVERY_HEADER).unwrap(), "7");
+
+		// The signature must match the body we received
+		let signature = headers.get(SIGNATURE_HEADER).unwrap().to_str().unwrap();
+		let timestamp: i64 = signature
+			.strip_prefix("t=")
+			.and_then(|x| x.split(',').next())
+			.unwrap()
+			.parse()
+			.unwrap();
+		assert_eq!(signature, sign_payload("secret", timestamp, &body));
+
+		let event: WebhookEvent = serde_json::from_slice(&body).unwrap();
+		assert!(matches!(event, WebhookEvent::JobSuccess { job_id } if job_id == "job-1"));
+	}
+
+	#[tokio::test]
+	async fn error_status_fails() {
+		let (url, _recv) = stub(StatusCode::INTERNAL_SERVER_ERROR).await;
+
+		let res = client().send(&webhook(&url), &delivery()).await;
+		assert_eq!(res.unwrap_err().status, Some(500));
+	}
+
+	#[tokio::test]
+	async fn unreachable_receiver_fails() {
+		// Bind and drop a listener to find a closed port
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		drop(listener);
+
+		let url = format!("http://{addr}/hook");
+		let res = client().send(&webhook(&url), &delivery()).await;
+		assert_eq!(res.unwrap_err().status, None);
+	}
+
+	#[tokio::test]
+	async fn private_receiver_fails() {
+		let (url, _recv) = stub(StatusCode::NO_CONTENT).await;
+		let client = DeliveryClient::new(Duration::from_secs(5), false);
+
+		let res = client.send(&webhook(&url), &delivery()).await;
+		assert_eq!(res.unwrap_err().status, None);
+
+		// Names are resolved before we check them
+		let url = url.replace("127.0.0.1", "localhost");
+		let res = client.send(&webhook(&url), &delivery()).await;
+		assert_eq!(res.unwrap_err().status, None);
+	}
+
+	#[test]
+	fn public_ips() {
+		for ip in ["1.1.1.1", "2606:4700::1111", "100.128.0.1"] {
+			assert!(is_public_ip(ip.parse().unwrap()), "{ip}");
+		}
+
+		for ip in [
+			"127.0.0.1",
+			"10.1.2.3",
+			"172.16.0.1",
+			"192.168.1.1",
+			"169.254.169.254",
+			"100.64.0.1",
+			"0.0.0.0",
+			"::1",
+			"fd00::1",
+			"fe80::1",
+			"::ffff:127.0.0.1",
+		] {
+			assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+		}
+	}
+}
@@ -1,14 +1,8 @@
+use copper_itemdb::UserId;
+use copper_jobqueue::{limits::JobLimits, scheduling::SchedulingPolicy};
 use copper_util::logging::LoggingPreset;
 use serde::Deserialize;
-
-/// `await` for this many ms between successive polls
-/// of pipeline tasks. This constant is used in a few
-/// different contexts, but it's purpose remains the same.
-///
-/// If this duration is too long, we'll waste time waiting
-/// but if it is too short we'll cpu cycles checking
-/// unfinished futures and switching tasks.
-pub const ASYNC_POLL_AWAIT_MS: u64 = 1000;
+use std::collections::BTreeMap;
 
 /// Note that the field of this struct are not capitalized.
 /// Envy is case-insensitive, and expects Rust fields to be snake_case.
@@ -48,6 +42,47 @@ pub struct PiperConfig {
 	/// The number of pipeline jobs to run in parallel
 	#[serde(default = "PiperConfig::default_parallel_jobs")]
 	pub piper_parallel_jobs: usize,
+
+	/// How long, in seconds, a runner's lease on a job lasts.
+	/// Runners renew their leases while they run jobs. If a runner
+	/// stops renewing its lease (e.g, because piper crashed),
+	/// its job is reaped once this lease expires.
+	#[serde(default = "PiperConfig::default_lease_secs")]
+	pub piper_lease_secs: u64,
+
+	/// How often, in seconds, idle runners check the queue for jobs.
+	///
+	/// Runners are woken up as soon as a job is queued,
+	/// so this is only a fallback for lost notifications
+	/// and for jobs that are waiting to be retried.
+	#[serde(default = "PiperConfig::default_queue_poll_secs")]
+	pub piper_queue_poll_secs: u64,
+
+	/// If true, share runners fairly between users, so that one user's
+	/// huge backlog can't block everyone else's jobs.
+	///
+	/// If false, always run the oldest job with the highest priority.
+	#[serde(default = "PiperConfig::default_fair_scheduling")]
+	pub piper_fair_scheduling: bool,
+
+	/// The share of runners each user gets when fair scheduling is enabled,
+	/// as a comma-separated list of `user_id:weight` pairs (e.g. `1:4,12:2`).
+	/// A user with weight 2 may run twice as many jobs as a user with weight 1.
+	#[serde(default)]
+	pub piper_user_weights: String,
+
+	/// The weight of users not listed in `piper_user_weights`
+	#[serde(default = "PiperConfig::default_user_weight")]
+	pub piper_default_user_weight: u32,
+
+	/// The maximum number of jobs one user may run at once,
+	/// across all piper instances. If unset, there is no limit.
+	#[serde(default)]
+	pub piper_max_running_per_user: Option<u32>,
+
+	/// How often, in seconds, we look for jobs with expired leases
+	#[serde(default = "PiperConfig::default_reaper_interval_secs")]
+	pub piper_reaper_interval_secs: u64,
 }
 
 impl PiperConfig {
@@ -59,9 +94,82 @@ impl PiperConfig {
 		10
 	}
 
+	fn default_lease_secs() -> u64 {
+		30
+	}
+
+	fn default_fair_scheduling() -> bool {
+		true
+	}
+
+	fn default_user_weight() -> u32 {
+		1
+	}
+
+	fn default_queue_poll_secs() -> u64 {
+		5
+	}
+
+	fn default_reaper_interval_secs() -> u64 {
+		10
+	}
+
 	fn default_parallel_jobs() -> usize {
 		std::thread::available_parallelism()
 			.map(|x| x.into())
 			.unwrap_or(4)
 	}
 }
+
+impl PiperConfig {
+	/// Make the job scheduling policy described by this config
+	pub fn scheduling_policy(&self) -> Result<SchedulingPolicy, String> {
+		let mut user_weights = BTreeMap::new();
+
+		for pair in self.piper_user_weights.split(',') {
+			let pair = pair.trim();
+			if pair.is_empty() {
+				continue;
+			}
+
+			let (user, weight) = pair
+				.split_once(':')
+				.ok_or_else(|| format!("bad user weight `{pair}`, expected `user_id:weight`"))?;
+
+			let user: i64 = user
+				.trim()
+				.parse()
+				.map_err(|e| format!("bad user id in user weight `{pair}`: {e}"))?;
+
+			let weight: u32 = weight
+				.trim()
+				.parse()
+				.map_err(|e| format!("bad weight in user weight `{pair}`: {e}"))?;
+
+			if weight == 0 {
+				return Err(format!("weight in `{pair}` must be positive"));
+			}
+
+			user_weights.insert(UserId::from(user), weight);
+		}
+
+		if self.piper_default_user_weight == 0 {
+			return Err("default user weight must be positive".into());
+		}
+
+		return Ok(SchedulingPolicy {
+			fair: self.piper_fair_scheduling,
+			user_weights,
+			default_weight: self.piper_default_user_weight,
+		});
+	}
+
+	/// The limits on the jobs one user may run.
+	/// Limits on adding jobs are enforced by edged, and are not set here.
+	pub fn job_limits(&self) -> JobLimits {
+		JobLimits {
+			max_running_per_user: self.piper_max_running_per_user,
+			..Default::default()
+		}
+	}
+}
@@ -1,6 +1,6 @@
 use aws_config::{BehaviorVersion, Region};
 use aws_sdk_s3::config::Credentials;
-use config::{PiperConfig, ASYNC_POLL_AWAIT_MS};
+use config::PiperConfig;
 use copper_itemdb::{
 	client::{ItemdbClient, ItemdbOpenError},
 	AttrData,
@@ -8,7 +8,10 @@ use copper_itemdb::{
 use copper_jobqueue::{
 	base::{
 		client::JobQueueClient,
-		errors::{BuildErrorJobError, FailJobError, GetQueuedJobError, SuccessJobError},
+		errors::{
+			BuildErrorJobError, FailJobError, GetQueuedJobError, HeartbeatJobError, ReapJobsError,
+			RetryJobError, SuccessJobError,
+		},
 	},
 	id::QueuedJobId,
 	postgres::{PgJobQueueClient, PgJobQueueOpenError},
@@ -17,14 +20,23 @@ use copper_piper::{
 	base::RunNodeError,
 	data::PipeData,
 	helpers::{processor::BytesProcessorBuilder, rawbytes::RawBytesSource},
+	retry::RetryPolicy,
+	trace::NodeTrace,
 	CopperContext,
 };
 use copper_util::{load_env, s3client::S3Client, LoadedEnv};
 use pipeline::runner::{PipelineRunner, StartJobError};
-use sqlx::Acquire;
-use std::{collections::BTreeMap, sync::Arc};
-use tokio::{sync::Mutex, task::JoinSet};
-use tracing::{error, info, trace};
+use rand::{distributions::Alphanumeric, Rng};
+use sqlx::{Acquire, Postgres, Transaction};
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use time::OffsetDateTime;
+use tokio::{
+	sync::{Mutex, Notify},
+	task::JoinSet,
+	time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, trace};
 
 mod config;
 mod pipeline;
@@ -64,6 +76,21 @@ async fn main() {
 		}
 	};
 
+	// Runners make their own policies, we only check the config here
+	if let Err(message) = config.scheduling_policy() {
+		error!(message = "Invalid scheduling config", error = message);
+		std::process::exit(1);
+	}
+
+	// Runners send heartbeats a few times per lease
+	if config.piper_lease_secs == 0 {
+		error!(
+			message = "Invalid lease config",
+			error = "lease duration must be positive"
+		);
+		std::process::exit(1);
+	}
+
 	let cred = Credentials::new(
 		&config.piper_objectstore_key_id,
 		&config.piper_objectstore_key_secret,
@@ -158,13 +185,33 @@ async fn main() {
 	};
 	trace!(message = "Successfully connected to itemdb");
 
+	// Wakes up idle runners when a job is queued
+	let job_ready = Arc::new(Notify::new());
+	let listener = tokio::spawn(listen_for_jobs(jobqueue_client.clone(), job_ready.clone()));
+
+	let reaper = tokio::spawn(reap_jobs(
+		jobqueue_client.clone(),
+		Duration::from_secs(config.piper_reaper_interval_secs),
+	));
+
+	// A unique id for this piper instance,
+	// used to identify the runners that hold job leases.
+	let instance_id: String = rand::thread_rng()
+		.sample_iter(&Alphanumeric)
+		.take(16)
+		.map(char::from)
+		.collect();
+	info!(message = "Starting runners", instance_id);
+
 	let mut tasks = JoinSet::new();
 	(0..config.piper_parallel_jobs).for_each(|runner_idx| {
 		let c = config.clone();
 		let i = itemdb_client.clone();
 		let j = jobqueue_client.clone();
 		let s = s3client.clone();
-		tasks.spawn(async move { one_runner(runner_idx, &c, i, j, s).await });
+		let n = job_ready.clone();
+		let lease_owner = format!("{instance_id}/{runner_idx}");
+		tasks.spawn(async move { one_runner(runner_idx, lease_owner, n, &c, i, j, s).await });
 	});
 
 	while let Some(res) = tasks.join_next().await {
@@ -193,53 +240,52 @@ async fn main() {
 			}
 		}
 	}
+
+	reaper.abort();
+	listener.abort();
 }
 
 /// Starts a `loop` that runs one pipeline job at a time.
 async fn one_runner(
 	runner_idx: usize,
+	lease_owner: String,
+	job_ready: Arc<Notify>,
 	config: &PiperConfig,
 	itemdb_client: Arc<ItemdbClient>,
 	jobqueue_client: Arc<PgJobQueueClient>,
 	s3client: Arc<S3Client>,
 ) -> Result<usize, (usize, sqlx::Error)> {
 	let mut runner: PipelineRunner = PipelineRunner::new();
-
-	{
-		// Base nodes
-		use nodes_basic::register;
-		match register(runner.mut_dispatcher()) {
-			Ok(()) => {}
-			Err(error) => {
-				error!(
-					message = "Could not register nodes",
-					module = "basic",
-					?error
-				);
-				std::process::exit(1);
-			}
-		};
-	}
-
-	{
-		// Audiofile nodes
-		use nodes_audiofile::nodes::register;
-		match register(runner.mut_dispatcher()) {
-			Ok(()) => {}
-			Err(error) => {
-				error!(
-					message = "Could not register nodes",
-					module = "audiofile",
-					?error
-				);
-				std::process::exit(1);
-			}
-		};
+	let lease_duration = Duration::from_secs(config.piper_lease_secs);
+	let queue_poll_interval = Duration::from_secs(config.piper_queue_poll_secs);
+	// This can't fail, we checked this config in `main()`
+	let scheduling_policy = config.scheduling_policy().unwrap();
+	let job_limits = config.job_limits();
+
+	if let Err((module, error)) = nodes_all::register_all(runner.mut_dispatcher()) {
+		error!(message = "Could not register nodes", module, ?error);
+		std::process::exit(1);
 	}
 
 	loop {
-		// Run the oldest job off the queue
-		let job = match jobqueue_client.get_queued_job().await {
+		// Start listening before we check the queue,
+		// so we don't miss jobs queued while we check.
+		let job_ready = job_ready.notified();
+		tokio::pin!(job_ready);
+		job_ready.as_mut().enable();
+
+		// Run the oldest job off the queue.
+		// If we get one, its lease expires `lease_duration` after this.
+		let claimed_at = Instant::now();
+		let job = match jobqueue_client
+			.get_queued_job(
+				&lease_owner,
+				lease_duration,
+				&scheduling_policy,
+				&job_limits,
+			)
+			.await
+		{
 			Ok(x) => x,
 			Err(GetQueuedJobError::DbError(error)) => {
 				error!(message = "DB error while getting job", ?error);
@@ -251,8 +297,13 @@ async fn one_runner(
 		let job = if let Some(job) = job {
 			job
 		} else {
-			// No job ready, wait a bit...
-			tokio::time::sleep(std::time::Duration::from_millis(ASYNC_POLL_AWAIT_MS)).await;
+			// No job ready, wait until one is queued.
+			// We still poll now and then, since notifications may be lost
+			// and jobs waiting to be retried become ready without one.
+			tokio::select! {
+				_ = job_ready => {}
+				_ = tokio::time::sleep(queue_poll_interval) => {}
+			}
 			continue;
 		};
 
@@ -279,6 +330,17 @@ async fn one_runner(
 			.map_err(|e| (runner_idx, e))?;
 		let trans = conn.begin().await.map_err(|e| (runner_idx, e))?;
 
+		// Hold our lease and watch for cancellation while this job runs
+		let cancel = CancellationToken::new();
+		let watcher = tokio::spawn(watch_job(
+			job.job_id.clone(),
+			lease_owner.clone(),
+			claimed_at + lease_duration,
+			lease_duration,
+			jobqueue_client.clone(),
+			cancel.clone(),
+		));
+
 		let context = CopperContext {
 			runner_idx,
 			stream_fragment_size: config.piper_stream_fragment_size,
@@ -289,6 +351,7 @@ async fn one_runner(
 			objectstore_blob_bucket: config.piper_objectstore_storage_bucket.as_str().into(),
 			objectstore_client: s3client.clone(),
 			item_db_transaction: Mutex::new(trans),
+			cancel,
 		};
 
 		// Run job
@@ -296,10 +359,167 @@ async fn one_runner(
 			.run_job(context, job.pipeline, &job.job_id, input)
 			.await;
 
+		watcher.abort();
+
 		match res {
-			Err(err) => handle_start_job_error(err, &job.job_id, &jobqueue_client).await,
-			Ok(Err(error)) => handle_run_job_error(error, &job.job_id, &jobqueue_client).await,
-			Ok(Ok(())) => handle_run_job_success(&job.job_id, &jobqueue_client).await,
+			Err(err) => {
+				handle_start_job_error(err, &job.job_id, &lease_owner, &jobqueue_client).await
+			}
+			Ok((Err(RunNodeError::Cancelled), _)) => {
+				// This job's state was set by whoever cancelled or reaped it,
+				// and its transaction was dropped without a commit.
+				info!(message = "Job cancelled", job_id = ?job.job_id);
+			}
+			Ok((Err(error), trace)) => {
+				handle_run_job_error(
+					error,
+					&trace,
+					&job.job_id,
+					job.attempt,
+					job.retry_policy.as_ref(),
+					&lease_owner,
+					&jobqueue_client,
+				)
+				.await
+			}
+			Ok((Ok(result), trace)) => {
+				handle_run_job_success(
+					result,
+					&trace,
+					&job.job_id,
+					job.attempt,
+					job.retry_policy.as_ref(),
+					&lease_owner,
+					&jobqueue_client,
+				)
+				.await
+			}
+		}
+	}
+}
+
+/// Keep extending our lease on a running job,
+/// which expires at `lease_expires_at` (or earlier).
+/// If we lose this lease (because the job was cancelled or reaped),
+/// or can't renew it before it expires, cancel `cancel`.
+async fn watch_job(
+	job_id: QueuedJobId,
+	lease_owner: String,
+	mut lease_expires_at: Instant,
+	lease_duration: Duration,
+	jobqueue_client: Arc<PgJobQueueClient>,
+	cancel: CancellationToken,
+) {
+	// This gives us a few tries to renew our lease before it expires
+	let heartbeat_interval = lease_duration / 3;
+
+	loop {
+		tokio::time::sleep(heartbeat_interval).await;
+
+		// If this heartbeat succeeds, our lease is extended
+		// from some time after this.
+		let sent_at = Instant::now();
+
+		// Stop before our lease expires and this job is reaped,
+		// so that it can't commit after it is retried elsewhere.
+		// A heartbeat may wait for a connection for a long time.
+		let res = tokio::time::timeout_at(
+			lease_expires_at,
+			jobqueue_client.heartbeat_job(&job_id, &lease_owner, lease_duration),
+		)
+		.await;
+
+		match res {
+			Ok(Ok(())) => {
+				lease_expires_at = sent_at + lease_duration;
+			}
+
+			Ok(Err(HeartbeatJobError::LeaseLost)) => {
+				debug!(
+					message = "Job was cancelled or lost its lease",
+					?job_id,
+					lease_owner
+				);
+				cancel.cancel();
+				return;
+			}
+
+			Ok(Err(HeartbeatJobError::DbError(error))) => {
+				error!(
+					message = "DB error while sending heartbeat",
+					?job_id,
+					?error
+				);
+
+				if Instant::now() + heartbeat_interval >= lease_expires_at {
+					error!(
+						message = "Could not renew job lease before it expired, cancelling job",
+						?job_id,
+						lease_owner
+					);
+					cancel.cancel();
+					return;
+				}
+			}
+
+			Err(_) => {
+				error!(
+					message = "Job lease expired while sending heartbeat, cancelling job",
+					?job_id,
+					lease_owner
+				);
+				cancel.cancel();
+				return;
+			}
+		}
+	}
+}
+
+/// Wake up idle runners every time a job is queued.
+async fn listen_for_jobs(jobqueue_client: Arc<PgJobQueueClient>, job_ready: Arc<Notify>) {
+	loop {
+		let mut listener = match jobqueue_client.listen().await {
+			Ok(x) => x,
+			Err(error) => {
+				error!(message = "Could not listen for new jobs, retrying", ?error);
+				tokio::time::sleep(Duration::from_secs(5)).await;
+				continue;
+			}
+		};
+
+		loop {
+			match listener.recv().await {
+				Ok(job_id) => {
+					trace!(message = "Got job notification", job_id);
+					job_ready.notify_waiters();
+				}
+
+				Err(error) => {
+					error!(message = "Error while listening for new jobs", ?error);
+					break;
+				}
+			}
+		}
+	}
+}
+
+/// Periodically reap jobs whose runners have stopped sending heartbeats.
+/// Every piper instance runs a reaper, this is safe because
+/// reaping is atomic.
+async fn reap_jobs(jobqueue_client: Arc<PgJobQueueClient>, interval: Duration) {
+	loop {
+		tokio::time::sleep(interval).await;
+
+		match jobqueue_client.reap_expired_jobs().await {
+			Ok(jobs) => {
+				if !jobs.is_empty() {
+					info!(message = "Reaped jobs with expired leases", ?jobs);
+				}
+			}
+
+			Err(ReapJobsError::DbError(error)) => {
+				error!(message = "DB error while reaping jobs", ?error);
+			}
 		}
 	}
 }
@@ -307,12 +527,13 @@ async fn one_runner(
 async fn handle_start_job_error(
 	err: StartJobError,
 	job_id: &QueuedJobId,
+	lease_owner: &str,
 	jobqueue_client: &PgJobQueueClient,
 ) {
 	match err {
 		StartJobError::BuildError(err) => {
 			match jobqueue_client
-				.builderror_job(job_id, &format!("{:?}", err))
+				.builderror_job(job_id, lease_owner, &format!("{}", err))
 				.await
 			{
 				Ok(()) => {}
@@ -327,7 +548,7 @@ async fn handle_start_job_error(
 
 				Err(BuildErrorJobError::NotRunning) => {
 					error!(
-						message = "Tried to mark a job that isn't running as `BuildError`",
+						message = "Tried to mark a job we no longer hold as `BuildError`",
 						?job_id
 					);
 				}
@@ -338,13 +559,59 @@ async fn handle_start_job_error(
 
 async fn handle_run_job_error(
 	error: RunNodeError,
+	trace: &[NodeTrace],
 	job_id: &QueuedJobId,
+	attempt: u32,
+	retry_policy: Option<&RetryPolicy>,
+	lease_owner: &str,
 	jobqueue_client: &PgJobQueueClient,
 ) {
-	info!(message = "Job failed", ?job_id, ?error);
+	// A retry we can't schedule fails the job
+	let next_attempt_at = retry_policy
+		.and_then(|x| x.retry_after(attempt, &error))
+		.and_then(|x| OffsetDateTime::now_utc().checked_add(x.try_into().ok()?));
+
+	if let Some(next_attempt_at) = next_attempt_at {
+		info!(
+			message = "Job failed, will retry",
+			?job_id,
+			attempt,
+			?next_attempt_at,
+			?error
+		);
+
+		match jobqueue_client
+			.retry_job(
+				job_id,
+				lease_owner,
+				&format!("{}", error),
+				trace,
+				next_attempt_at,
+			)
+			.await
+		{
+			Ok(()) => {}
+
+			Err(RetryJobError::DbError(error)) => {
+				error!(
+					message = "DB error while queueing job for retry",
+					?job_id,
+					?error
+				);
+			}
+
+			Err(RetryJobError::NotRunning) => {
+				error!(message = "Tried to retry a job we no longer hold", ?job_id);
+			}
+		}
+
+		return;
+	}
+
+	info!(message = "Job failed", ?job_id, attempt, ?error);
 
 	match jobqueue_client
-		.fail_job_run(job_id, &format!("{}", error))
+		.fail_job_run(job_id, lease_owner, &format!("{}", error), trace)
 		.await
 	{
 		Ok(()) => {}
@@ -359,32 +626,55 @@ async fn handle_run_job_error(
 
 		Err(FailJobError::NotRunning) => {
 			error!(
-				message = "Tried to mark a job that isn't running as `Failed`",
+				message = "Tried to mark a job we no longer hold as `Failed`",
 				?job_id
 			);
 		}
 	}
 }
 
-async fn handle_run_job_success(job_id: &QueuedJobId, jobqueue_client: &PgJobQueueClient) {
-	info!(message = "Job finished successfully", ?job_id);
-
-	match jobqueue_client.success_job(job_id).await {
-		Ok(()) => {}
+async fn handle_run_job_success(
+	result: Transaction<'_, Postgres>,
+	trace: &[NodeTrace],
+	job_id: &QueuedJobId,
+	attempt: u32,
+	retry_policy: Option<&RetryPolicy>,
+	lease_owner: &str,
+	jobqueue_client: &PgJobQueueClient,
+) {
+	match jobqueue_client
+		.success_job(job_id, lease_owner, trace, result)
+		.await
+	{
+		Ok(()) => {
+			info!(message = "Job finished successfully", ?job_id);
+		}
 
 		Err(SuccessJobError::DbError(error)) => {
 			error!(
-				message = "DB error while marking job `Failed`",
+				message = "DB error while marking job `Success`",
 				?job_id,
 				?error
 			);
 		}
 
 		Err(SuccessJobError::NotRunning) => {
-			error!(
-				message = "Tried to mark a job that isn't running as `Failed`",
-				?job_id
-			);
+			// This job was cancelled or reaped while it ran,
+			// its changes were dropped.
+			info!(message = "Job finished, but was no longer running", ?job_id);
+		}
+
+		Err(SuccessJobError::CommitFailed(error)) => {
+			handle_run_job_error(
+				error.into(),
+				trace,
+				job_id,
+				attempt,
+				retry_policy,
+				lease_owner,
+				jobqueue_client,
+			)
+			.await
 		}
 	}
 }
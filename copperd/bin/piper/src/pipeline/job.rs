@@ -1,23 +1,25 @@
 use copper_piper::{
 	base::{
-		Node, NodeDispatcher, NodeId, NodeParameterValue, PortName, RunNodeError, ThisNodeInfo,
-		INPUT_NODE_TYPE,
+		Node, NodeDispatcher, NodeId, NodeInputs, NodeParameterValue, NodePortType, PortName,
+		RunNodeError, ThisNodeInfo, INPUT_NODE_TYPE,
 	},
-	data::PipeData,
+	data::{PipeData, PipeDataStub},
 	json::PipelineJson,
+	trace::{NodeTrace, NodeTraceStatus},
+	validate::PipelineProblem,
 	CopperContext,
 };
 use copper_util::graph::{finalized::FinalizedGraph, graph::Graph, util::GraphNodeIdx};
+use futures::{stream::FuturesUnordered, StreamExt};
 use smartstring::{LazyCompact, SmartString};
 use std::{
 	collections::{BTreeMap, HashMap},
 	fmt::Debug,
 };
 use thiserror::Error;
+use time::OffsetDateTime;
 use tracing::{debug, trace};
 
-use crate::config::ASYNC_POLL_AWAIT_MS;
-
 //
 // MARK: Errors
 //
@@ -25,31 +27,16 @@ use crate::config::ASYNC_POLL_AWAIT_MS;
 /// An error we encounter when a pipeline spec is invalid
 #[derive(Debug, Error)]
 pub enum PipelineBuildError {
-	/// An edge references a node, but it doesn't exist
-	#[error("edge `{edge_id}` references a node `{invalid_node_id}` that doesn't exist")]
-	NoNode {
-		/// The edge that references an invalid node
-		edge_id: SmartString<LazyCompact>,
-
-		/// The node id that doesn't exist
-		invalid_node_id: NodeId,
-	},
-
-	/// We found a node with an invalid type
-	#[error("invalid node type `{bad_type}`")]
-	BadNodeType { bad_type: SmartString<LazyCompact> },
-
-	/// This pipeline has a cycle and is thus invalid
-	#[error("this pipeline has a cycle")]
-	HasCycle,
+	/// We found problems while validating this pipeline
+	#[error(
+		"invalid pipeline: {}",
+		problems.iter().map(|x| x.to_string()).collect::<Vec<_>>().join("; ")
+	)]
+	InvalidPipeline { problems: Vec<PipelineProblem> },
 
 	/// We expected an input, but it wasn't provided
 	#[error("missing pipeline input `{input}`")]
 	MissingInput { input: SmartString<LazyCompact> },
-
-	/// An input node wasn't specified properly
-	#[error("input node `{node}` is invalid")]
-	InvalidInputNode { node: NodeId },
 }
 
 //
@@ -115,6 +102,13 @@ struct NodeSpec<'ctx> {
 
 	/// This node's state
 	pub state: NodeState<'ctx>,
+
+	/// The types of this node's input ports that have an exact type.
+	/// Data sent to these ports is checked before this node runs.
+	pub input_types: BTreeMap<PortName, PipeDataStub>,
+
+	/// A record of what happened to this node while it ran
+	pub trace: NodeTrace,
 }
 
 // We need to do this ourselves, since the ContextType generic
@@ -126,6 +120,8 @@ impl Debug for NodeSpec<'_> {
 			.field("node_type", &self.node_type)
 			.field("node_params", &self.node_params)
 			.field("state", &self.state)
+			.field("input_types", &self.input_types)
+			.field("trace", &self.trace)
 			.finish()
 	}
 }
@@ -202,6 +198,19 @@ impl<'ctx> PipelineJob<'ctx> {
 		json: &PipelineJson,
 		input: BTreeMap<SmartString<LazyCompact>, PipeData>,
 	) -> Result<FinalizedGraph<NodeSpec<'ctx>, EdgeSpec>, PipelineBuildError> {
+		trace!(message = "Validating pipeline", job_id);
+		let input_types = input
+			.iter()
+			.map(|(name, value)| (name.clone(), value.as_stub()))
+			.collect();
+		let problems = json.validate(dispatcher, &input_types);
+		if !problems.is_empty() {
+			return Err(PipelineBuildError::InvalidPipeline { problems });
+		}
+
+		// Since our pipeline is valid, none of the
+		// `unwrap()`s below should fail.
+
 		trace!(message = "Building pipeline graph", job_id);
 
 		// The graph that stores this pipeline
@@ -218,58 +227,51 @@ impl<'ctx> PipelineJob<'ctx> {
 					// Input nodes are never run, start them as "done".
 					// They are filled in at the end of this method.
 					state: NodeState::Done,
+					input_types: BTreeMap::new(),
 					node_params: node_spec.params.clone(),
 					node_type: node_spec.node_type.clone(),
+					trace: NodeTrace::new(node_id.clone(), node_spec.node_type.clone()),
 				});
 				node_id_map.insert(node_id.clone(), n);
 			} else {
-				let node_instance = dispatcher.init_node(&node_spec.node_type);
-				if node_instance.is_none() {
-					return Err(PipelineBuildError::BadNodeType {
-						bad_type: node_spec.node_type.clone(),
-					});
-				}
+				let node_instance = dispatcher.init_node(&node_spec.node_type).unwrap();
+				let input_types = match dispatcher
+					.node_ports(&node_spec.node_type, &node_spec.params)
+					.unwrap()
+					.unwrap()
+					.inputs
+				{
+					NodeInputs::Dynamic => BTreeMap::new(),
+					NodeInputs::Fixed(inputs) => inputs
+						.into_iter()
+						.filter_map(|(port, spec)| match spec.port_type {
+							NodePortType::Exact(t) => Some((port, t)),
+							_ => None,
+						})
+						.collect(),
+				};
 
 				let n = graph.add_node(NodeSpec {
 					id: node_id.clone(),
 					state: NodeState::NotStarted {
-						instance: node_instance.unwrap(),
+						instance: node_instance,
 					},
+					input_types,
 					node_params: node_spec.params.clone(),
 					node_type: node_spec.node_type.clone(),
+					trace: NodeTrace::new(node_id.clone(), node_spec.node_type.clone()),
 				});
 
 				node_id_map.insert(node_id.clone(), n);
 			}
 		}
 
-		// Make sure all edges are valid and create them in the graph.
-		//
-		// We do not check if ports exist & have matching types here,
-		// since not all nodes know their ports at build time.
+		// Create all edges in the graph
 		trace!(message = "Making edges", job_id);
-		for (edge_id, edge_spec) in json.edges.iter() {
-			// These should never fail
-			let source_node_idx = node_id_map.get(&edge_spec.source.node);
-			if source_node_idx.is_none() {
-				return Err(PipelineBuildError::NoNode {
-					edge_id: edge_id.clone(),
-					invalid_node_id: edge_spec.source.node.clone(),
-				});
-			}
-
-			let target_node_idx = node_id_map.get(&edge_spec.target.node);
-			if target_node_idx.is_none() {
-				return Err(PipelineBuildError::NoNode {
-					edge_id: edge_id.clone(),
-					invalid_node_id: edge_spec.target.node.clone(),
-				});
-			}
-
-			// Create the edge
+		for edge_spec in json.edges.values() {
 			graph.add_edge(
-				*source_node_idx.unwrap(),
-				*target_node_idx.unwrap(),
+				*node_id_map.get(&edge_spec.source.node).unwrap(),
+				*node_id_map.get(&edge_spec.target.node).unwrap(),
 				EdgeSpec {
 					source_port: edge_spec.source.port.clone(),
 					target_port: edge_spec.target.port.clone(),
@@ -278,12 +280,6 @@ impl<'ctx> PipelineJob<'ctx> {
 			);
 		}
 
-		trace!(message = "Looking for cycles", job_id);
-		// Make sure our graph doesn't have any cycles
-		if graph.has_cycle() {
-			return Err(PipelineBuildError::HasCycle);
-		}
-
 		let mut finalized_graph = graph.finalize();
 
 		trace!(message = "Filling edges connected to input nodes", job_id);
@@ -302,19 +298,9 @@ impl<'ctx> PipelineJob<'ctx> {
 		// ...and "run" them.
 		for idx in input_nodes {
 			let node = finalized_graph.get_node(idx).unwrap();
-			let input_param =
-				node.node_params
-					.get("input_name")
-					.ok_or(PipelineBuildError::InvalidInputNode {
-						node: node.id.clone(),
-					})?;
-			let input_name: SmartString<LazyCompact> = match input_param {
-				NodeParameterValue::String(s) => s.clone(),
-				_ => {
-					return Err(PipelineBuildError::InvalidInputNode {
-						node: node.id.clone(),
-					})
-				}
+			let input_name: SmartString<LazyCompact> = match node.node_params.get("input_name") {
+				Some(NodeParameterValue::String(s)) => s.clone(),
+				_ => unreachable!(),
 			};
 
 			if let Some(i_val) = input.get(&input_name) {
@@ -339,7 +325,41 @@ impl<'ctx> PipelineJob<'ctx> {
 	// MARK: Run
 	//
 
-	pub async fn run(mut self, context: &CopperContext<'ctx>) -> Result<(), RunNodeError> {
+	/// Run this job.
+	///
+	/// Returns the result of this job and a trace of every node in it.
+	/// Input nodes are not included in the trace.
+	pub async fn run(
+		mut self,
+		context: &CopperContext<'ctx>,
+	) -> (Result<(), RunNodeError>, Vec<NodeTrace>) {
+		let result = self.run_nodes(context).await;
+
+		let now = OffsetDateTime::now_utc();
+		let mut traces: Vec<NodeTrace> = self
+			.graph
+			.iter_nodes()
+			.filter(|node| node.node_type != INPUT_NODE_TYPE)
+			.map(|node| {
+				let mut trace = node.trace.clone();
+
+				// Nodes that were still running when we stopped were aborted
+				if node.state.is_running() && trace.status == NodeTraceStatus::NotStarted {
+					trace.status = NodeTraceStatus::Aborted;
+					trace.finished_at = Some(now);
+				}
+
+				trace
+			})
+			.collect();
+
+		// Order nodes by start time, nodes that never started go last.
+		traces.sort_by_key(|x| (x.started_at.is_none(), x.started_at));
+
+		return (result, traces);
+	}
+
+	async fn run_nodes(&mut self, context: &CopperContext<'ctx>) -> Result<(), RunNodeError> {
 		trace!(
 			message = "Running job",
 			runner_idx = context.runner_idx,
@@ -350,12 +370,28 @@ impl<'ctx> PipelineJob<'ctx> {
 		let all_nodes: Vec<GraphNodeIdx> =
 			self.graph.iter_nodes_idx().map(|(idx, _)| idx).collect();
 
+		// Nodes that are currently running.
+		//
+		// Nodes borrow `context`, so we can't give them to `tokio::spawn`.
+		// Instead, we poll all running nodes concurrently inside this task.
+		// Nodes that do heavy work should use `spawn_blocking` or spawn their own tasks.
+		let mut running_nodes = FuturesUnordered::new();
+
 		while all_nodes
 			.iter()
 			.any(|x| !self.graph.get_node(*x).unwrap().state.is_done())
 		{
+			if context.cancel.is_cancelled() {
+				debug!(
+					message = "Job cancelled, stopping",
+					runner_idx = context.runner_idx,
+					job_id = ?self.job_id,
+				);
+				return Err(RunNodeError::Cancelled);
+			}
+
 			//
-			// Start nodes
+			// Start all nodes that are ready
 			//
 			for node_idx in &all_nodes {
 				let node_idx = *node_idx;
@@ -394,7 +430,7 @@ impl<'ctx> PipelineJob<'ctx> {
 				}
 
 				// Take all inputs
-				let node_run_input: BTreeMap<PortName, Option<PipeData>> = {
+				let node_run_input: Vec<(PortName, Option<PipeData>)> = {
 					let input_edges = Vec::from(self.graph.edges_ending_at(node_idx).unwrap());
 					input_edges
 						.into_iter()
@@ -406,7 +442,23 @@ impl<'ctx> PipelineJob<'ctx> {
 				};
 
 				let node = self.graph.get_node_mut(node_idx).unwrap();
+
+				// Types were checked when we built this pipeline,
+				// but non-negative numbers can only be checked here.
+				let node_run_input: Result<BTreeMap<PortName, Option<PipeData>>, RunNodeError> =
+					node_run_input
+						.into_iter()
+						.map(|(port, data)| match (node.input_types.get(&port), data) {
+							(Some(t), Some(data)) => match data.conform_to(*t) {
+								Some(x) => Ok((port, Some(x))),
+								None => Err(RunNodeError::BadInputType { port }),
+							},
+							(_, data) => Ok((port, data)),
+						})
+						.collect();
+
 				let node_inst = node.state.start().unwrap();
+				node.trace.started_at = Some(OffsetDateTime::now_utc());
 
 				debug!(
 					message = "Starting node",
@@ -420,93 +472,135 @@ impl<'ctx> PipelineJob<'ctx> {
 				// MARK: Run node
 				//
 
-				let result = match node_inst
-					.run(
-						context,
-						ThisNodeInfo {
-							id: node.id.clone(),
-							idx: node_idx,
-							node_type: node.node_type.clone(),
-						},
-						node.node_params.clone().into(),
-						node_run_input,
-					)
-					.await
-				{
-					Ok(x) => x,
-					Err(error) => {
-						debug!(
-							message = "Node finished with error",
-							runner_idx = context.runner_idx,
-							node_id = ?node.id,
-							job_id = ?self.job_id,
-							?error
-						);
-						return Err(error);
-					}
+				let this_node = ThisNodeInfo {
+					id: node.id.clone(),
+					idx: node_idx,
+					node_type: node.node_type.clone(),
 				};
+				let params = node.node_params.clone().into();
+
+				running_nodes.push(async move {
+					let result = match node_run_input {
+						Ok(input) => node_inst.run(context, this_node, params, input).await,
+						Err(error) => Err(error),
+					};
+					(node_idx, result)
+				});
+			}
 
-				trace!(
-					message = "Node done, processing output",
-					runner_idx = context.runner_idx,
-					node_type = ?node.node_type,
-					node_id = ?node.id,
-					job_id = ?self.job_id,
-				);
+			//
+			// Wait for any running node to finish,
+			// or for this job to be cancelled.
+			//
 
-				//
-				// MARK: Process output
-				//
+			let next = tokio::select! {
+				x = running_nodes.next() => x,
+				_ = context.cancel.cancelled() => {
+					debug!(
+						message = "Job cancelled while nodes were running, stopping",
+						runner_idx = context.runner_idx,
+						job_id = ?self.job_id,
+					);
+
+					// Returning here drops `running_nodes`,
+					// which cancels all running nodes.
+					return Err(RunNodeError::Cancelled);
+				}
+			};
 
-				// Send output to edges
-				let node_id = node.id.clone();
-				let node_type = node.node_type.clone();
+			let (node_idx, result) = match next {
+				Some(x) => x,
 
-				for (from_node, _to_node, edge) in self.graph.iter_edges_mut() {
-					if from_node != node_idx {
-						continue;
-					}
+				// This should never happen. Our graph is acyclic,
+				// so some node is always either running or ready.
+				None => unreachable!("Pipeline has unfinished nodes, but none of them can run"),
+			};
 
-					if !matches!(edge.data, EdgeDataContainer::Unset) {
-						return Err(RunNodeError::OutputPortSetTwice {
-							node_id,
-							node_type,
-							port: edge.source_port.clone(),
-						});
-					}
+			let node = self.graph.get_node_mut(node_idx).unwrap();
+			node.trace.finished_at = Some(OffsetDateTime::now_utc());
+			let result = match result {
+				Ok(x) => {
+					node.trace.status = NodeTraceStatus::Success;
+					node.trace.outputs = x.iter().map(|(k, v)| (k.clone(), v.into())).collect();
+					x
+				}
+				Err(error) => {
+					node.trace.status = NodeTraceStatus::Failed {
+						message: format!("{}", error),
+					};
+
+					debug!(
+						message = "Node finished with error",
+						runner_idx = context.runner_idx,
+						node_id = ?node.id,
+						job_id = ?self.job_id,
+						?error
+					);
+
+					// Returning here drops `running_nodes`,
+					// which cancels all other nodes.
+					return Err(error);
+				}
+			};
+
+			trace!(
+				message = "Node done, processing output",
+				runner_idx = context.runner_idx,
+				node_type = ?node.node_type,
+				node_id = ?node.id,
+				job_id = ?self.job_id,
+			);
+
+			//
+			// MARK: Process output
+			//
+
+			// Send output to edges
+			let node_id = node.id.clone();
+			let node_type = node.node_type.clone();
 
-					edge.data = EdgeDataContainer::Some(result.get(&edge.source_port).cloned());
+			for (from_node, _to_node, edge) in self.graph.iter_edges_mut() {
+				if from_node != node_idx {
+					continue;
 				}
 
-				//
-				// MARK: finish node
-				//
+				if !matches!(edge.data, EdgeDataContainer::Unset) {
+					return Err(RunNodeError::OutputPortSetTwice {
+						node_id,
+						node_type,
+						port: edge.source_port.clone(),
+					});
+				}
 
-				// re-borrow again, we needed a &mut graph above
-				let node = self.graph.get_node_mut(node_idx).unwrap();
+				edge.data = EdgeDataContainer::Some(result.get(&edge.source_port).cloned());
+			}
 
-				assert!(
-					node.state.is_running(),
-					"Expected node to be running. node: {node:?}"
-				);
+			//
+			// MARK: finish node
+			//
 
-				node.state = NodeState::Done;
+			// re-borrow again, we needed a &mut graph above
+			let node = self.graph.get_node_mut(node_idx).unwrap();
 
-				// Make sure all edges starting at this node got output
-				for (from_node, _to_node, edge) in self.graph.iter_edges_mut() {
-					if from_node != node_idx {
-						continue;
-					}
+			assert!(
+				node.state.is_running(),
+				"Expected node to be running. node: {node:?}"
+			);
 
-					if matches!(edge.data, EdgeDataContainer::Unset) {
-						return Err(RunNodeError::UnrecognizedOutput {
-							port: edge.source_port.clone(),
-						});
-					}
+			node.state = NodeState::Done;
+
+			// Make sure all edges starting at this node got output
+			for (from_node, _to_node, edge) in self.graph.iter_edges_mut() {
+				if from_node != node_idx {
+					continue;
 				}
-			}
 
-			tokio::time::sleep(std::time::Duration::from_millis(ASYNC_POLL_AWAIT_MS)).await;
+				if matches!(edge.data, EdgeDataContainer::Unset) {
+					return Err(RunNodeError::UnrecognizedOutput {
+						port: edge.source_port.clone(),
+					});
+				}
+			}
 		}
 
 		return Ok(());
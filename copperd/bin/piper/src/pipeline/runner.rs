@@ -3,10 +3,12 @@ use copper_piper::{
 	base::{NodeDispatcher, RunNodeError},
 	data::PipeData,
 	json::PipelineJson,
+	trace::NodeTrace,
 	CopperContext,
 };
 use smartstring::{LazyCompact, SmartString};
-use std::{collections::BTreeMap, sync::Arc};
+use sqlx::{Postgres, Transaction};
+use std::collections::BTreeMap;
 use thiserror::Error;
 use tracing::debug;
 
@@ -39,14 +41,25 @@ impl PipelineRunner {
 		}
 	}
 
-	/// Start a job in this runner
-	pub async fn run_job(
+	/// Start a job in this runner.
+	/// If the job starts, returns its result and execution trace.
+	///
+	/// If the job succeeds, its result is the transaction that holds its changes.
+	/// This must be committed with [`copper_jobqueue::base::client::JobQueueClient::success_job`], so that
+	/// a job that was cancelled or reaped never commits.
+	pub async fn run_job<'a>(
 		&mut self,
-		context: CopperContext<'_>,
+		context: CopperContext<'a>,
 		pipeline: PipelineJson,
 		job_id: &QueuedJobId,
 		inputs: BTreeMap<SmartString<LazyCompact>, PipeData>,
-	) -> Result<Result<(), RunNodeError>, StartJobError> {
+	) -> Result<
+		(
+			Result<Transaction<'a, Postgres>, RunNodeError>,
+			Vec<NodeTrace>,
+		),
+		StartJobError,
+	> {
 		debug!(
 			message = "Starting job",
 			?job_id,
@@ -54,18 +67,14 @@ impl PipelineRunner {
 		);
 
 		let job = PipelineJob::new(&self.dispatcher, job_id.as_str(), inputs.clone(), &pipeline)?;
-		let x = job.run(&context).await;
+		let (x, trace) = job.run(&context).await;
 
-		if x.is_ok() {
-			// Commit only if job ran successfully
-			let trans = context.item_db_transaction.into_inner();
-			match trans.commit().await {
-				Ok(()) => {}
-				Err(e) => return Ok(Err(Arc::new(e).into())),
-			}
+		// We may have been cancelled while the last node ran
+		if x.is_ok() && context.cancel.is_cancelled() {
+			return Ok((Err(RunNodeError::Cancelled), trace));
 		}
 
-		return Ok(x);
+		return Ok((x.map(|()| context.item_db_transaction.into_inner()), trace));
 	}
 }
 
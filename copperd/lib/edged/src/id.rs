@@ -22,3 +22,57 @@ impl From<i64> for PipelineId {
 		Self { id: value }
 	}
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ScheduleId {
+	id: i64,
+}
+
+impl From<ScheduleId> for i64 {
+	fn from(value: ScheduleId) -> Self {
+		value.id
+	}
+}
+
+impl From<i64> for ScheduleId {
+	fn from(value: i64) -> Self {
+		Self { id: value }
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct WebhookId {
+	id: i64,
+}
+
+impl From<WebhookId> for i64 {
+	fn from(value: WebhookId) -> Self {
+		value.id
+	}
+}
+
+impl From<i64> for WebhookId {
+	fn from(value: i64) -> Self {
+		Self { id: value }
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct WebhookDeliveryId {
+	id: i64,
+}
+
+impl From<WebhookDeliveryId> for i64 {
+	fn from(value: WebhookDeliveryId) -> Self {
+		value.id
+	}
+}
+
+impl From<i64> for WebhookDeliveryId {
+	fn from(value: i64) -> Self {
+		Self { id: value }
+	}
+}
@@ -4,13 +4,19 @@ use argon2::{
 	password_hash::{rand_core::OsRng, SaltString},
 	Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
 };
-use copper_itemdb::UserId;
+use copper_itemdb::{AttrData, UserId};
 use copper_piper::json::PipelineJson;
 use serde::{Deserialize, Serialize};
 use smartstring::{LazyCompact, SmartString};
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
 use utoipa::ToSchema;
 
-use crate::PipelineId;
+use crate::{
+	schedule::ScheduleTrigger,
+	webhook::{WebhookEvent, WebhookEventKind},
+	PipelineId, ScheduleId, WebhookDeliveryId, WebhookId,
+};
 
 /// A user's hashed password.
 /// This is serialized for storage in the db.
@@ -101,3 +107,117 @@ pub struct PipelineInfo {
 	/// The pipeline
 	pub data: PipelineJson,
 }
+
+/// Schedule Information
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ScheduleInfo {
+	/// The id of this schedule
+	#[schema(value_type = i64)]
+	pub id: ScheduleId,
+
+	/// The user that owns this schedule
+	#[schema(value_type = i64)]
+	pub owned_by: UserId,
+
+	/// The pipeline this schedule runs
+	#[schema(value_type = i64)]
+	pub pipeline: PipelineId,
+
+	/// When this schedule runs
+	pub trigger: ScheduleTrigger,
+
+	/// The input we give the pipeline every time it runs
+	#[schema(value_type = BTreeMap<String, Object>)]
+	pub input: BTreeMap<SmartString<LazyCompact>, AttrData>,
+
+	/// If true, this schedule never runs
+	pub paused: bool,
+
+	/// The next time this schedule will run, if it isn't paused
+	#[schema(value_type = String)]
+	pub next_run_at: OffsetDateTime,
+
+	/// The last time this schedule ran
+	#[schema(value_type = Option<String>)]
+	pub last_run_at: Option<OffsetDateTime>,
+}
+
+/// Webhook Information
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WebhookInfo {
+	/// The id of this webhook
+	#[schema(value_type = i64)]
+	pub id: WebhookId,
+
+	/// The user that owns this webhook
+	#[schema(value_type = i64)]
+	pub owned_by: UserId,
+
+	/// The url we send events to
+	#[schema(value_type = String)]
+	pub url: SmartString<LazyCompact>,
+
+	/// The events this webhook receives
+	pub events: Vec<WebhookEventKind>,
+
+	/// The key we sign this webhook's requests with.
+	///
+	/// This is only shown to the user once, when this webhook is created.
+	/// This field should always be tagged with `#[serde(skip)]`
+	#[serde(skip)]
+	pub secret: SmartString<LazyCompact>,
+
+	#[schema(value_type = String)]
+	pub created_at: OffsetDateTime,
+}
+
+/// The state of one webhook delivery
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum WebhookDeliveryState {
+	/// This delivery hasn't succeeded yet, and will be attempted again
+	Pending,
+
+	/// The receiver accepted this delivery
+	Delivered,
+
+	/// Every attempt to deliver this failed, we won't try again
+	Failed,
+}
+
+/// One event we (tried to) send to a webhook
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WebhookDeliveryInfo {
+	/// The id of this delivery
+	#[schema(value_type = i64)]
+	pub id: WebhookDeliveryId,
+
+	/// The webhook this delivery is for
+	#[schema(value_type = i64)]
+	pub webhook: WebhookId,
+
+	/// The event we're delivering
+	pub event: WebhookEvent,
+
+	/// The state of this delivery
+	pub state: WebhookDeliveryState,
+
+	/// The number of times we've tried to deliver this
+	pub attempt: u32,
+
+	/// The http status of the last attempt, if we got a response
+	pub last_status: Option<u16>,
+
+	/// The error of the last attempt, if it failed
+	pub last_error: Option<String>,
+
+	#[schema(value_type = String)]
+	pub created_at: OffsetDateTime,
+
+	/// The next time we'll try to deliver this, if it's pending
+	#[schema(value_type = Option<String>)]
+	pub next_attempt_at: Option<OffsetDateTime>,
+
+	/// When this was delivered
+	#[schema(value_type = Option<String>)]
+	pub delivered_at: Option<OffsetDateTime>,
+}
@@ -3,3 +3,6 @@ pub use info::*;
 
 mod id;
 pub use id::*;
+
+pub mod schedule;
+pub mod webhook;
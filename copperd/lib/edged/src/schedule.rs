@@ -0,0 +1,133 @@
+//! Triggers for scheduled pipeline runs
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use smartstring::{LazyCompact, SmartString};
+use std::str::FromStr;
+use thiserror::Error;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+/// An error we may encounter when checking a [`ScheduleTrigger`]
+#[derive(Debug, Error)]
+pub enum ScheduleTriggerError {
+	/// An interval trigger must have a nonzero interval
+	#[error("interval must be at least one second")]
+	ZeroInterval,
+
+	/// We could not parse a cron expression
+	#[error("invalid cron expression: {0}")]
+	BadCron(#[from] cron::error::Error),
+}
+
+/// Decides when a scheduled pipeline runs
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type")]
+pub enum ScheduleTrigger {
+	/// Run once every `interval_secs` seconds
+	Interval { interval_secs: u64 },
+
+	/// Run whenever this cron expression matches, in UTC.
+	///
+	/// This may have five fields (`min hour day month weekday`)
+	/// or six (`sec min hour day month weekday`). Note that numeric
+	/// weekdays start at 1 (Sunday), so prefer names like `Mon-Fri`.
+	Cron {
+		#[schema(value_type = String)]
+		expression: SmartString<LazyCompact>,
+	},
+}
+
+impl ScheduleTrigger {
+	fn parse_cron(expression: &str) -> Result<cron::Schedule, ScheduleTriggerError> {
+		// Our cron library expects a seconds field,
+		// add one if this is a standard five-field expression.
+		if expression.split_whitespace().count() == 5 {
+			return Ok(cron::Schedule::from_str(&format!("0 {expression}"))?);
+		}
+
+		return Ok(cron::Schedule::from_str(expression)?);
+	}
+
+	/// Make sure this trigger is valid
+	pub fn validate(&self) -> Result<(), ScheduleTriggerError> {
+		match self {
+			Self::Interval { interval_secs } => {
+				if *interval_secs == 0 {
+					return Err(ScheduleTriggerError::ZeroInterval);
+				}
+			}
+
+			Self::Cron { expression } => {
+				Self::parse_cron(expression)?;
+			}
+		}
+
+		return Ok(());
+	}
+
+	/// Get the first time this trigger fires strictly after `after`.
+	/// Returns `None` if this trigger is invalid or never fires again.
+	pub fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+		return match self {
+			Self::Interval { interval_secs } => {
+				if *interval_secs == 0 {
+					return None;
+				}
+
+				let interval = time::Duration::seconds(i64::try_from(*interval_secs).ok()?);
+				after.checked_add(interval)
+			}
+
+			Self::Cron { expression } => {
+				let schedule = Self::parse_cron(expression).ok()?;
+				let after: DateTime<Utc> =
+					DateTime::from_timestamp(after.unix_timestamp(), after.nanosecond())?;
+				let next = schedule.after(&after).next()?;
+				OffsetDateTime::from_unix_timestamp(next.timestamp()).ok()
+			}
+		};
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use time::macros::datetime;
+
+	#[test]
+	fn interval() {
+		let t = ScheduleTrigger::Interval { interval_secs: 90 };
+		assert!(t.validate().is_ok());
+		assert_eq!(
+			t.next_after(datetime!(2024-01-01 00:00:00 UTC)),
+			Some(datetime!(2024-01-01 00:01:30 UTC))
+		);
+
+		let t = ScheduleTrigger::Interval { interval_secs: 0 };
+		assert!(t.validate().is_err());
+		assert_eq!(t.next_after(datetime!(2024-01-01 00:00:00 UTC)), None);
+	}
+
+	#[test]
+	fn five_field_cron() {
+		// Every day at 03:30
+		let t = ScheduleTrigger::Cron {
+			expression: "30 3 * * *".into(),
+		};
+		assert!(t.validate().is_ok());
+		assert_eq!(
+			t.next_after(datetime!(2024-01-01 03:30:00 UTC)),
+			Some(datetime!(2024-01-02 03:30:00 UTC))
+		);
+	}
+
+	#[test]
+	fn bad_cron() {
+		let t = ScheduleTrigger::Cron {
+			expression: "not a cron expression".into(),
+		};
+		assert!(t.validate().is_err());
+		assert_eq!(t.next_after(datetime!(2024-01-01 00:00:00 UTC)), None);
+	}
+}
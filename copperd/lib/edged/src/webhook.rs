@@ -0,0 +1,137 @@
+//! Events we send to webhooks, and how we sign them
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use smartstring::{LazyCompact, SmartString};
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// The header we put a delivery's signature in.
+/// See [`sign_payload`].
+pub const SIGNATURE_HEADER: &str = "X-Copper-Signature";
+
+/// The header we put a delivery's id in.
+/// This is the same for every attempt of one delivery.
+pub const DELIVERY_HEADER: &str = "X-Copper-Delivery";
+
+/// The kinds of events a webhook may subscribe to
+#[derive(
+	Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema,
+)]
+pub enum WebhookEventKind {
+	/// A job finished successfully
+	JobSuccess,
+
+	/// A job failed to build or run.
+	/// Cancelled jobs don't trigger this event.
+	JobFailure,
+
+	/// Every job in a batch has finished
+	BatchComplete,
+}
+
+/// An event we send to webhooks.
+/// This is the body of every request we send.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "event")]
+pub enum WebhookEvent {
+	/// A job finished successfully
+	JobSuccess {
+		#[schema(value_type = String)]
+		job_id: SmartString<LazyCompact>,
+	},
+
+	/// A job failed to build or run
+	JobFailure {
+		#[schema(value_type = String)]
+		job_id: SmartString<LazyCompact>,
+
+		/// The state this job ended in
+		#[schema(value_type = String)]
+		state: SmartString<LazyCompact>,
+	},
+
+	/// Every job in a batch has finished
+	BatchComplete {
+		#[schema(value_type = String)]
+		batch_id: SmartString<LazyCompact>,
+
+		/// The number of jobs in this batch
+		total_jobs: i64,
+
+		/// The number of jobs in this batch that succeeded
+		successful_jobs: i64,
+	},
+}
+
+impl WebhookEvent {
+	/// The kind of this event
+	pub fn kind(&self) -> WebhookEventKind {
+		match self {
+			Self::JobSuccess { .. } => WebhookEventKind::JobSuccess,
+			Self::JobFailure { .. } => WebhookEventKind::JobFailure,
+			Self::BatchComplete { .. } => WebhookEventKind::BatchComplete,
+		}
+	}
+
+	/// A key that is the same for all copies of this event.
+	/// Each webhook receives at most one delivery per key.
+	pub fn key(&self) -> String {
+		match self {
+			Self::JobSuccess { job_id } => format!("job-success:{job_id}"),
+			Self::JobFailure { job_id, .. } => format!("job-failure:{job_id}"),
+			Self::BatchComplete { batch_id, .. } => format!("batch-complete:{batch_id}"),
+		}
+	}
+}
+
+/// Sign a webhook request.
+///
+/// The signature is a hex-encoded HMAC-SHA256 of `{timestamp}.{body}`,
+/// keyed with the webhook's secret. We send it as `t={timestamp},v1={signature}`,
+/// receivers should check it and reject old timestamps.
+pub fn sign_payload(secret: &str, timestamp: i64, body: &[u8]) -> String {
+	let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+	mac.update(timestamp.to_string().as_bytes());
+	mac.update(b".");
+	mac.update(body);
+	return format!(
+		"t={timestamp},v1={}",
+		hex::encode(mac.finalize().into_bytes())
+	);
+}
+
+/// How long to wait before retrying a delivery
+/// that has failed `attempt` times.
+pub fn retry_delay(attempt: u32) -> Duration {
+	const BASE: Duration = Duration::from_secs(30);
+	const MAX: Duration = Duration::from_secs(60 * 60);
+
+	// 30s, 1m, 2m, 4m, ...
+	let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+	return BASE.saturating_mul(factor).min(MAX);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn signature() {
+		// Reference value from
+		// `printf '1700000000.{}' | openssl dgst -sha256 -hmac secret`
+		assert_eq!(
+			sign_payload("secret", 1700000000, b"{}"),
+			"t=1700000000,v1=b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163"
+		);
+	}
+
+	#[test]
+	fn retry_backoff() {
+		assert_eq!(retry_delay(1), Duration::from_secs(30));
+		assert_eq!(retry_delay(2), Duration::from_secs(60));
+		assert_eq!(retry_delay(3), Duration::from_secs(120));
+		assert_eq!(retry_delay(100), Duration::from_secs(3600));
+	}
+}
@@ -0,0 +1,283 @@
+//! Compiles item filters into sql
+
+use sqlx::{Postgres, QueryBuilder};
+
+use super::value::{push_value_eq, value_column};
+use crate::{
+	client::errors::item::ItemFilterError,
+	filter::{ItemFilter, ItemSort, SortDirection},
+	AttrDataStub, AttributeId, AttributeInfo,
+};
+
+/// Find the attribute `id` in `attrs`
+fn get_attr(attrs: &[AttributeInfo], id: AttributeId) -> Result<&AttributeInfo, ItemFilterError> {
+	return attrs
+		.iter()
+		.find(|x| x.id == id)
+		.ok_or(ItemFilterError::NoSuchAttribute(id));
+}
+
+/// Escape LIKE's wildcards (and its escape character),
+/// so that they match literally.
+fn escape_like(s: &str) -> String {
+	let mut out = String::with_capacity(s.len());
+	for c in s.chars() {
+		if matches!(c, '\\' | '%' | '_') {
+			out.push('\\');
+		}
+		out.push(c);
+	}
+	return out;
+}
+
+/// Start an `EXISTS` subquery over the instances of `attr` that belong to the current item.
+/// The caller may add more conditions, and must close the subquery.
+fn push_instance_exists(q: &mut QueryBuilder<'_, Postgres>, attr: &AttributeInfo) {
+	q.push(
+		"EXISTS (SELECT 1 FROM attribute_instance ai WHERE ai.item_id=item.id AND ai.attribute_id=",
+	);
+	q.push_bind(i64::from(attr.id));
+}
+
+/// Add a condition that is true for each row of `item` that matches `filter`.
+/// `attrs` must contain all attributes of the class we're filtering.
+pub(super) fn push_item_filter(
+	q: &mut QueryBuilder<'_, Postgres>,
+	filter: &ItemFilter,
+	attrs: &[AttributeInfo],
+) -> Result<(), ItemFilterError> {
+	match filter {
+		ItemFilter::All => {
+			q.push("TRUE");
+		}
+
+		ItemFilter::And { filters } => {
+			q.push("(TRUE");
+			for f in filters {
+				q.push(" AND ");
+				push_item_filter(q, f, attrs)?;
+			}
+			q.push(")");
+		}
+
+		ItemFilter::Or { filters } => {
+			q.push("(FALSE");
+			for f in filters {
+				q.push(" OR ");
+				push_item_filter(q, f, attrs)?;
+			}
+			q.push(")");
+		}
+
+		ItemFilter::Not { filter } => {
+			q.push("(NOT ");
+			push_item_filter(q, filter, attrs)?;
+			q.push(")");
+		}
+
+		ItemFilter::Equals { attribute, value } => {
+			let attr = get_attr(attrs, *attribute)?;
+			let value = value
+				.as_attr_data(attr.data_type)
+				.ok_or(ItemFilterError::TypeMismatch(attr.id))?;
+
+			push_instance_exists(q, attr);
+			q.push(" AND ");
+			push_value_eq(q, &value);
+			q.push(")");
+		}
+
+		ItemFilter::IntegerRange {
+			attribute,
+			min,
+			max,
+		} => {
+			let attr = get_attr(attrs, *attribute)?;
+			if !matches!(attr.data_type, AttrDataStub::Integer { .. }) {
+				return Err(ItemFilterError::TypeMismatch(attr.id));
+			}
+
+			push_instance_exists(q, attr);
+			if let Some(min) = min {
+				q.push(format!(" AND ai.{}>=", value_column(attr.data_type)));
+				q.push_bind(*min);
+			}
+			if let Some(max) = max {
+				q.push(format!(" AND ai.{}<=", value_column(attr.data_type)));
+				q.push_bind(*max);
+			}
+			q.push(")");
+		}
+
+		ItemFilter::FloatRange {
+			attribute,
+			min,
+			max,
+		} => {
+			let attr = get_attr(attrs, *attribute)?;
+			if !matches!(attr.data_type, AttrDataStub::Float { .. }) {
+				return Err(ItemFilterError::TypeMismatch(attr.id));
+			}
+
+			push_instance_exists(q, attr);
+			if let Some(min) = min {
+				q.push(format!(" AND ai.{}>=", value_column(attr.data_type)));
+				q.push_bind(*min);
+			}
+			if let Some(max) = max {
+				q.push(format!(" AND ai.{}<=", value_column(attr.data_type)));
+				q.push_bind(*max);
+			}
+			q.push(")");
+		}
+
+		ItemFilter::TextContains { attribute, value } => {
+			let attr = get_attr(attrs, *attribute)?;
+			if attr.data_type != AttrDataStub::Text {
+				return Err(ItemFilterError::TypeMismatch(attr.id));
+			}
+
+			push_instance_exists(q, attr);
+			q.push(format!(" AND ai.{} ILIKE ", value_column(attr.data_type)));
+			q.push_bind(format!("%{}%", escape_like(value)));
+			q.push(")");
+		}
+
+		ItemFilter::TextPrefix { attribute, value } => {
+			let attr = get_attr(attrs, *attribute)?;
+			if attr.data_type != AttrDataStub::Text {
+				return Err(ItemFilterError::TypeMismatch(attr.id));
+			}
+
+			push_instance_exists(q, attr);
+			q.push(format!(" AND ai.{} ILIKE ", value_column(attr.data_type)));
+			q.push_bind(format!("{}%", escape_like(value)));
+			q.push(")");
+		}
+
+		ItemFilter::IsNull { attribute } => {
+			let attr = get_attr(attrs, *attribute)?;
+			q.push("NOT ");
+			push_instance_exists(q, attr);
+			q.push(")");
+		}
+
+		ItemFilter::IsNotNull { attribute } => {
+			let attr = get_attr(attrs, *attribute)?;
+			push_instance_exists(q, attr);
+			q.push(")");
+		}
+	}
+
+	return Ok(());
+}
+
+/// Add an `ORDER BY` clause that sorts rows of `item`.
+/// If `sort` is `None`, items are ordered by id.
+pub(super) fn push_item_sort(
+	q: &mut QueryBuilder<'_, Postgres>,
+	sort: Option<&ItemSort>,
+	attrs: &[AttributeInfo],
+) -> Result<(), ItemFilterError> {
+	q.push(" ORDER BY ");
+
+	if let Some(sort) = sort {
+		let attr = get_attr(attrs, sort.attribute)?;
+
+		q.push(format!(
+			"(SELECT ai.{} FROM attribute_instance ai WHERE ai.item_id=item.id AND ai.attribute_id=",
+			value_column(attr.data_type)
+		));
+		q.push_bind(i64::from(attr.id));
+		q.push(match sort.direction {
+			SortDirection::Ascending => ") ASC NULLS LAST, ",
+			SortDirection::Descending => ") DESC NULLS LAST, ",
+		});
+	}
+
+	q.push("item.id");
+	return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{filter::FilterValue, AttributeOptions};
+
+	fn attrs() -> Vec<AttributeInfo> {
+		[
+			(1, AttrDataStub::Text),
+			(
+				2,
+				AttrDataStub::Integer {
+					is_non_negative: true,
+				},
+			),
+		]
+		.into_iter()
+		.map(|(id, data_type)| AttributeInfo {
+			id: id.into(),
+			class: 1.into(),
+			order: id,
+			name: format!("attr{id}").into(),
+			data_type,
+			options: AttributeOptions::default(),
+		})
+		.collect()
+	}
+
+	#[test]
+	fn compiles_nested_filter() {
+		let filter = ItemFilter::And {
+			filters: vec![
+				ItemFilter::TextPrefix {
+					attribute: 1.into(),
+					value: "50%".into(),
+				},
+				ItemFilter::Not {
+					filter: Box::new(ItemFilter::IntegerRange {
+						attribute: 2.into(),
+						min: Some(3),
+						max: None,
+					}),
+				},
+			],
+		};
+
+		let mut q = QueryBuilder::new("");
+		push_item_filter(&mut q, &filter, &attrs()).unwrap();
+		assert_eq!(
+			q.sql(),
+			"(TRUE AND EXISTS (SELECT 1 FROM attribute_instance ai \
+			WHERE ai.item_id=item.id AND ai.attribute_id=$1 \
+			AND ai.value_text ILIKE $2) \
+			AND (NOT EXISTS (SELECT 1 FROM attribute_instance ai \
+			WHERE ai.item_id=item.id AND ai.attribute_id=$3 \
+			AND ai.value_integer>=$4)))"
+		);
+	}
+
+	#[test]
+	fn rejects_bad_attributes() {
+		let mut q = QueryBuilder::new("");
+		let res = push_item_filter(
+			&mut q,
+			&ItemFilter::Equals {
+				attribute: 2.into(),
+				value: FilterValue::Text { value: "x".into() },
+			},
+			&attrs(),
+		);
+		assert!(matches!(res, Err(ItemFilterError::TypeMismatch(x)) if x == 2.into()));
+
+		let mut q = QueryBuilder::new("");
+		let res = push_item_filter(
+			&mut q,
+			&ItemFilter::IsNull {
+				attribute: 3.into(),
+			},
+			&attrs(),
+		);
+		assert!(matches!(res, Err(ItemFilterError::NoSuchAttribute(x)) if x == 3.into()));
+	}
+}
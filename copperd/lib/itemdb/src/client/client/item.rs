@@ -1,18 +1,29 @@
 //! This modules contains Copper's itemdb client
 
 use itertools::Itertools;
-use sqlx::{Acquire, Row};
-use std::collections::BTreeMap;
+use sqlx::{Acquire, QueryBuilder, Row};
+use std::collections::{BTreeMap, BTreeSet};
 use thiserror::Error;
 
 use crate::{
 	AttrData, AttrDataStub, AttributeId, AttributeInfo, AttributeOptions, ClassId, ItemId,
 };
 
-use super::ItemdbClient;
+use super::{
+	filter::{push_item_filter, push_item_sort},
+	value::{
+		find_unique_conflicts, instance_is_unique, is_unique_violation, read_value,
+		upsert_instances,
+	},
+	ItemdbClient,
+};
 use crate::{
-	client::errors::item::{CountItemsError, GetItemError, ListItemsError},
-	ItemInfo,
+	client::errors::item::{
+		BulkSetItemAttrsError, CountItemsError, DeleteItemError, FindItemsError, GetItemError,
+		ListItemsError, SetItemAttrsError,
+	},
+	filter::{ItemFilter, ItemSort},
+	ItemInfo, UniqueConflict,
 };
 
 /// An error we can encounter when creating an item
@@ -85,14 +96,22 @@ impl ItemdbClient {
 
 		// Fill in attributes that have data
 		// Empty attributes will be `None`.
-		let res = sqlx::query("SELECT * FROM attribute_instance WHERE item_id=$1;")
-			.bind(i64::from(item))
-			.fetch_all(&mut **t)
-			.await?;
+		let res = sqlx::query(
+			"
+			SELECT ai.*, attribute.data_type
+			FROM attribute_instance ai
+			JOIN attribute ON attribute.id=ai.attribute_id
+			WHERE ai.item_id=$1;
+			",
+		)
+		.bind(i64::from(item))
+		.fetch_all(&mut **t)
+		.await?;
 		for row in res {
 			let attr_id: AttributeId = row.get::<i64, _>("attribute_id").into();
-			let value: AttrData =
-				serde_json::from_str(row.get::<&str, _>("attribute_value")).unwrap();
+			let data_type: AttrDataStub =
+				serde_json::from_str(row.get::<&str, _>("data_type")).unwrap();
+			let value = read_value(&row, data_type);
 
 			let x = attribute_values.insert(attr_id, value);
 			assert!(x.is_none()) // Each insert should be new
@@ -105,58 +124,73 @@ impl ItemdbClient {
 		})
 	}
 
+	/// List the items in `class` that match `filter`.
+	/// If `sort` is `None`, items are ordered by id.
 	pub async fn list_items(
 		&self,
 		t: &mut sqlx::Transaction<'_, sqlx::Postgres>,
 		class: ClassId,
+		filter: &ItemFilter,
+		sort: Option<&ItemSort>,
 		skip: i64,
 		count: usize,
 	) -> Result<Vec<ItemInfo>, ListItemsError> {
-		let res = sqlx::query(
-			"
-			SELECT * FROM attribute_instance
-			WHERE item_id in (
-				SELECT id FROM item
-				WHERE class_id=$1
-				ORDER BY id
-				OFFSET $2 LIMIT $3
-			)
-			ORDER BY item_id;
-			",
-		)
-		.bind(i64::from(class))
-		.bind(skip)
-		.bind(i64::try_from(count).unwrap())
-		.fetch_all(&mut **t)
-		.await;
-		// Produces three columns:
-		// item_id, attribute_id, attribute_value
-
-		return match res {
-			Err(sqlx::Error::RowNotFound) => Err(ListItemsError::ClassNotFound),
-			Err(e) => Err(e.into()),
-			Ok(rows) => {
-				let mut out: BTreeMap<ItemId, ItemInfo> = BTreeMap::new();
+		let attrs = class_attributes(t, class).await?;
+
+		// Find the items we want...
+		let mut q = QueryBuilder::new("SELECT id FROM item WHERE class_id=");
+		q.push_bind(i64::from(class));
+		q.push(" AND ");
+		push_item_filter(&mut q, filter, &attrs)?;
+		push_item_sort(&mut q, sort, &attrs)?;
+		q.push(" OFFSET ");
+		q.push_bind(skip);
+		q.push(" LIMIT ");
+		q.push_bind(i64::try_from(count).unwrap());
+
+		let item_ids: Vec<i64> = q
+			.build()
+			.fetch_all(&mut **t)
+			.await?
+			.into_iter()
+			.map(|row| row.get::<i64, _>("id"))
+			.collect();
 
-				for row in rows {
-					let item_id: ItemId = row.get::<i64, _>("item_id").into();
-					let attr_id: AttributeId = row.get::<i64, _>("attribute_id").into();
-					let value: AttrData =
-						serde_json::from_str(row.get::<&str, _>("attribute_value")).unwrap();
+		// ...and then get their attributes
+		let rows = sqlx::query("SELECT * FROM attribute_instance WHERE item_id=ANY($1);")
+			.bind(&item_ids)
+			.fetch_all(&mut **t)
+			.await?;
 
-					out.entry(item_id).or_insert_with(|| ItemInfo {
-						id: item_id,
+		let mut out: BTreeMap<ItemId, ItemInfo> = item_ids
+			.iter()
+			.map(|id| {
+				(
+					(*id).into(),
+					ItemInfo {
+						id: (*id).into(),
 						class,
 						attribute_values: BTreeMap::new(),
-					});
+					},
+				)
+			})
+			.collect();
 
-					let x = out.get_mut(&item_id).unwrap();
-					x.attribute_values.insert(attr_id, value);
-				}
+		for row in rows {
+			let item_id: ItemId = row.get::<i64, _>("item_id").into();
+			let attr_id: AttributeId = row.get::<i64, _>("attribute_id").into();
+			let attr = attrs.iter().find(|x| x.id == attr_id).unwrap();
+			let value = read_value(&row, attr.data_type);
 
-				Ok(out.into_values().collect())
-			}
-		};
+			let x = out.get_mut(&item_id).unwrap();
+			x.attribute_values.insert(attr_id, value);
+		}
+
+		// Return items in the order we found them
+		return Ok(item_ids
+			.into_iter()
+			.map(|id| out.remove(&id.into()).unwrap())
+			.collect());
 	}
 
 	pub async fn add_item(
@@ -195,23 +229,7 @@ impl ItemdbClient {
 		//
 
 		// Get all attributes this class has
-		let all_attrs = sqlx::query("SELECT * FROM attribute WHERE class_id=$1;")
-			.bind(i64::from(to_class))
-			.fetch_all(&mut *t)
-			.await?
-			.into_iter()
-			.map(|row| AttributeInfo {
-				id: row.get::<i64, _>("id").into(),
-				class: row.get::<i64, _>("class_id").into(),
-				order: row.get::<i64, _>("attr_order"),
-				name: row.get::<String, _>("pretty_name").into(),
-				data_type: serde_json::from_str(row.get::<&str, _>("data_type")).unwrap(),
-				options: AttributeOptions {
-					is_unique: row.get("is_unique"),
-					is_not_null: row.get("is_not_null"),
-				},
-			})
-			.collect::<Vec<_>>();
+		let all_attrs = class_attributes(&mut t, to_class).await?;
 
 		for (attr_id, _) in &attributes {
 			// Make sure all attributes we got belong to this class...
@@ -225,72 +243,36 @@ impl ItemdbClient {
 			}
 		}
 
-		// Serialize each attribute...
-		let ser_attrs = {
-			let mut ser_attrs = Vec::new();
-
-			for attr in all_attrs.into_iter() {
-				let value = attributes
-					.iter()
-					.find(|(a_id, _)| *a_id == attr.id)
-					.map(|x| &x.1);
-
-				if let Some(value) = value {
-					// Make sure type matches
-					if value.as_stub() != attr.data_type {
-						return Err(AddItemError::AttributeDataTypeMismatch);
-					}
+		// Match each attribute with its value...
+		let mut values = Vec::new();
+		for attr in all_attrs.iter() {
+			let value = attributes
+				.iter()
+				.find(|(a_id, _)| *a_id == attr.id)
+				.map(|x| &x.1);
+
+			if let Some(value) = value {
+				// Make sure type matches
+				if value.as_stub() != attr.data_type {
+					return Err(AddItemError::AttributeDataTypeMismatch);
 				}
-
-				let value_ser = value.map(|x| serde_json::to_string(&x).unwrap());
-
-				// Generate value for "unique" constraint
-				// Has no effect on blobs, so don't check them.
-				// (this is why that switch is hidden in ui)
-				let unique_hash: Option<String> = if attr.options.is_unique
-					&& attr.data_type != AttrDataStub::Blob
-					&& value_ser.is_some()
-				{
-					Some(value_ser.clone().unwrap())
-				} else {
-					None
-				};
-
-				ser_attrs.push((attr, value_ser, unique_hash));
 			}
 
-			ser_attrs
-		};
+			values.push((attr, value));
+		}
 
 		// Now, create instances for every attribute we got.
-		for (attr, value_ser, unique_hash) in &ser_attrs {
-			if let Some(value_ser) = value_ser {
+		for (attr, value) in &values {
+			if let Some(value) = value {
 				// Create the attribute instances
-				let res = sqlx::query(
-					"
-					INSERT INTO attribute_instance
-					(item_id, attribute_id, attribute_value, unique_hash)
-					VALUES ($1, $2, $3, $4);
-					",
-				)
-				.bind(i64::from(new_item))
-				.bind(i64::from(attr.id))
-				.bind(value_ser)
-				.bind(unique_hash)
-				.execute(&mut *t)
-				.await;
+				let res = upsert_instances(&mut t, &[i64::from(new_item)], attr, value).await;
 
 				match res {
 					Ok(_) => {}
 					Err(sqlx::Error::Database(e)) => {
 						if e.is_foreign_key_violation() {
 							return Err(AddItemError::BadAttribute);
-						} else if e
-							.constraint()
-							.map(|x| x == "idx_attrinst_unique_hash")
-							.unwrap_or(false) && attr.options.is_unique
-							&& attr.data_type != AttrDataStub::Blob
-						{
+						} else if is_unique_violation(&*e) {
 							// Drop transaction to free `trans`, we can't use it anyway
 							// (since it encountered an error)
 							t.rollback().await?;
@@ -298,31 +280,12 @@ impl ItemdbClient {
 							// Find all conflicts
 							// (even those across multiple attributes)
 							let mut conflicting_ids = Vec::new();
-
-							for (attr, _, unique_hash) in &ser_attrs {
-								// Look for non-unique row
-								match sqlx::query(
-									"
-									SELECT item_id
-									FROM attribute_instance
-									WHERE attribute_id=$1
-									AND unique_hash=$2
-									",
-								)
-								.bind(i64::from(attr.id))
-								.bind(unique_hash)
-								.fetch_all(&mut **trans)
-								.await
-								{
-									Ok(res) => {
-										for row in res {
-											conflicting_ids
-												.push(row.get::<i64, _>("item_id").into())
-										}
-									}
-
-									Err(e) => return Err(e.into()),
-								};
+							for (attr, value) in &values {
+								if let (true, Some(value)) = (instance_is_unique(attr), value) {
+									conflicting_ids.extend(
+										find_unique_conflicts(trans, attr, value, &[]).await?,
+									);
+								}
 							}
 
 							return Err(AddItemError::UniqueViolated { conflicting_ids });
@@ -344,19 +307,350 @@ impl ItemdbClient {
 		return Ok(new_item);
 	}
 
+	/// Change some of an item's attributes.
+	///
+	/// Attributes set to `None` are cleared,
+	/// attributes that aren't given are left unchanged.
+	pub async fn set_item_attrs(
+		&self,
+		trans: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+		item: ItemId,
+		attributes: Vec<(AttributeId, Option<AttrData>)>,
+	) -> Result<(), SetItemAttrsError> {
+		// Make sure we have at most one of each attribute
+		if !attributes.iter().map(|(x, _)| x).all_unique() {
+			return Err(SetItemAttrsError::RepeatedAttribute);
+		}
+
+		let mut t = trans.begin().await?;
+
+		let res = sqlx::query("SELECT class_id FROM item WHERE id=$1 FOR UPDATE;")
+			.bind(i64::from(item))
+			.fetch_one(&mut *t)
+			.await;
+
+		let class: ClassId = match res {
+			Ok(res) => res.get::<i64, _>("class_id").into(),
+			Err(sqlx::Error::RowNotFound) => return Err(SetItemAttrsError::NotFound),
+			Err(e) => return Err(e.into()),
+		};
+
+		let all_attrs = class_attributes(&mut t, class).await?;
+
+		for (attr_id, value) in &attributes {
+			let attr = match all_attrs.iter().find(|x| x.id == *attr_id) {
+				Some(x) => x,
+				None => return Err(SetItemAttrsError::ForeignAttribute),
+			};
+
+			let value = match value {
+				Some(x) => x,
+				None => {
+					// Check "not null" constraint
+					if attr.options.is_not_null {
+						return Err(SetItemAttrsError::NotNullViolated);
+					}
+
+					sqlx::query(
+						"DELETE FROM attribute_instance WHERE item_id=$1 AND attribute_id=$2;",
+					)
+					.bind(i64::from(item))
+					.bind(i64::from(attr.id))
+					.execute(&mut *t)
+					.await?;
+
+					continue;
+				}
+			};
+
+			// Make sure type matches
+			if value.as_stub() != attr.data_type {
+				return Err(SetItemAttrsError::AttributeDataTypeMismatch);
+			}
+
+			// Make sure references point to a real item.
+			// This also keeps that item from being deleted until we commit.
+			if let AttrData::Reference {
+				class: ref_class,
+				item: ref_item,
+			} = value
+			{
+				let res = sqlx::query("SELECT id FROM item WHERE id=$1 AND class_id=$2 FOR SHARE;")
+					.bind(i64::from(*ref_item))
+					.bind(i64::from(*ref_class))
+					.fetch_optional(&mut *t)
+					.await?;
+
+				if res.is_none() {
+					return Err(SetItemAttrsError::NoSuchReferencedItem);
+				}
+			}
+
+			let res = upsert_instances(&mut t, &[i64::from(item)], attr, value).await;
+
+			match res {
+				Ok(_) => {}
+				Err(sqlx::Error::Database(e)) => {
+					if is_unique_violation(&*e) {
+						// We can't use this transaction after an error
+						t.rollback().await?;
+
+						let conflicting_ids =
+							find_unique_conflicts(trans, attr, value, &[i64::from(item)]).await?;
+
+						return Err(SetItemAttrsError::UniqueViolated { conflicting_ids });
+					} else {
+						return Err(sqlx::Error::Database(e).into());
+					}
+				}
+				Err(e) => return Err(e.into()),
+			}
+		}
+
+		t.commit().await?;
+		return Ok(());
+	}
+
+	/// Delete an item.
+	/// Fails if any other item references this one.
+	pub async fn del_item(
+		&self,
+		trans: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+		item: ItemId,
+	) -> Result<(), DeleteItemError> {
+		let mut t = trans.begin().await?;
+
+		let res = sqlx::query("SELECT id FROM item WHERE id=$1 FOR UPDATE;")
+			.bind(i64::from(item))
+			.fetch_one(&mut *t)
+			.await;
+
+		match res {
+			Ok(_) => {}
+			Err(sqlx::Error::RowNotFound) => return Err(DeleteItemError::NotFound),
+			Err(e) => return Err(e.into()),
+		};
+
+		// Item ids are unique across classes,
+		// so we don't need to check the referenced class.
+		let referencing_ids: Vec<ItemId> = sqlx::query(
+			"
+			SELECT DISTINCT item_id
+			FROM attribute_instance
+			WHERE value_reference=$1
+			AND item_id!=$2
+			ORDER BY item_id;
+			",
+		)
+		.bind(i64::from(item))
+		.bind(i64::from(item))
+		.fetch_all(&mut *t)
+		.await?
+		.into_iter()
+		.map(|row| row.get::<i64, _>("item_id").into())
+		.collect();
+
+		if !referencing_ids.is_empty() {
+			return Err(DeleteItemError::Referenced { referencing_ids });
+		}
+
+		// This also deletes all attribute instances,
+		// since they're marked with ON DELETE CASCADE.
+		sqlx::query("DELETE FROM item WHERE id=$1;")
+			.bind(i64::from(item))
+			.execute(&mut *t)
+			.await?;
+
+		t.commit().await?;
+		return Ok(());
+	}
+
+	//
+	// MARK: bulk
+	//
+
+	/// Get the ids of all items in `class` that match `filter`, in order
+	pub async fn find_items(
+		&self,
+		t: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+		class: ClassId,
+		filter: &ItemFilter,
+	) -> Result<Vec<ItemId>, FindItemsError> {
+		let attrs = class_attributes(t, class).await?;
+
+		let mut q = QueryBuilder::new("SELECT id FROM item WHERE class_id=");
+		q.push_bind(i64::from(class));
+		q.push(" AND ");
+		push_item_filter(&mut q, filter, &attrs)?;
+		q.push(" ORDER BY id;");
+
+		let res = q.build().fetch_all(&mut **t).await?;
+		return Ok(res
+			.into_iter()
+			.map(|row| row.get::<i64, _>("id").into())
+			.collect());
+	}
+
+	/// Set the same attributes on many items of one class.
+	/// Returns the number of items we edited.
+	///
+	/// Attributes set to `None` are cleared,
+	/// attributes that aren't given are left unchanged.
+	///
+	/// All constraints are checked before any item is changed,
+	/// so this either edits every item or none of them.
+	pub async fn bulk_set_item_attrs(
+		&self,
+		trans: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+		class: ClassId,
+		items: &[ItemId],
+		attributes: Vec<(AttributeId, Option<AttrData>)>,
+	) -> Result<u64, BulkSetItemAttrsError> {
+		// Make sure we have at most one of each attribute
+		if !attributes.iter().map(|(x, _)| x).all_unique() {
+			return Err(BulkSetItemAttrsError::RepeatedAttribute);
+		}
+
+		let item_ids: Vec<i64> = items.iter().map(|x| i64::from(*x)).unique().collect();
+		if item_ids.is_empty() {
+			return Ok(0);
+		}
+
+		let mut t = trans.begin().await?;
+
+		// Make sure all items are in this class,
+		// and keep them from changing until we're done.
+		let found: BTreeSet<i64> =
+			sqlx::query("SELECT id FROM item WHERE id=ANY($1) AND class_id=$2 FOR UPDATE;")
+				.bind(&item_ids)
+				.bind(i64::from(class))
+				.fetch_all(&mut *t)
+				.await?
+				.into_iter()
+				.map(|row| row.get::<i64, _>("id"))
+				.collect();
+
+		if found.len() != item_ids.len() {
+			return Err(BulkSetItemAttrsError::BadItems {
+				item_ids: item_ids
+					.iter()
+					.filter(|x| !found.contains(x))
+					.map(|x| (*x).into())
+					.collect(),
+			});
+		}
+
+		let all_attrs = class_attributes(&mut t, class).await?;
+
+		// Check every assignment before changing anything
+		let mut assignments = Vec::new();
+		for (attr_id, value) in &attributes {
+			let attr = match all_attrs.iter().find(|x| x.id == *attr_id) {
+				Some(x) => x,
+				None => return Err(BulkSetItemAttrsError::ForeignAttribute),
+			};
+
+			let value = match value {
+				Some(x) => x,
+				None => {
+					// Check "not null" constraint
+					if attr.options.is_not_null {
+						return Err(BulkSetItemAttrsError::NotNullViolated);
+					}
+
+					assignments.push(BulkAssignment { attr, value: None });
+					continue;
+				}
+			};
+
+			// Make sure type matches
+			if value.as_stub() != attr.data_type {
+				return Err(BulkSetItemAttrsError::AttributeDataTypeMismatch);
+			}
+
+			// Make sure references point to a real item
+			if let AttrData::Reference {
+				class: ref_class,
+				item: ref_item,
+			} = value
+			{
+				let res = sqlx::query("SELECT id FROM item WHERE id=$1 AND class_id=$2 FOR SHARE;")
+					.bind(i64::from(*ref_item))
+					.bind(i64::from(*ref_class))
+					.fetch_optional(&mut *t)
+					.await?;
+
+				if res.is_none() {
+					return Err(BulkSetItemAttrsError::NoSuchReferencedItem);
+				}
+			}
+
+			assignments.push(BulkAssignment {
+				attr,
+				value: Some(value),
+			});
+		}
+
+		let conflicts = bulk_unique_conflicts(&mut t, &item_ids, &assignments).await?;
+		if !conflicts.is_empty() {
+			return Err(BulkSetItemAttrsError::UniqueViolated { conflicts });
+		}
+
+		for a in &assignments {
+			let res =
+				match &a.value {
+					None => sqlx::query(
+						"DELETE FROM attribute_instance WHERE attribute_id=$1 AND item_id=ANY($2);",
+					)
+					.bind(i64::from(a.attr.id))
+					.bind(&item_ids)
+					.execute(&mut *t)
+					.await,
+
+					Some(value) => upsert_instances(&mut t, &item_ids, a.attr, value).await,
+				};
+
+			match res {
+				Ok(_) => {}
+				Err(sqlx::Error::Database(e)) => {
+					if is_unique_violation(&*e) {
+						// Another transaction added a conflicting value after we checked.
+						// We can't use this transaction after an error.
+						t.rollback().await?;
+						let conflicts =
+							bulk_unique_conflicts(trans, &item_ids, &assignments).await?;
+						return Err(BulkSetItemAttrsError::UniqueViolated { conflicts });
+					} else {
+						return Err(sqlx::Error::Database(e).into());
+					}
+				}
+				Err(e) => return Err(e.into()),
+			}
+		}
+
+		t.commit().await?;
+		return Ok(u64::try_from(item_ids.len()).unwrap());
+	}
+
 	//
 	// MARK: misc
 	//
 
+	/// Count the items in `class` that match `filter`
 	pub async fn count_items(
 		&self,
 		t: &mut sqlx::Transaction<'_, sqlx::Postgres>,
 		class: ClassId,
+		filter: &ItemFilter,
 	) -> Result<i64, CountItemsError> {
-		let res = sqlx::query("SELECT COUNT(*) FROM item WHERE class_id=$1;")
-			.bind(i64::from(class))
-			.fetch_one(&mut **t)
-			.await;
+		let attrs = class_attributes(t, class).await?;
+
+		let mut q = QueryBuilder::new("SELECT COUNT(*) FROM item WHERE class_id=");
+		q.push_bind(i64::from(class));
+		q.push(" AND ");
+		push_item_filter(&mut q, filter, &attrs)?;
+
+		let res = q.build().fetch_one(&mut **t).await;
 
 		return match res {
 			Err(sqlx::Error::RowNotFound) => Err(CountItemsError::ClassNotFound),
@@ -365,3 +659,66 @@ impl ItemdbClient {
 		};
 	}
 }
+
+/// Get all attributes of a class
+async fn class_attributes(
+	t: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+	class: ClassId,
+) -> Result<Vec<AttributeInfo>, sqlx::Error> {
+	return Ok(sqlx::query("SELECT * FROM attribute WHERE class_id=$1;")
+		.bind(i64::from(class))
+		.fetch_all(&mut **t)
+		.await?
+		.into_iter()
+		.map(|row| AttributeInfo {
+			id: row.get::<i64, _>("id").into(),
+			class: row.get::<i64, _>("class_id").into(),
+			order: row.get::<i64, _>("attr_order"),
+			name: row.get::<String, _>("pretty_name").into(),
+			data_type: serde_json::from_str(row.get::<&str, _>("data_type")).unwrap(),
+			options: AttributeOptions {
+				is_unique: row.get("is_unique"),
+				is_not_null: row.get("is_not_null"),
+			},
+		})
+		.collect());
+}
+
+/// One attribute assignment in [`ItemdbClient::bulk_set_item_attrs`]
+struct BulkAssignment<'a> {
+	attr: &'a AttributeInfo,
+
+	/// The value to set, or `None` to clear this attribute
+	value: Option<&'a AttrData>,
+}
+
+/// Find all `unique` constraints that setting `assignments`
+/// on all items in `item_ids` would violate.
+async fn bulk_unique_conflicts(
+	t: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+	item_ids: &[i64],
+	assignments: &[BulkAssignment<'_>],
+) -> Result<Vec<UniqueConflict>, sqlx::Error> {
+	let mut conflicts = Vec::new();
+
+	for a in assignments {
+		let value = match a.value {
+			Some(x) if instance_is_unique(a.attr) => x,
+			_ => continue,
+		};
+
+		let existing = find_unique_conflicts(t, a.attr, value, item_ids).await?;
+
+		// Every edited item gets the same value,
+		// so they conflict with each other if there are many.
+		if item_ids.len() > 1 || !existing.is_empty() {
+			conflicts.push(UniqueConflict {
+				attribute: a.attr.id,
+				items: item_ids.iter().map(|x| (*x).into()).collect(),
+				existing,
+			});
+		}
+	}
+
+	return Ok(conflicts);
+}
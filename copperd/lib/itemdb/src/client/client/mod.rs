@@ -12,7 +12,10 @@ use crate::client::migrate;
 mod attribute;
 mod class;
 mod dataset;
+mod filter;
 mod item;
+mod search;
+mod value;
 pub use item::*;
 
 #[derive(Debug, Error)]
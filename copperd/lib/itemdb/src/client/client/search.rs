@@ -0,0 +1,164 @@
+//! Full-text search over text attributes
+
+use itertools::Itertools;
+use sqlx::Row;
+
+use super::ItemdbClient;
+use crate::{client::errors::search::SearchItemsError, DatasetId, ItemSearchResult, SnippetPart};
+
+/// Marks the start of a match in a headline.
+/// These are control characters, so they won't appear in item data.
+const MATCH_START: char = '\u{2}';
+
+/// Marks the end of a match in a headline
+const MATCH_STOP: char = '\u{3}';
+
+/// Make a tsquery that matches text containing every word in `query`.
+/// The last word is matched as a prefix, so that results update as a user types.
+///
+/// Returns `None` if `query` has no words.
+fn make_tsquery(query: &str) -> Option<String> {
+	// `simple` splits text on anything that isn't a letter or digit.
+	// We do the same, which also removes all tsquery syntax.
+	let words = query
+		.split(|c: char| !c.is_alphanumeric())
+		.filter(|x| !x.is_empty())
+		.collect::<Vec<_>>();
+
+	let (last, rest) = words.split_last()?;
+	return Some(
+		rest.iter()
+			.map(|x| format!("'{x}'"))
+			.chain(std::iter::once(format!("'{last}':*")))
+			.join(" & "),
+	);
+}
+
+/// Split a headline made by `ts_headline` into parts
+fn parse_headline(headline: &str) -> Vec<SnippetPart> {
+	let mut out = Vec::new();
+	let mut is_match = false;
+	let mut text = String::new();
+
+	for c in headline.chars() {
+		if c == MATCH_START || c == MATCH_STOP {
+			if !text.is_empty() {
+				out.push(SnippetPart {
+					text: std::mem::take(&mut text),
+					is_match,
+				});
+			}
+			is_match = c == MATCH_START;
+		} else {
+			text.push(c);
+		}
+	}
+
+	if !text.is_empty() {
+		out.push(SnippetPart { text, is_match });
+	}
+
+	return out;
+}
+
+impl ItemdbClient {
+	/// Find items in any class of `dataset` with text attributes that match `query`.
+	/// Results are ordered best match first.
+	pub async fn search_items(
+		&self,
+		t: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+		dataset: DatasetId,
+		query: &str,
+		skip: i64,
+		count: usize,
+	) -> Result<Vec<ItemSearchResult>, SearchItemsError> {
+		let tsquery = match make_tsquery(query) {
+			Some(x) => x,
+			None => return Ok(Vec::new()),
+		};
+
+		// Each item is represented by its best-matching attribute.
+		// Headlines are slow, so we only make them for the results we return.
+		let res = sqlx::query(
+			"
+			WITH matches AS (
+				SELECT DISTINCT ON (ai.item_id)
+					ai.item_id,
+					ai.attribute_id,
+					item.class_id,
+					ts_rank(ai.search_vector, to_tsquery('simple', $2)) AS rank,
+					ai.value_text AS value
+				FROM attribute_instance ai
+				JOIN item ON item.id=ai.item_id
+				JOIN class ON class.id=item.class_id
+				WHERE class.dataset_id=$1
+				AND ai.search_vector @@ to_tsquery('simple', $2)
+				ORDER BY ai.item_id, rank DESC
+			)
+			SELECT
+				item_id, attribute_id, class_id, rank,
+				ts_headline('simple', value, to_tsquery('simple', $2), $3) AS headline
+			FROM matches
+			ORDER BY rank DESC, item_id
+			OFFSET $4 LIMIT $5;
+			",
+		)
+		.bind(i64::from(dataset))
+		.bind(&tsquery)
+		.bind(format!(
+			"StartSel={MATCH_START}, StopSel={MATCH_STOP}, MinWords=8, MaxWords=24"
+		))
+		.bind(skip)
+		.bind(i64::try_from(count).unwrap())
+		.fetch_all(&mut **t)
+		.await?;
+
+		return Ok(res
+			.into_iter()
+			.map(|row| ItemSearchResult {
+				item: row.get::<i64, _>("item_id").into(),
+				class: row.get::<i64, _>("class_id").into(),
+				attribute: row.get::<i64, _>("attribute_id").into(),
+				rank: row.get("rank"),
+				snippet: parse_headline(row.get("headline")),
+			})
+			.collect());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn tsquery_matches_words() {
+		assert_eq!(make_tsquery("  "), None);
+		assert_eq!(make_tsquery("coltrane").unwrap(), "'coltrane':*");
+		assert_eq!(
+			make_tsquery("AC/DC 'back in' bl").unwrap(),
+			"'AC' & 'DC' & 'back' & 'in' & 'bl':*"
+		);
+	}
+
+	#[test]
+	fn headline_parts() {
+		let headline = format!("a {MATCH_START}love{MATCH_STOP} supreme");
+		assert_eq!(
+			parse_headline(&headline),
+			vec![
+				SnippetPart {
+					text: "a ".into(),
+					is_match: false
+				},
+				SnippetPart {
+					text: "love".into(),
+					is_match: true
+				},
+				SnippetPart {
+					text: " supreme".into(),
+					is_match: false
+				},
+			]
+		);
+	}
+}
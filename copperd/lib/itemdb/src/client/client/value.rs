@@ -0,0 +1,366 @@
+//! Reads and writes attribute values.
+//!
+//! Each attribute instance stores its value in the typed column
+//! that matches its attribute's type, all other value columns are `NULL`.
+//! Parts of [`AttrData`] that are fixed by the attribute's type
+//! (hash type, referenced class, etc) aren't stored.
+
+use itertools::Itertools;
+use sqlx::{
+	error::DatabaseError,
+	postgres::{PgQueryResult, PgRow},
+	Postgres, QueryBuilder, Row,
+};
+
+use crate::{AttrData, AttrDataStub, AttributeInfo, ItemId};
+
+/// All columns of `attribute_instance` that may hold a value
+const VALUE_COLUMNS: [&str; 8] = [
+	"value_text",
+	"value_integer",
+	"value_float",
+	"value_boolean",
+	"value_bytes",
+	"value_reference",
+	"value_blob_bucket",
+	"value_blob_key",
+];
+
+/// The column that holds values of type `data_type`.
+/// Blobs use two columns, this is the one that holds their key.
+pub(super) fn value_column(data_type: AttrDataStub) -> &'static str {
+	return match data_type {
+		AttrDataStub::Text => "value_text",
+		AttrDataStub::Integer { .. } => "value_integer",
+		AttrDataStub::Float { .. } => "value_float",
+		AttrDataStub::Boolean => "value_boolean",
+		AttrDataStub::Hash { .. } => "value_bytes",
+		AttrDataStub::Reference { .. } => "value_reference",
+		AttrDataStub::Blob => "value_blob_key",
+	};
+}
+
+/// The value columns of one attribute instance.
+/// Fields are in the same order as [`VALUE_COLUMNS`].
+#[derive(Debug, Default)]
+struct ValueColumns {
+	text: Option<String>,
+	integer: Option<i64>,
+	float: Option<f64>,
+	boolean: Option<bool>,
+	bytes: Option<Vec<u8>>,
+	reference: Option<i64>,
+	blob_bucket: Option<String>,
+	blob_key: Option<String>,
+}
+
+impl ValueColumns {
+	/// The columns that store `value`
+	fn from_value(value: &AttrData) -> Self {
+		let mut out = Self::default();
+
+		match value {
+			AttrData::Text { value } => out.text = Some(value.to_string()),
+			AttrData::Integer { value, .. } => out.integer = Some(*value),
+			AttrData::Float { value, .. } => out.float = Some(*value),
+			AttrData::Boolean { value } => out.boolean = Some(*value),
+			AttrData::Hash { data, .. } => out.bytes = Some(data.clone()),
+			AttrData::Reference { item, .. } => out.reference = Some(i64::from(*item)),
+			AttrData::Blob { bucket, key } => {
+				out.blob_bucket = Some(bucket.to_string());
+				out.blob_key = Some(key.to_string());
+			}
+		}
+
+		return out;
+	}
+
+	/// Read all value columns from `row`
+	fn from_row(row: &PgRow) -> Self {
+		return Self {
+			text: row.get("value_text"),
+			integer: row.get("value_integer"),
+			float: row.get("value_float"),
+			boolean: row.get("value_boolean"),
+			bytes: row.get("value_bytes"),
+			reference: row.get("value_reference"),
+			blob_bucket: row.get("value_blob_bucket"),
+			blob_key: row.get("value_blob_key"),
+		};
+	}
+
+	/// The value of type `data_type` these columns hold.
+	/// Panics if the columns this type uses are `NULL`.
+	fn into_value(self, data_type: AttrDataStub) -> AttrData {
+		return match data_type {
+			AttrDataStub::Text => AttrData::Text {
+				value: self.text.unwrap().into(),
+			},
+
+			AttrDataStub::Integer { is_non_negative } => AttrData::Integer {
+				value: self.integer.unwrap(),
+				is_non_negative,
+			},
+
+			AttrDataStub::Float { is_non_negative } => AttrData::Float {
+				value: self.float.unwrap(),
+				is_non_negative,
+			},
+
+			AttrDataStub::Boolean => AttrData::Boolean {
+				value: self.boolean.unwrap(),
+			},
+
+			AttrDataStub::Hash { hash_type } => AttrData::Hash {
+				hash_type,
+				data: self.bytes.unwrap(),
+			},
+
+			AttrDataStub::Reference { class } => AttrData::Reference {
+				class,
+				item: self.reference.unwrap().into(),
+			},
+
+			AttrDataStub::Blob => AttrData::Blob {
+				bucket: self.blob_bucket.unwrap().into(),
+				key: self.blob_key.unwrap().into(),
+			},
+		};
+	}
+}
+
+/// Read the value of an attribute instance of type `data_type` from `row`.
+/// `row` must contain every column in [`VALUE_COLUMNS`].
+pub(super) fn read_value(row: &PgRow, data_type: AttrDataStub) -> AttrData {
+	return ValueColumns::from_row(row).into_value(data_type);
+}
+
+/// Should instances of `attr` be checked by `unique` indices?
+/// This has no effect on blobs, so we don't check them.
+/// (this is why that switch is hidden in ui)
+pub(super) fn instance_is_unique(attr: &AttributeInfo) -> bool {
+	return attr.options.is_unique && attr.data_type != AttrDataStub::Blob;
+}
+
+/// Is `e` a violation of an attribute's `unique` constraint?
+pub(super) fn is_unique_violation(e: &dyn DatabaseError) -> bool {
+	return e
+		.constraint()
+		.map(|x| x.starts_with("idx_attrinst_unique_"))
+		.unwrap_or(false);
+}
+
+/// Add a condition that is true for each row of `attribute_instance ai` that holds `value`
+pub(super) fn push_value_eq(q: &mut QueryBuilder<'_, Postgres>, value: &AttrData) {
+	match value {
+		AttrData::Text { value } => {
+			q.push("ai.value_text=");
+			q.push_bind(value.to_string());
+		}
+
+		AttrData::Integer { value, .. } => {
+			q.push("ai.value_integer=");
+			q.push_bind(*value);
+		}
+
+		AttrData::Float { value, .. } => {
+			q.push("ai.value_float=");
+			q.push_bind(*value);
+		}
+
+		AttrData::Boolean { value } => {
+			q.push("ai.value_boolean=");
+			q.push_bind(*value);
+		}
+
+		AttrData::Hash { data, .. } => {
+			q.push("ai.value_bytes=");
+			q.push_bind(data.clone());
+		}
+
+		AttrData::Reference { item, .. } => {
+			q.push("ai.value_reference=");
+			q.push_bind(i64::from(*item));
+		}
+
+		AttrData::Blob { bucket, key } => {
+			q.push("(ai.value_blob_bucket=");
+			q.push_bind(bucket.to_string());
+			q.push(" AND ai.value_blob_key=");
+			q.push_bind(key.to_string());
+			q.push(")");
+		}
+	}
+}
+
+/// Set the value of `attr` to `value` on every item in `items`,
+/// replacing the values they already have.
+///
+/// `value` must have the same type as `attr`.
+pub(super) async fn upsert_instances(
+	t: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+	items: &[i64],
+	attr: &AttributeInfo,
+	value: &AttrData,
+) -> Result<PgQueryResult, sqlx::Error> {
+	let mut q =
+		QueryBuilder::new("INSERT INTO attribute_instance (item_id, attribute_id, is_unique");
+	for c in VALUE_COLUMNS {
+		q.push(", ");
+		q.push(c);
+	}
+
+	q.push(") SELECT id, ");
+	q.push_bind(i64::from(attr.id));
+	q.push(", ");
+	q.push_bind(instance_is_unique(attr));
+
+	// Same order as `VALUE_COLUMNS`
+	let columns = ValueColumns::from_value(value);
+	q.push(", ");
+	q.push_bind(columns.text);
+	q.push(", ");
+	q.push_bind(columns.integer);
+	q.push(", ");
+	q.push_bind(columns.float);
+	q.push(", ");
+	q.push_bind(columns.boolean);
+	q.push(", ");
+	q.push_bind(columns.bytes);
+	q.push(", ");
+	q.push_bind(columns.reference);
+	q.push(", ");
+	q.push_bind(columns.blob_bucket);
+	q.push(", ");
+	q.push_bind(columns.blob_key);
+
+	q.push(" FROM UNNEST(");
+	q.push_bind(items.to_vec());
+	q.push("::BIGINT[]) AS id ON CONFLICT (item_id, attribute_id) DO UPDATE SET ");
+	q.push(
+		VALUE_COLUMNS
+			.iter()
+			.map(|c| format!("{c}=EXCLUDED.{c}"))
+			.join(", "),
+	);
+	q.push(";");
+
+	return q.build().execute(&mut **t).await;
+}
+
+/// Find all unique instances of `attr` that hold `value`,
+/// ignoring the items in `exclude`.
+pub(super) async fn find_unique_conflicts(
+	t: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+	attr: &AttributeInfo,
+	value: &AttrData,
+	exclude: &[i64],
+) -> Result<Vec<ItemId>, sqlx::Error> {
+	let mut q = QueryBuilder::new(
+		"SELECT ai.item_id FROM attribute_instance ai WHERE ai.is_unique AND ai.attribute_id=",
+	);
+	q.push_bind(i64::from(attr.id));
+	q.push(" AND ");
+	push_value_eq(&mut q, value);
+	q.push(" AND NOT (ai.item_id=ANY(");
+	q.push_bind(exclude.to_vec());
+	q.push(")) ORDER BY ai.item_id;");
+
+	return Ok(q
+		.build()
+		.fetch_all(&mut **t)
+		.await?
+		.into_iter()
+		.map(|row| row.get::<i64, _>("item_id").into())
+		.collect());
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use copper_util::HashType;
+
+	fn values() -> Vec<AttrData> {
+		vec![
+			AttrData::Text {
+				value: "some text".into(),
+			},
+			AttrData::Integer {
+				value: -3,
+				is_non_negative: false,
+			},
+			AttrData::Float {
+				value: -0.0,
+				is_non_negative: false,
+			},
+			AttrData::Float {
+				value: f64::INFINITY,
+				is_non_negative: true,
+			},
+			AttrData::Float {
+				value: f64::NAN,
+				is_non_negative: false,
+			},
+			AttrData::Boolean { value: true },
+			AttrData::Hash {
+				hash_type: HashType::SHA256,
+				data: vec![0, 1, 254, 255],
+			},
+			AttrData::Reference {
+				class: 4.into(),
+				item: 12.into(),
+			},
+			AttrData::Blob {
+				bucket: "bucket".into(),
+				key: "some/key".into(),
+			},
+		]
+	}
+
+	/// The names of the columns in `columns` that aren't `NULL`
+	fn set_columns(columns: &ValueColumns) -> Vec<&'static str> {
+		let set = [
+			columns.text.is_some(),
+			columns.integer.is_some(),
+			columns.float.is_some(),
+			columns.boolean.is_some(),
+			columns.bytes.is_some(),
+			columns.reference.is_some(),
+			columns.blob_bucket.is_some(),
+			columns.blob_key.is_some(),
+		];
+
+		return VALUE_COLUMNS
+			.into_iter()
+			.zip(set)
+			.filter_map(|(c, set)| set.then_some(c))
+			.collect();
+	}
+
+	#[test]
+	fn values_round_trip() {
+		for value in values() {
+			let columns = ValueColumns::from_value(&value);
+			let read = columns.into_value(value.as_stub());
+
+			// `AttrData` isn't `PartialEq`, and this
+			// tells `0.0` from `-0.0` and matches `NaN`s.
+			assert_eq!(format!("{read:?}"), format!("{value:?}"));
+		}
+	}
+
+	#[test]
+	fn values_use_their_column() {
+		for value in values() {
+			let columns = ValueColumns::from_value(&value);
+			let data_type = value.as_stub();
+
+			let expected = match data_type {
+				AttrDataStub::Blob => vec!["value_blob_bucket", "value_blob_key"],
+				_ => vec![value_column(data_type)],
+			};
+
+			assert_eq!(set_columns(&columns), expected, "{value:?}");
+		}
+	}
+}
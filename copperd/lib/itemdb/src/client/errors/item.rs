@@ -1,6 +1,8 @@
 //! Errors we can encounter when operating on classes
 use thiserror::Error;
 
+use crate::{AttributeId, ItemId, UniqueConflict};
+
 /// An error we can encounter when listing items
 #[derive(Debug, Error)]
 pub enum ListItemsError {
@@ -11,6 +13,10 @@ pub enum ListItemsError {
 	/// We tried get items from a class that doesn't exist
 	#[error("class not found")]
 	ClassNotFound,
+
+	/// The filter or sort we were given is invalid
+	#[error("invalid filter")]
+	BadFilter(#[from] ItemFilterError),
 }
 
 /// An error we can encounter when counting
@@ -23,6 +29,10 @@ pub enum CountItemsError {
 	/// We tried count items in a class that doesn't exist
 	#[error("class not found")]
 	ClassNotFound,
+
+	/// The filter we were given is invalid
+	#[error("invalid filter")]
+	BadFilter(#[from] ItemFilterError),
 }
 
 /// An error we can encounter when getting item info
@@ -36,3 +46,121 @@ pub enum GetItemError {
 	#[error("item not found")]
 	NotFound,
 }
+
+/// An error we can encounter when changing an item's attributes
+#[derive(Debug, Error)]
+pub enum SetItemAttrsError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+
+	/// An item with this id doesn't exist
+	#[error("item not found")]
+	NotFound,
+
+	/// We provided multiple values for one attribute
+	#[error("multiple values were provided for one attribute")]
+	RepeatedAttribute,
+
+	/// We tried to set an attribute from another class
+	#[error("tried to set a foreign attribute")]
+	ForeignAttribute,
+
+	/// We tried to assign data to an attribute,
+	/// but that data has the wrong type
+	#[error("tried to assign data to an attribute, but type doesn't match")]
+	AttributeDataTypeMismatch,
+
+	/// We tried to reference an item that doesn't exist
+	#[error("tried to reference an item that doesn't exist")]
+	NoSuchReferencedItem,
+
+	/// We tried to clear an attribute with a "not null" constraint
+	#[error("tried to set attributes that violate a `not null` constraint")]
+	NotNullViolated,
+
+	/// We tried to set attributes that violate a "unique" constraint
+	#[error("tried to set attributes that violate a `unique` constraint")]
+	UniqueViolated { conflicting_ids: Vec<ItemId> },
+}
+
+/// An error we can encounter when deleting an item
+#[derive(Debug, Error)]
+pub enum DeleteItemError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+
+	/// An item with this id doesn't exist
+	#[error("item not found")]
+	NotFound,
+
+	/// Other items reference this item.
+	/// These references must be removed first.
+	#[error("this item is referenced by other items")]
+	Referenced { referencing_ids: Vec<ItemId> },
+}
+
+/// An error we can encounter when compiling an item filter
+#[derive(Debug, Error)]
+pub enum ItemFilterError {
+	/// The filter uses an attribute that isn't in this class
+	#[error("attribute {0:?} is not in this class")]
+	NoSuchAttribute(AttributeId),
+
+	/// The filter compares an attribute to a value of another type,
+	/// or uses an operation this attribute's type doesn't support
+	#[error("attribute {0:?} can't be compared to a value of this type")]
+	TypeMismatch(AttributeId),
+}
+
+/// An error we can encounter when finding items
+#[derive(Debug, Error)]
+pub enum FindItemsError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+
+	/// The filter we were given is invalid
+	#[error("invalid filter")]
+	BadFilter(#[from] ItemFilterError),
+}
+
+/// An error we can encounter when changing many items' attributes
+#[derive(Debug, Error)]
+pub enum BulkSetItemAttrsError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+
+	/// Some of the items we tried to edit don't exist
+	/// or aren't in the class we're editing
+	#[error("some items don't exist or aren't in this class")]
+	BadItems { item_ids: Vec<ItemId> },
+
+	/// We provided multiple values for one attribute
+	#[error("multiple values were provided for one attribute")]
+	RepeatedAttribute,
+
+	/// We tried to set an attribute from another class
+	#[error("tried to set a foreign attribute")]
+	ForeignAttribute,
+
+	/// We tried to assign data to an attribute,
+	/// but that data has the wrong type
+	#[error("tried to assign data to an attribute, but type doesn't match")]
+	AttributeDataTypeMismatch,
+
+	/// We tried to reference an item that doesn't exist
+	#[error("tried to reference an item that doesn't exist")]
+	NoSuchReferencedItem,
+
+	/// We tried to clear an attribute with a "not null" constraint
+	#[error("tried to set attributes that violate a `not null` constraint")]
+	NotNullViolated,
+
+	/// We tried to set attributes that violate "unique" constraints.
+	/// This lists every violation.
+	#[error("tried to set attributes that violate a `unique` constraint")]
+	UniqueViolated { conflicts: Vec<UniqueConflict> },
+}
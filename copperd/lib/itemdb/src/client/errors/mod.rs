@@ -4,3 +4,4 @@ pub mod attribute;
 pub mod class;
 pub mod dataset;
 pub mod item;
+pub mod search;
@@ -0,0 +1,11 @@
+//! Errors we can encounter when searching items
+
+use thiserror::Error;
+
+/// An error we can encounter when searching a dataset
+#[derive(Debug, Error)]
+pub enum SearchItemsError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
@@ -0,0 +1,42 @@
+use copper_migrate::Migration;
+use sqlx::Connection;
+
+pub(super) struct MigrationStep {}
+
+#[async_trait::async_trait]
+impl Migration for MigrationStep {
+	fn name(&self) -> &str {
+		"m_1_search"
+	}
+
+	async fn up(&self, conn: &mut sqlx::PgConnection) -> Result<(), sqlx::Error> {
+		let mut t = conn.begin().await?;
+
+		// Only text attributes are searchable.
+		// We use the `simple` configuration since collections
+		// are rarely in one language, and are full of names.
+		sqlx::query(
+			"
+			ALTER TABLE attribute_instance
+			ADD COLUMN search_vector TSVECTOR
+			GENERATED ALWAYS AS (
+				CASE WHEN attribute_value::JSONB->>'type' = 'Text'
+				THEN to_tsvector('simple'::REGCONFIG, attribute_value::JSONB->>'value')
+				ELSE NULL END
+			) STORED;
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		sqlx::query(
+			"CREATE INDEX idx_attrinst_search on attribute_instance USING GIN (search_vector);",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		t.commit().await?;
+
+		return Ok(());
+	}
+}
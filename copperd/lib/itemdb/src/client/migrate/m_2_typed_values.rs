@@ -0,0 +1,214 @@
+use copper_migrate::Migration;
+use sqlx::{Connection, Row};
+use tracing::{error, warn};
+
+pub(super) struct MigrationStep {}
+
+#[async_trait::async_trait]
+impl Migration for MigrationStep {
+	fn name(&self) -> &str {
+		"m_2_typed_values"
+	}
+
+	async fn up(&self, conn: &mut sqlx::PgConnection) -> Result<(), sqlx::Error> {
+		let mut t = conn.begin().await?;
+
+		//
+		// MARK: New columns
+		//
+
+		// Each attribute instance sets exactly one of these columns
+		// (two for blobs), which one depends on its attribute's type.
+		// Everything else about a value (hash type, referenced class, etc)
+		// is part of the attribute's type.
+		sqlx::query(
+			"
+			ALTER TABLE attribute_instance
+			ADD COLUMN value_text TEXT,
+			ADD COLUMN value_integer BIGINT,
+			ADD COLUMN value_float DOUBLE PRECISION,
+			ADD COLUMN value_boolean BOOLEAN,
+			ADD COLUMN value_bytes BYTEA,
+			ADD COLUMN value_reference BIGINT,
+			ADD COLUMN value_blob_bucket TEXT,
+			ADD COLUMN value_blob_key TEXT,
+
+			-- True if this instance's attribute is `unique`.
+			-- Replaces `unique_hash`.
+			ADD COLUMN is_unique BOOLEAN NOT NULL DEFAULT FALSE;
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		//
+		// MARK: Move data
+		//
+
+		// serde_json writes non-finite floats as `null`,
+		// so we can't tell infinities from NaN. They all become NaN.
+		let res = sqlx::query(
+			"
+			SELECT item_id, attribute_id FROM attribute_instance
+			WHERE attribute_value::JSONB->>'type' = 'Float'
+			AND attribute_value::JSONB->>'value' IS NULL;
+			",
+		)
+		.fetch_all(&mut *t)
+		.await?;
+
+		for row in &res {
+			warn!(
+				message = "Non-finite float will be stored as NaN",
+				item = row.get::<i64, _>("item_id"),
+				attribute = row.get::<i64, _>("attribute_id"),
+			);
+		}
+
+		// `attribute_value` holds a serialized `AttrData`.
+		// Hashes are arrays of bytes.
+		sqlx::query(
+			"
+			UPDATE attribute_instance SET
+				value_text = CASE WHEN v->>'type' = 'Text' THEN v->>'value' END,
+				value_integer = CASE WHEN v->>'type' = 'Integer' THEN (v->>'value')::BIGINT END,
+				value_float = CASE WHEN v->>'type' = 'Float'
+					THEN COALESCE((v->>'value')::DOUBLE PRECISION, 'NaN') END,
+				value_boolean = CASE WHEN v->>'type' = 'Boolean' THEN (v->>'value')::BOOLEAN END,
+				value_bytes = CASE WHEN v->>'type' = 'Hash' THEN COALESCE(
+					(
+						SELECT decode(string_agg(lpad(to_hex(b::INTEGER), 2, '0'), '' ORDER BY i), 'hex')
+						FROM jsonb_array_elements_text(v->'data') WITH ORDINALITY AS bytes(b, i)
+					),
+					''::BYTEA
+				) END,
+				value_reference = CASE WHEN v->>'type' = 'Reference' THEN (v->>'item')::BIGINT END,
+				value_blob_bucket = CASE WHEN v->>'type' = 'Blob' THEN v->>'bucket' END,
+				value_blob_key = CASE WHEN v->>'type' = 'Blob' THEN v->>'key' END,
+				is_unique = unique_hash IS NOT NULL
+			FROM (
+				SELECT item_id AS v_item, attribute_id AS v_attr, attribute_value::JSONB AS v
+				FROM attribute_instance
+			) AS old
+			WHERE item_id=old.v_item AND attribute_id=old.v_attr;
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		//
+		// MARK: Drop old columns
+		//
+
+		// This also drops every index on these columns
+		sqlx::query(
+			"
+			ALTER TABLE attribute_instance
+			DROP COLUMN search_vector,
+			DROP COLUMN unique_hash,
+			DROP COLUMN attribute_value;
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		// Only text attributes set `value_text`, see `m_1_search`.
+		sqlx::query(
+			"
+			ALTER TABLE attribute_instance
+			ADD COLUMN search_vector TSVECTOR
+			GENERATED ALWAYS AS (to_tsvector('simple'::REGCONFIG, value_text)) STORED;
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		sqlx::query(
+			"CREATE INDEX idx_attrinst_search on attribute_instance USING GIN (search_vector);",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		//
+		// MARK: Indices
+		//
+
+		// Used to filter and sort by value
+		for col in ["value_text", "value_integer", "value_float", "value_bytes"] {
+			sqlx::query(&format!(
+				"
+				CREATE INDEX idx_attrinst_{col}
+				ON attribute_instance(attribute_id, {col})
+				WHERE {col} IS NOT NULL;
+				"
+			))
+			.execute(&mut *t)
+			.await?;
+		}
+
+		// Used to find references to an item
+		sqlx::query(
+			"
+			CREATE INDEX idx_attrinst_value_reference
+			ON attribute_instance(value_reference)
+			WHERE value_reference IS NOT NULL;
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		// Enforce `unique` attributes.
+		// Blobs are never unique.
+		let unique_columns = [
+			"value_text",
+			"value_integer",
+			"value_float",
+			"value_boolean",
+			"value_bytes",
+			"value_reference",
+		];
+
+		// `unique_hash` was a hash of the serialized value, which tells `0.0` from `-0.0`.
+		// Postgres doesn't, so items that used to be unique may conflict now.
+		// Report every conflict here, since the index below only reports the first one.
+		// Creating that index fails if there are any, which rolls back this migration.
+		for col in unique_columns {
+			let res = sqlx::query(&format!(
+				"
+				SELECT attribute_id, {col}::TEXT AS value, array_agg(item_id ORDER BY item_id) AS items
+				FROM attribute_instance
+				WHERE is_unique AND {col} IS NOT NULL
+				GROUP BY attribute_id, {col}
+				HAVING COUNT(*) > 1;
+				"
+			))
+			.fetch_all(&mut *t)
+			.await?;
+
+			for row in &res {
+				error!(
+					message = "Items share the value of a unique attribute, change one of them and migrate again",
+					attribute = row.get::<i64, _>("attribute_id"),
+					value = row.get::<&str, _>("value"),
+					items = ?row.get::<Vec<i64>, _>("items"),
+				);
+			}
+		}
+
+		for col in unique_columns {
+			sqlx::query(&format!(
+				"
+				CREATE UNIQUE INDEX idx_attrinst_unique_{col}
+				ON attribute_instance(attribute_id, {col})
+				WHERE is_unique;
+				"
+			))
+			.execute(&mut *t)
+			.await?;
+		}
+
+		t.commit().await?;
+
+		return Ok(());
+	}
+}
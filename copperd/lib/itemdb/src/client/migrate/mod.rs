@@ -1,5 +1,11 @@
 use copper_migrate::Migration;
 
 mod m_0_init;
+mod m_1_search;
+mod m_2_typed_values;
 
-pub const MIGRATE_STEPS: &[&'static dyn Migration] = &[&m_0_init::MigrationStep {}];
+pub const MIGRATE_STEPS: &[&'static dyn Migration] = &[
+	&m_0_init::MigrationStep {},
+	&m_1_search::MigrationStep {},
+	&m_2_typed_values::MigrationStep {},
+];
@@ -11,7 +11,7 @@ use super::id::{ClassId, ItemId};
 
 /// A value stored inside an attribute.
 /// These are never directly provided by users,
-/// and are serialized as json in the item db.
+/// and are stored in typed columns in the item db.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum AttrData {
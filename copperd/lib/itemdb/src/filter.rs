@@ -0,0 +1,179 @@
+//! Filters that select items in a class
+
+use serde::Deserialize;
+use smartstring::{LazyCompact, SmartString};
+use utoipa::ToSchema;
+
+use crate::{AttrData, AttrDataStub, AttributeId, ItemId};
+
+/// A value we compare an attribute to.
+/// This must have the same type as the attribute it is compared to.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(tag = "type")]
+pub enum FilterValue {
+	/// A block of text
+	Text {
+		#[schema(value_type = String)]
+		value: SmartString<LazyCompact>,
+	},
+
+	/// An integer
+	Integer { value: i64 },
+
+	/// A float
+	Float { value: f64 },
+
+	/// A boolean
+	Boolean { value: bool },
+
+	/// A checksum
+	Hash { data: Vec<u8> },
+
+	/// An item in the class the attribute references
+	Reference {
+		#[schema(value_type = i64)]
+		item: ItemId,
+	},
+}
+
+impl FilterValue {
+	/// Make the [`AttrData`] an attribute of type `data_type`
+	/// must hold to be equal to this value.
+	/// Returns `None` if this value can't be stored in that attribute.
+	pub fn as_attr_data(&self, data_type: AttrDataStub) -> Option<AttrData> {
+		Some(match (self, data_type) {
+			(Self::Text { value }, AttrDataStub::Text) => AttrData::Text {
+				value: value.clone(),
+			},
+
+			(Self::Integer { value }, AttrDataStub::Integer { is_non_negative }) => {
+				AttrData::Integer {
+					value: *value,
+					is_non_negative,
+				}
+			}
+
+			(Self::Float { value }, AttrDataStub::Float { is_non_negative }) => AttrData::Float {
+				value: *value,
+				is_non_negative,
+			},
+
+			(Self::Boolean { value }, AttrDataStub::Boolean) => AttrData::Boolean { value: *value },
+
+			(Self::Hash { data }, AttrDataStub::Hash { hash_type }) => AttrData::Hash {
+				hash_type,
+				data: data.clone(),
+			},
+
+			(Self::Reference { item }, AttrDataStub::Reference { class }) => {
+				AttrData::Reference { class, item: *item }
+			}
+
+			_ => return None,
+		})
+	}
+}
+
+/// Selects items in a class
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(tag = "op")]
+pub enum ItemFilter {
+	/// Match every item
+	All,
+
+	/// Match items that match all of these filters
+	And { filters: Vec<ItemFilter> },
+
+	/// Match items that match any of these filters
+	Or { filters: Vec<ItemFilter> },
+
+	/// Match items that don't match this filter
+	Not { filter: Box<ItemFilter> },
+
+	/// Match items whose attribute has exactly this value.
+	/// Use a `Reference` value to find items that reference an item.
+	Equals {
+		#[schema(value_type = i64)]
+		attribute: AttributeId,
+		value: FilterValue,
+	},
+
+	/// Match items whose integer attribute is in a range.
+	/// Both bounds are inclusive, and `None` bounds are ignored.
+	IntegerRange {
+		#[schema(value_type = i64)]
+		attribute: AttributeId,
+
+		#[serde(default)]
+		min: Option<i64>,
+
+		#[serde(default)]
+		max: Option<i64>,
+	},
+
+	/// Match items whose float attribute is in a range.
+	/// Both bounds are inclusive, and `None` bounds are ignored.
+	FloatRange {
+		#[schema(value_type = i64)]
+		attribute: AttributeId,
+
+		#[serde(default)]
+		min: Option<f64>,
+
+		#[serde(default)]
+		max: Option<f64>,
+	},
+
+	/// Match items whose text attribute contains this string, ignoring case
+	TextContains {
+		#[schema(value_type = i64)]
+		attribute: AttributeId,
+
+		#[schema(value_type = String)]
+		value: SmartString<LazyCompact>,
+	},
+
+	/// Match items whose text attribute starts with this string, ignoring case
+	TextPrefix {
+		#[schema(value_type = i64)]
+		attribute: AttributeId,
+
+		#[schema(value_type = String)]
+		value: SmartString<LazyCompact>,
+	},
+
+	/// Match items that have no value for this attribute
+	IsNull {
+		#[schema(value_type = i64)]
+		attribute: AttributeId,
+	},
+
+	/// Match items that have a value for this attribute
+	IsNotNull {
+		#[schema(value_type = i64)]
+		attribute: AttributeId,
+	},
+}
+
+/// The order we list items in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+pub enum SortDirection {
+	#[default]
+	Ascending,
+
+	Descending,
+}
+
+/// Sorts items by the value of an attribute.
+/// Items without a value are always listed last,
+/// and items with equal values are ordered by id.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct ItemSort {
+	/// The attribute to sort by
+	#[schema(value_type = i64)]
+	pub attribute: AttributeId,
+
+	/// The order to list items in
+	#[serde(default)]
+	pub direction: SortDirection,
+}
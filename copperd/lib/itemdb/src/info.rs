@@ -103,3 +103,54 @@ pub struct ItemInfo {
 	/// All attributes this item has
 	pub attribute_values: BTreeMap<AttributeId, AttrData>,
 }
+
+/// A `unique` constraint that an edit of many items would violate
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UniqueConflict {
+	/// The attribute whose constraint would be violated
+	#[schema(value_type = i64)]
+	pub attribute: AttributeId,
+
+	/// The edited items that conflict.
+	/// If there is more than one, these conflict with each other.
+	#[schema(value_type = Vec<i64>)]
+	pub items: Vec<ItemId>,
+
+	/// Items we didn't edit that already have the new value.
+	/// Every item in `items` conflicts with these.
+	#[schema(value_type = Vec<i64>)]
+	pub existing: Vec<ItemId>,
+}
+
+/// A piece of a search result's snippet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SnippetPart {
+	/// The text of this piece
+	pub text: String,
+
+	/// If true, this text matches the search query
+	pub is_match: bool,
+}
+
+/// An item that matches a search
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ItemSearchResult {
+	/// The item that matched
+	#[schema(value_type = i64)]
+	pub item: ItemId,
+
+	/// The class this item belongs to
+	#[schema(value_type = i64)]
+	pub class: ClassId,
+
+	/// The attribute that best matches the query
+	#[schema(value_type = i64)]
+	pub attribute: AttributeId,
+
+	/// How well this item matches the query.
+	/// Only meaningful relative to other results of the same search.
+	pub rank: f32,
+
+	/// An excerpt of `attribute` that shows the matched text
+	pub snippet: Vec<SnippetPart>,
+}
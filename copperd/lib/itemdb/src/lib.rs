@@ -7,4 +7,6 @@ pub use info::*;
 mod id;
 pub use id::*;
 
+pub mod filter;
+
 pub mod client;
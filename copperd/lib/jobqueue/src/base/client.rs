@@ -1,19 +1,29 @@
 //! The job queue client api
 
 use async_trait::async_trait;
-use copper_itemdb::{AttrData, UserId};
-use copper_piper::json::PipelineJson;
-use smartstring::{LazyCompact, SmartString};
-use std::collections::BTreeMap;
+use copper_itemdb::UserId;
+use copper_piper::trace::NodeTrace;
+use sqlx::{Postgres, Transaction};
+use std::time::Duration;
+use time::OffsetDateTime;
 
 use crate::{
-	id::QueuedJobId,
-	info::{QueuedJobInfo, QueuedJobInfoList, QueuedJobInfoShort},
+	filter::{JobFilter, JobSortBy, SortDirection},
+	id::{BatchId, QueuedJobId},
+	info::{
+		BatchInfo, ExpiredJobs, FinishedJob, JobAttempt, NewJob, QueuedJobInfo, QueuedJobInfoList,
+		QueuedJobInfoShort,
+	},
+	limits::JobLimits,
+	retention::JobRetention,
+	scheduling::SchedulingPolicy,
 };
 
 use super::errors::{
-	AddJobError, BuildErrorJobError, FailJobError, GetJobShortError, GetQueuedJobError,
-	GetUserJobsError, SuccessJobError,
+	AddJobError, BuildErrorJobError, CancelJobError, DeleteJobsError, FailJobError, GetBatchError,
+	GetExpiredJobsError, GetFinishedJobsError, GetJobAttemptsError, GetJobError, GetJobShortError,
+	GetJobTraceError, GetQueuedJobError, GetUserJobsError, HeartbeatJobError, ReapJobsError,
+	RetryJobError, SuccessJobError,
 };
 
 /// A generic job queue
@@ -22,49 +32,188 @@ pub trait JobQueueClient
 where
 	Self: Send + Sync,
 {
-	/// Queue a new job
+	/// Queue a new job.
+	///
+	/// This fails if `owned_by` would exceed any of the given `limits`.
+	/// Limits are checked atomically, so concurrent calls can't exceed them.
 	async fn add_job(
 		&self,
-		job_id: QueuedJobId,
 		owned_by: UserId,
-		pipeline: &PipelineJson,
-		input: &BTreeMap<SmartString<LazyCompact>, AttrData>,
+		job: NewJob<'_>,
+		limits: &JobLimits,
 	) -> Result<QueuedJobId, AddJobError>;
 
+	/// Queue a batch of jobs.
+	///
+	/// Either all of `jobs` are added, or none of them are.
+	/// `limits` are checked as in [`JobQueueClient::add_job`], counting every job in the batch.
+	/// Batches larger than [`crate::limits::MAX_BATCH_SIZE`] are never added.
+	async fn add_jobs(
+		&self,
+		owned_by: UserId,
+		batch_id: &BatchId,
+		jobs: &[NewJob<'_>],
+		limits: &JobLimits,
+	) -> Result<(), AddJobError>;
+
+	/// Get a batch of jobs, and the progress of its jobs
+	async fn get_batch(&self, batch_id: &BatchId) -> Result<BatchInfo, GetBatchError>;
+
 	/// Get a job by id
 	async fn get_job_short(
 		&self,
 		job_id: &QueuedJobId,
 	) -> Result<QueuedJobInfoShort, GetJobShortError>;
 
+	/// Get all information about a job
+	async fn get_job(&self, job_id: &QueuedJobId) -> Result<QueuedJobInfo, GetJobError>;
+
+	/// Get the execution trace of a job.
+	/// This is empty if the job hasn't finished running.
+	async fn get_job_trace(&self, job_id: &QueuedJobId)
+		-> Result<Vec<NodeTrace>, GetJobTraceError>;
+
+	/// Get every finished attempt of a job, oldest first
+	async fn get_job_attempts(
+		&self,
+		job_id: &QueuedJobId,
+	) -> Result<Vec<JobAttempt>, GetJobAttemptsError>;
+
 	/// List all a user's jobs
 	async fn get_user_jobs(
 		&self,
 		owned_by: UserId,
+		filter: &JobFilter,
+		sort_by: JobSortBy,
+		direction: SortDirection,
 		skip: i64,
 		count: i64,
 	) -> Result<QueuedJobInfoList, GetUserJobsError>;
 
-	/// Get the oldest job with `state = Queued` and set `state = Running`
+	/// Pick a job with `state = Queued` that is ready to be attempted
+	/// and set `state = Running`. This starts a new attempt.
 	/// The returned QueuedJobInfo should have `state = Running`.
 	///
+	/// `policy` decides which job we pick, see [`SchedulingPolicy`].
+	/// Only `limits.max_running_per_user` is checked here,
+	/// the other limits apply when jobs are added.
+	///
 	/// This action must be globally atomic. Only one process should
 	/// ever get a queued job.
-	async fn get_queued_job(&self) -> Result<Option<QueuedJobInfo>, GetQueuedJobError>;
+	///
+	/// The returned job is leased to `lease_owner` for `lease_duration`.
+	/// The owner must keep extending this lease with [`JobQueueClient::heartbeat_job`]
+	/// while it runs the job, or the job will be reaped.
+	async fn get_queued_job(
+		&self,
+		lease_owner: &str,
+		lease_duration: Duration,
+		policy: &SchedulingPolicy,
+		limits: &JobLimits,
+	) -> Result<Option<QueuedJobInfo>, GetQueuedJobError>;
+
+	/// Extend the lease on a running job, so that it expires
+	/// `lease_duration` from now.
+	///
+	/// Returns [`HeartbeatJobError::LeaseLost`] if this job is
+	/// no longer `Running` or is leased by someone else
+	/// (e.g, because it was cancelled or reaped).
+	/// The caller should stop running this job.
+	async fn heartbeat_job(
+		&self,
+		job_id: &QueuedJobId,
+		lease_owner: &str,
+		lease_duration: Duration,
+	) -> Result<(), HeartbeatJobError>;
+
+	/// End the current attempt of every `Running` job whose lease has expired.
+	/// These jobs are queued again if their retry policy allows it,
+	/// and marked `FailedRunning` otherwise.
+	///
+	/// Returns the ids of all jobs we reaped.
+	async fn reap_expired_jobs(&self) -> Result<Vec<QueuedJobId>, ReapJobsError>;
 
 	/// Atomically mark the given job as `BuildError`.
-	/// If this job is not `Running`, throw an error.
+	/// If this job is not `Running` or is not leased to `lease_owner`, throw an error.
 	async fn builderror_job(
 		&self,
 		job_id: &QueuedJobId,
+		lease_owner: &str,
 		error_message: &str,
 	) -> Result<(), BuildErrorJobError>;
 
-	/// Atomically mark the given job as `FailedRunning`.
-	/// If this job is not `Running`, throw an error.
-	async fn fail_job_run(&self, job_id: &QueuedJobId, message: &str) -> Result<(), FailJobError>;
+	/// Atomically mark the given job as `FailedRunning`
+	/// and save its execution trace.
+	/// If this job is not `Running` or is not leased to `lease_owner`, throw an error.
+	async fn fail_job_run(
+		&self,
+		job_id: &QueuedJobId,
+		lease_owner: &str,
+		message: &str,
+		trace: &[NodeTrace],
+	) -> Result<(), FailJobError>;
+
+	/// Atomically mark the given job as `Queued` so that it is
+	/// attempted again after `next_attempt_at`, and save the trace
+	/// of the failed attempt.
+	/// If this job is not `Running` or is not leased to `lease_owner`, throw an error.
+	async fn retry_job(
+		&self,
+		job_id: &QueuedJobId,
+		lease_owner: &str,
+		message: &str,
+		trace: &[NodeTrace],
+		next_attempt_at: OffsetDateTime,
+	) -> Result<(), RetryJobError>;
+
+	/// Atomically mark the given job as `Success`,
+	/// save its execution trace, and commit `result`.
+	/// If this job is not `Running` or is not leased to `lease_owner`, throw an error.
+	///
+	/// `result` holds the changes this job made, and is committed only if
+	/// this job may still finish. This job can't be cancelled or reaped
+	/// between that check and the commit.
+	async fn success_job(
+		&self,
+		job_id: &QueuedJobId,
+		lease_owner: &str,
+		trace: &[NodeTrace],
+		result: Transaction<'_, Postgres>,
+	) -> Result<(), SuccessJobError>;
+
+	/// Atomically mark the given job as `Cancelled`.
+	/// If this job is not `Queued` or `Running`, throw an error.
+	///
+	/// Runners check for cancellation while a job runs,
+	/// so `Running` jobs are stopped as soon as possible.
+	async fn cancel_job(&self, job_id: &QueuedJobId) -> Result<(), CancelJobError>;
+
+	/// Get at most `count` finished jobs, ordered by the time they finished and then by id.
+	/// Only jobs that come after `finished_after` and `after_job` in this order are returned,
+	/// pass the last job of the previous page to get the next one.
+	///
+	/// Jobs that are waiting to be retried haven't finished.
+	async fn get_finished_jobs(
+		&self,
+		finished_after: OffsetDateTime,
+		after_job: Option<&QueuedJobId>,
+		count: i64,
+	) -> Result<Vec<FinishedJob>, GetFinishedJobsError>;
 
-	/// Atomically mark the given job as `Success`.
-	/// If this job is not `Running`, throw an error.
-	async fn success_job(&self, job_id: &QueuedJobId) -> Result<(), SuccessJobError>;
+	/// Claim at most `count` finished jobs that `retention` no longer keeps,
+	/// oldest first. Jobs that are waiting to be retried are never expired.
+	///
+	/// Claimed jobs are locked until the claim is deleted or dropped.
+	/// Jobs claimed by someone else are skipped, so concurrent callers
+	/// never get the same job.
+	async fn claim_expired_jobs(
+		&self,
+		retention: &JobRetention,
+		count: i64,
+	) -> Result<ExpiredJobs, GetExpiredJobsError>;
+
+	/// Delete the jobs in `claim`, and any batches they leave empty.
+	///
+	/// Returns the number of jobs we deleted.
+	async fn delete_expired_jobs(&self, claim: ExpiredJobs) -> Result<u64, DeleteJobsError>;
 }
@@ -1,5 +1,6 @@
 //! Errors we can encounter when operating on datasets
 
+use std::time::Duration;
 use thiserror::Error;
 
 /// An error we can encounter when creating a job
@@ -9,9 +10,39 @@ pub enum AddJobError {
 	#[error("database backend error")]
 	DbError(#[from] sqlx::Error),
 
-	/// A job with this id already exists
-	#[error("a job with this id already exists")]
+	/// A job (or batch) with this id already exists
+	#[error("a job or batch with this id already exists")]
 	AlreadyExists,
+
+	/// This user already has too many unfinished jobs
+	#[error("too many unfinished jobs, the limit is {limit}")]
+	TooManyJobs { limit: u32 },
+
+	/// This user is adding jobs too quickly
+	#[error("too many new jobs, the limit is {limit} per minute")]
+	RateLimited {
+		limit: u32,
+
+		/// How long the user should wait before adding another job
+		retry_after: Duration,
+	},
+
+	/// This batch has more jobs than `limit`,
+	/// and could never be added.
+	#[error("batch is too large, the limit is {limit}")]
+	BatchTooLarge { limit: u32 },
+}
+
+/// An error we may encounter when getting a job
+#[derive(Debug, Error)]
+pub enum GetJobError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+
+	/// A job with this id doesn't exist
+	#[error("a job with this id doesn't exist")]
+	NotFound,
 }
 
 /// An error we can encounter when getting a job by id
@@ -49,7 +80,7 @@ pub enum BuildErrorJobError {
 	#[error("database backend error")]
 	DbError(#[from] sqlx::Error),
 
-	/// This job is not running
+	/// This job is not running, or is leased by someone else
 	#[error("job is not running")]
 	NotRunning,
 }
@@ -61,7 +92,7 @@ pub enum FailJobError {
 	#[error("database backend error")]
 	DbError(#[from] sqlx::Error),
 
-	/// This job is not running
+	/// This job is not running, or is leased by someone else
 	#[error("job is not running")]
 	NotRunning,
 }
@@ -73,7 +104,120 @@ pub enum SuccessJobError {
 	#[error("database backend error")]
 	DbError(#[from] sqlx::Error),
 
-	/// This job is not running
+	/// This job is not running, or is leased by someone else
 	#[error("job is not running")]
 	NotRunning,
+
+	/// We could not commit this job's result.
+	/// The job's state was not changed.
+	#[error("could not commit job result")]
+	CommitFailed(sqlx::Error),
+}
+
+/// An error we can encounter when getting a job's trace
+#[derive(Debug, Error)]
+pub enum GetJobTraceError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+
+	/// A job with this id doesn't exist
+	#[error("a job with this id doesn't exist")]
+	NotFound,
+}
+
+/// An error we can encounter when cancelling a job
+#[derive(Debug, Error)]
+pub enum CancelJobError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+
+	/// A job with this id doesn't exist
+	#[error("a job with this id doesn't exist")]
+	NotFound,
+
+	/// This job has already finished
+	#[error("this job has already finished")]
+	AlreadyFinished,
+}
+
+/// An error we can encounter when queueing a failed job for another attempt
+#[derive(Debug, Error)]
+pub enum RetryJobError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+
+	/// This job is not running, or is leased by someone else
+	#[error("job is not running")]
+	NotRunning,
+}
+
+/// An error we can encounter when getting a job's attempt history
+#[derive(Debug, Error)]
+pub enum GetJobAttemptsError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+
+	/// A job with this id doesn't exist
+	#[error("a job with this id doesn't exist")]
+	NotFound,
+}
+
+/// An error we can encounter when extending a job's lease
+#[derive(Debug, Error)]
+pub enum HeartbeatJobError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+
+	/// This job is not running, or is leased by someone else
+	#[error("job is not running under this lease")]
+	LeaseLost,
+}
+
+/// An error we can encounter when reaping jobs with expired leases
+#[derive(Debug, Error)]
+pub enum ReapJobsError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when getting finished jobs
+#[derive(Debug, Error)]
+pub enum GetFinishedJobsError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when getting expired jobs
+#[derive(Debug, Error)]
+pub enum GetExpiredJobsError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we can encounter when deleting jobs
+#[derive(Debug, Error)]
+pub enum DeleteJobsError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+}
+
+/// An error we may encounter when getting a batch
+#[derive(Debug, Error)]
+pub enum GetBatchError {
+	/// Database error
+	#[error("database backend error")]
+	DbError(#[from] sqlx::Error),
+
+	/// We tried to get a batch that doesn't exist
+	#[error("tried to get a batch that doesn't exist")]
+	NotFound,
 }
@@ -0,0 +1,50 @@
+//! Filters and orderings for job lists
+
+use serde::Deserialize;
+use smartstring::{LazyCompact, SmartString};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use crate::info::QueuedJobStateKind;
+
+/// Decides which jobs we list.
+/// `None` and empty fields match every job.
+#[derive(Debug, Clone, Default)]
+pub struct JobFilter {
+	/// Only list jobs in one of these states
+	pub states: Vec<QueuedJobStateKind>,
+
+	/// Only list jobs that run this pipeline
+	pub pipeline_id: Option<i64>,
+
+	/// Only list jobs created at or after this time
+	pub created_after: Option<OffsetDateTime>,
+
+	/// Only list jobs created before this time
+	pub created_before: Option<OffsetDateTime>,
+
+	/// Only list jobs whose id starts with this string
+	pub id_prefix: Option<SmartString<LazyCompact>>,
+}
+
+/// The field we sort a job list by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+pub enum JobSortBy {
+	#[default]
+	Created,
+
+	/// Jobs that haven't started are listed last
+	Started,
+
+	/// Jobs that haven't finished are listed last
+	Finished,
+}
+
+/// The order we list jobs in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+pub enum SortDirection {
+	Ascending,
+
+	#[default]
+	Descending,
+}
@@ -23,3 +23,22 @@ impl From<&str> for QueuedJobId {
 		Self { id: value.into() }
 	}
 }
+
+/// The id of a batch of jobs
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BatchId {
+	id: SmartString<LazyCompact>,
+}
+
+impl BatchId {
+	pub fn as_str(&self) -> &str {
+		&self.id
+	}
+}
+
+impl From<&str> for BatchId {
+	fn from(value: &str) -> Self {
+		Self { id: value.into() }
+	}
+}
@@ -1,24 +1,131 @@
 //! Helper structs that contain database element properties
 
 use copper_itemdb::{AttrData, UserId};
-use copper_piper::json::PipelineJson;
+use copper_piper::{json::PipelineJson, retry::RetryPolicy, trace::NodeTrace};
 use serde::{Deserialize, Serialize};
 use smartstring::{LazyCompact, SmartString};
+use sqlx::{Postgres, Transaction};
 use std::collections::BTreeMap;
 use time::OffsetDateTime;
 use utoipa::ToSchema;
 
-use crate::id::QueuedJobId;
+use crate::{
+	id::{BatchId, QueuedJobId},
+	scheduling::JobPriority,
+};
 
 /// A queued job's state, as stored in the db
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 #[serde(tag = "state")]
 pub enum QueuedJobState {
-	BuildError { message: String },
+	BuildError {
+		message: String,
+	},
 	Queued,
 	Running,
-	FailedRunning { message: String },
+	FailedRunning {
+		message: String,
+	},
 	Success,
+
+	/// This job was cancelled by its owner
+	/// before it could finish.
+	Cancelled,
+}
+
+impl QueuedJobState {
+	/// Get the kind of this state
+	pub fn kind(&self) -> QueuedJobStateKind {
+		match self {
+			Self::BuildError { .. } => QueuedJobStateKind::BuildError,
+			Self::Queued => QueuedJobStateKind::Queued,
+			Self::Running => QueuedJobStateKind::Running,
+			Self::FailedRunning { .. } => QueuedJobStateKind::FailedRunning,
+			Self::Success => QueuedJobStateKind::Success,
+			Self::Cancelled => QueuedJobStateKind::Cancelled,
+		}
+	}
+	/// Returns true if this job will never run again
+	pub fn is_finished(&self) -> bool {
+		self.kind().is_finished()
+	}
+}
+
+/// A [`QueuedJobState`] without its data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+pub enum QueuedJobStateKind {
+	BuildError,
+	Queued,
+	Running,
+	FailedRunning,
+	Success,
+	Cancelled,
+}
+
+impl QueuedJobStateKind {
+	/// The name of this state.
+	/// This is the same as the `state` tag of a serialized [`QueuedJobState`].
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::BuildError => "BuildError",
+			Self::Queued => "Queued",
+			Self::Running => "Running",
+			Self::FailedRunning => "FailedRunning",
+			Self::Success => "Success",
+			Self::Cancelled => "Cancelled",
+		}
+	}
+
+	/// Returns true if jobs in this state will never run again
+	pub fn is_finished(&self) -> bool {
+		match self {
+			Self::BuildError => true,
+			Self::FailedRunning => true,
+			Self::Success => true,
+			Self::Cancelled => true,
+
+			Self::Queued => false,
+			Self::Running => false,
+		}
+	}
+
+	/// The inverse of [`Self::as_str`]
+	pub fn from_name(name: &str) -> Option<Self> {
+		Some(match name {
+			"BuildError" => Self::BuildError,
+			"Queued" => Self::Queued,
+			"Running" => Self::Running,
+			"FailedRunning" => Self::FailedRunning,
+			"Success" => Self::Success,
+			"Cancelled" => Self::Cancelled,
+			_ => return None,
+		})
+	}
+}
+
+/// A job we want to add to the queue
+#[derive(Debug, Clone)]
+pub struct NewJob<'a> {
+	/// A unique id for this job
+	pub job_id: QueuedJobId,
+
+	/// The id of the pipeline this job runs, in edged's database.
+	/// This is `None` if we don't know it, which may happen
+	/// when we re-run a job created before we tracked it.
+	pub pipeline_id: Option<i64>,
+
+	/// The pipeline this job runs
+	pub pipeline: &'a PipelineJson,
+
+	/// This job's input
+	pub input: &'a BTreeMap<SmartString<LazyCompact>, AttrData>,
+
+	/// How this job should be retried if it fails.
+	/// If this is `None`, this job is never retried.
+	pub retry_policy: Option<&'a RetryPolicy>,
+
+	/// This job's priority
+	pub priority: JobPriority,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -29,10 +136,18 @@ pub struct QueuedJobInfo {
 	/// The user that owns this job
 	pub owned_by: UserId,
 
+	/// The id of the pipeline this job runs, in edged's database.
+	/// This is `None` for jobs created before we tracked it.
+	pub pipeline_id: Option<i64>,
+
+	/// The batch this job is part of, if any
+	pub batch_id: Option<BatchId>,
+
 	/// The state of this job
 	pub state: QueuedJobState,
 
-	/// The pipeline to run
+	/// The pipeline to run.
+	/// This is a snapshot taken when this job was created.
 	pub pipeline: PipelineJson,
 
 	/// When this job was created
@@ -46,6 +161,78 @@ pub struct QueuedJobInfo {
 
 	/// The input to pass to this pipeline
 	pub input: BTreeMap<SmartString<LazyCompact>, AttrData>,
+
+	/// How this job should be retried if it fails
+	pub retry_policy: Option<RetryPolicy>,
+
+	/// The number of times this job has been started.
+	/// The first attempt is attempt 1.
+	pub attempt: u32,
+
+	/// If this job failed and is waiting to be retried,
+	/// it will not run before this time.
+	pub next_attempt_at: Option<OffsetDateTime>,
+
+	/// This job's priority
+	pub priority: JobPriority,
+}
+
+/// A change in a job's state
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct JobEvent {
+	/// The job that changed
+	#[schema(value_type = String)]
+	pub job_id: QueuedJobId,
+
+	/// The user that owns this job
+	#[schema(value_type = i64)]
+	pub owned_by: UserId,
+
+	/// The batch this job is part of, if any
+	#[schema(value_type = Option<String>)]
+	pub batch_id: Option<BatchId>,
+
+	/// This job's new state
+	pub state: QueuedJobStateKind,
+}
+
+/// A job that has finished.
+/// See [`crate::base::client::JobQueueClient::get_finished_jobs`].
+#[derive(Debug, Clone)]
+pub struct FinishedJob {
+	/// This job's final state
+	pub event: JobEvent,
+
+	/// When this job finished
+	pub finished_at: OffsetDateTime,
+}
+
+/// Everything we store about a job, including its trace and history.
+/// This is what we write to job archives.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedJob {
+	#[serde(flatten)]
+	pub info: QueuedJobInfo,
+
+	/// The trace of this job's last attempt
+	pub trace: Vec<NodeTrace>,
+
+	/// All finished attempts to run this job
+	pub attempts: Vec<JobAttempt>,
+}
+
+/// Expired jobs claimed by [`crate::base::client::JobQueueClient::claim_expired_jobs`].
+///
+/// These jobs stay locked until this claim is passed to
+/// [`crate::base::client::JobQueueClient::delete_expired_jobs`] or dropped,
+/// so no other cleanup task can archive or delete them meanwhile.
+/// Dropping a claim leaves its jobs untouched.
+pub struct ExpiredJobs {
+	/// The jobs we claimed, oldest first
+	pub jobs: Vec<ArchivedJob>,
+
+	/// The transaction that holds these jobs' row locks
+	pub(crate) t: Transaction<'static, Postgres>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -58,6 +245,10 @@ pub struct QueuedJobInfoShort {
 	#[schema(value_type = i64)]
 	pub owned_by: UserId,
 
+	/// The id of the pipeline this job runs, in edged's database.
+	/// This is `None` for jobs created before we tracked it.
+	pub pipeline_id: Option<i64>,
+
 	/// The state of this job
 	pub state: QueuedJobState,
 
@@ -69,6 +260,36 @@ pub struct QueuedJobInfoShort {
 
 	#[schema(value_type = Option<String>)]
 	pub finished_at: Option<OffsetDateTime>,
+
+	/// The number of times this job has been started
+	pub attempt: u32,
+
+	/// This job's priority
+	pub priority: JobPriority,
+
+	/// If this job failed and is waiting to be retried,
+	/// it will not run before this time.
+	#[schema(value_type = Option<String>)]
+	pub next_attempt_at: Option<OffsetDateTime>,
+}
+
+/// One finished attempt to run a job
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct JobAttempt {
+	/// Which attempt this was, starting at 1
+	pub attempt: u32,
+
+	/// When this attempt started
+	#[schema(value_type = String)]
+	pub started_at: OffsetDateTime,
+
+	/// When this attempt finished
+	#[schema(value_type = String)]
+	pub finished_at: OffsetDateTime,
+
+	/// The error this attempt failed with,
+	/// or `None` if it succeeded.
+	pub error: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -79,10 +300,30 @@ pub struct QueuedJobCounts {
 	pub successful_jobs: i64,
 	pub failed_jobs: i64,
 	pub build_errors: i64,
+	pub cancelled_jobs: i64,
+}
+
+/// A batch of jobs, and the progress of the jobs in it
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct BatchInfo {
+	/// This batch's id
+	#[schema(value_type = String)]
+	pub batch_id: BatchId,
+
+	/// The user that owns this batch
+	#[schema(value_type = i64)]
+	pub owned_by: UserId,
+
+	#[schema(value_type = String)]
+	pub created_at: OffsetDateTime,
+
+	/// The number of jobs in this batch, by state
+	pub counts: QueuedJobCounts,
 }
 
 #[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct QueuedJobInfoList {
+	/// The number of jobs that match this list's filter, by state
 	pub counts: QueuedJobCounts,
 
 	/// The number of jobs we skipped while paginating.
@@ -1,4 +1,8 @@
 pub mod base;
+pub mod filter;
 pub mod id;
 pub mod info;
+pub mod limits;
 pub mod postgres;
+pub mod retention;
+pub mod scheduling;
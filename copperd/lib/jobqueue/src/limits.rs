@@ -0,0 +1,23 @@
+//! Limits on the jobs of one user
+
+/// The largest batch anyone may add, no matter their [`JobLimits`].
+/// A batch is added in one transaction, so this keeps that transaction small.
+pub const MAX_BATCH_SIZE: u32 = 10_000;
+
+/// Limits on the jobs one user may add to the queue and run.
+/// `None` means "no limit".
+#[derive(Debug, Clone, Default)]
+pub struct JobLimits {
+	/// The maximum number of unfinished (`Queued` or `Running`)
+	/// jobs one user may have at once.
+	pub max_unfinished_jobs: Option<u32>,
+
+	/// The maximum number of jobs one user may add in any one-minute window
+	pub max_jobs_per_minute: Option<u32>,
+
+	/// The maximum number of jobs one user may run at once, across all runners.
+	/// Users at this limit are skipped until one of their jobs finishes.
+	///
+	/// Unlike the limits above, this is checked when a runner takes a job.
+	pub max_running_per_user: Option<u32>,
+}
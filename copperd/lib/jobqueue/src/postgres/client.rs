@@ -1,173 +1,381 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, time::Duration};
 
 use async_trait::async_trait;
 use copper_itemdb::{AttrData, UserId};
-use copper_piper::json::PipelineJson;
+use copper_piper::{json::PipelineJson, retry::RetryPolicy, trace::NodeTrace};
 use smartstring::{LazyCompact, SmartString};
 use sqlx::{
+	postgres::PgRow,
 	types::{time::OffsetDateTime, Json},
-	Connection, Row,
+	Connection, PgConnection, Postgres, QueryBuilder, Row, Transaction,
 };
 
-use super::PgJobQueueClient;
+use super::{
+	listen::{JOB_EVENT_CHANNEL, JOB_READY_CHANNEL},
+	PgJobQueueClient,
+};
 use crate::{
 	base::{
 		client::JobQueueClient,
 		errors::{
-			AddJobError, BuildErrorJobError, FailJobError, GetJobShortError, GetQueuedJobError,
-			GetUserJobsError, SuccessJobError,
+			AddJobError, BuildErrorJobError, CancelJobError, DeleteJobsError, FailJobError,
+			GetBatchError, GetExpiredJobsError, GetFinishedJobsError, GetJobAttemptsError,
+			GetJobError, GetJobShortError, GetJobTraceError, GetQueuedJobError, GetUserJobsError,
+			HeartbeatJobError, ReapJobsError, RetryJobError, SuccessJobError,
 		},
 	},
-	id::QueuedJobId,
-	info::{QueuedJobCounts, QueuedJobInfo, QueuedJobInfoList, QueuedJobInfoShort, QueuedJobState},
+	filter::{JobFilter, JobSortBy, SortDirection},
+	id::{BatchId, QueuedJobId},
+	info::{
+		ArchivedJob, BatchInfo, ExpiredJobs, FinishedJob, JobAttempt, JobEvent, NewJob,
+		QueuedJobCounts, QueuedJobInfo, QueuedJobInfoList, QueuedJobInfoShort, QueuedJobState,
+		QueuedJobStateKind,
+	},
+	limits::{JobLimits, MAX_BATCH_SIZE},
+	retention::JobRetention,
+	scheduling::{JobCandidate, JobPriority, SchedulingPolicy},
 };
 
-#[async_trait]
-impl JobQueueClient for PgJobQueueClient {
-	/// Queue a new job
-	async fn add_job(
-		&self,
-		job_id: QueuedJobId,
+/// Advisory lock namespaces.
+/// We use the two-key form of postgres' advisory locks,
+/// where the first key is one of these and the second identifies a user.
+mod lock {
+	/// Held while adding jobs for a user
+	pub(super) const ADD_JOBS: i32 = 1;
+
+	/// Held while a runner takes a job of a user
+	pub(super) const RUN_JOBS: i32 = 2;
+}
+
+impl PgJobQueueClient {
+	/// Take the advisory lock `namespace` for `user`.
+	/// This lock is released when `conn`'s transaction ends.
+	///
+	/// User ids are folded into 32 bits, so two users may share a lock.
+	/// This only makes one of them wait for the other.
+	async fn lock_user(
+		conn: &mut PgConnection,
+		namespace: i32,
+		user: UserId,
+	) -> Result<(), sqlx::Error> {
+		let id = i64::from(user);
+		let key = (id ^ (id >> 32)) as i32;
+
+		sqlx::query("SELECT pg_advisory_xact_lock($1, $2);")
+			.bind(namespace)
+			.bind(key)
+			.execute(&mut *conn)
+			.await?;
+
+		return Ok(());
+	}
+
+	/// Tell everyone listening for job events that a job changed state.
+	/// If `conn` is in a transaction, this is delivered when it commits.
+	async fn notify_event(
+		conn: &mut PgConnection,
+		job_id: &QueuedJobId,
 		owned_by: UserId,
-		pipeline: &PipelineJson,
-		input: &BTreeMap<SmartString<LazyCompact>, AttrData>,
-	) -> Result<QueuedJobId, AddJobError> {
-		// Start transaction
-		let mut conn = self.pool.acquire().await?;
-		let mut t = conn.begin().await?;
+		batch_id: Option<&BatchId>,
+		state: QueuedJobStateKind,
+	) -> Result<(), sqlx::Error> {
+		let event = JobEvent {
+			job_id: job_id.clone(),
+			owned_by,
+			batch_id: batch_id.cloned(),
+			state,
+		};
+
+		sqlx::query("SELECT pg_notify($1, $2);")
+			.bind(JOB_EVENT_CHANNEL)
+			.bind(serde_json::to_string(&event).unwrap())
+			.execute(&mut *conn)
+			.await?;
+
+		return Ok(());
+	}
+
+	/// End the current attempt of a running job.
+	///
+	/// This sets the job's state, saves its trace, releases its lease,
+	/// and adds this attempt to the job's attempt history. If `next_attempt_at`
+	/// is `Some`, the job isn't finished and will be attempted again.
+	///
+	/// `row` must be a locked row of a `Running` job, and must contain
+	/// its `id`, `attempt`, `started_at` and `attempt_history`.
+	async fn end_attempt(
+		conn: &mut PgConnection,
+		row: &PgRow,
+		state: &QueuedJobState,
+		error: Option<&str>,
+		trace: &[NodeTrace],
+		next_attempt_at: Option<OffsetDateTime>,
+	) -> Result<(), sqlx::Error> {
+		let now = OffsetDateTime::now_utc();
+		let mut history = row.get::<Json<Vec<JobAttempt>>, _>("attempt_history").0;
+		history.push(JobAttempt {
+			attempt: u32::try_from(row.get::<i32, _>("attempt")).unwrap(),
+			started_at: row.get("started_at"),
+			finished_at: now,
+			error: error.map(|x| x.into()),
+		});
 
 		let res = sqlx::query(
 			"
-			INSERT INTO jobs (id, created_at, owned_by, state, pipeline, input)
-			VALUES ($1, $2, $3, $4, $5, $6)
-			RETURNING id;
+			UPDATE jobs
+			SET state = $1, finished_at = $2, trace = $3,
+			attempt_history = $4, next_attempt_at = $5,
+			lease_owner = NULL, lease_expires_at = NULL
+			WHERE id = $6
+			RETURNING owned_by, batch_id;
 			",
 		)
-		.bind(job_id.as_str())
-		.bind(OffsetDateTime::now_utc())
-		.bind(i64::from(owned_by))
-		.bind(serde_json::to_string(&QueuedJobState::Queued).unwrap())
-		.bind(Json::from(pipeline))
-		.bind(Json::from(input))
-		.fetch_one(&mut *t)
-		.await;
+		.bind(serde_json::to_string(state).unwrap())
+		.bind(if next_attempt_at.is_some() {
+			None
+		} else {
+			Some(now)
+		})
+		.bind(Json::from(trace))
+		.bind(Json::from(&history))
+		.bind(next_attempt_at)
+		.bind(row.get::<&str, _>("id"))
+		.fetch_one(&mut *conn)
+		.await?;
 
-		t.commit().await?;
+		Self::notify_event(
+			conn,
+			&row.get::<&str, _>("id").into(),
+			res.get::<i64, _>("owned_by").into(),
+			res.get::<Option<&str>, _>("batch_id")
+				.map(BatchId::from)
+				.as_ref(),
+			state.kind(),
+		)
+		.await?;
 
-		let new_job_id: QueuedJobId = match res {
-			Ok(row) => row.get::<&str, _>("id").into(),
-			Err(sqlx::Error::Database(e)) => {
-				if e.is_unique_violation() {
-					return Err(AddJobError::AlreadyExists);
-				} else {
-					return Err(sqlx::Error::Database(e).into());
-				}
-			}
-			Err(e) => return Err(AddJobError::DbError(e)),
-		};
+		if next_attempt_at.is_some() {
+			// Delivered when this transaction commits
+			sqlx::query("SELECT pg_notify($1, $2);")
+				.bind(JOB_READY_CHANNEL)
+				.bind(row.get::<&str, _>("id"))
+				.execute(&mut *conn)
+				.await?;
+		}
 
-		return Ok(new_job_id);
+		return Ok(());
 	}
 
-	async fn get_job_short(
+	/// Lock a running job and end its current attempt.
+	/// See [`PgJobQueueClient::end_attempt`].
+	///
+	/// Returns `false` if this job isn't running or isn't leased to `lease_owner`.
+	async fn finish_attempt(
 		&self,
 		job_id: &QueuedJobId,
-	) -> Result<QueuedJobInfoShort, GetJobShortError> {
+		lease_owner: &str,
+		state: &QueuedJobState,
+		error: Option<&str>,
+		trace: &[NodeTrace],
+		next_attempt_at: Option<OffsetDateTime>,
+	) -> Result<bool, sqlx::Error> {
 		let mut conn = self.pool.acquire().await?;
+		let mut t = conn.begin().await?;
 
 		let res = sqlx::query(
 			"
-			SELECT id, owned_by, created_at, started_at, finished_at, state
+			SELECT id, attempt, started_at, attempt_history
 			FROM jobs
-			WHERE id=$1
+			WHERE id = $1
+			AND state = $2
+			AND lease_owner = $3
+			FOR UPDATE;
 			",
 		)
 		.bind(job_id.as_str())
-		.fetch_one(&mut *conn)
+		.bind(serde_json::to_string(&QueuedJobState::Running).unwrap())
+		.bind(lease_owner)
+		.fetch_one(&mut *t)
 		.await;
 
-		return match res {
-			Err(sqlx::Error::RowNotFound) => Err(GetJobShortError::NotFound),
-			Err(e) => Err(e.into()),
-			Ok(res) => Ok(QueuedJobInfoShort {
-				job_id: res.get::<&str, _>("id").into(),
-				owned_by: res.get::<i64, _>("owned_by").into(),
-				created_at: res.get("created_at"),
-				started_at: res.get("started_at"),
-				finished_at: res.get("finished_at"),
-				state: serde_json::from_str(res.get::<&str, _>("state")).unwrap(),
-			}),
+		let row = match res {
+			Err(sqlx::Error::RowNotFound) => return Ok(false),
+			Err(e) => return Err(e),
+			Ok(row) => row,
 		};
+
+		Self::end_attempt(&mut t, &row, state, error, trace, next_attempt_at).await?;
+		t.commit().await?;
+
+		return Ok(true);
 	}
 
-	async fn get_user_jobs(
-		&self,
+	/// Make sure `owned_by` may add `n_jobs` new jobs without exceeding `limits`.
+	///
+	/// `conn` should be in the transaction that adds these jobs.
+	/// If any limit is set, this locks `owned_by`'s jobs until that
+	/// transaction ends, so that concurrent calls can't exceed them.
+	///
+	/// `limits.max_running_per_user` is not checked here.
+	async fn check_limits(
+		conn: &mut PgConnection,
 		owned_by: UserId,
-		skip: i64,
-		count: i64,
-	) -> Result<QueuedJobInfoList, GetUserJobsError> {
-		let mut conn = self.pool.acquire().await?;
+		n_jobs: u32,
+		limits: &JobLimits,
+		now: OffsetDateTime,
+	) -> Result<(), AddJobError> {
+		if limits.max_unfinished_jobs.is_none() && limits.max_jobs_per_minute.is_none() {
+			return Ok(());
+		}
 
-		let res = sqlx::query(
+		// Only one transaction may add jobs for a user at once.
+		// This lock is released when the transaction ends.
+		Self::lock_user(conn, lock::ADD_JOBS, owned_by).await?;
+
+		if let Some(limit) = limits.max_unfinished_jobs {
+			if n_jobs > limit {
+				return Err(AddJobError::BatchTooLarge { limit });
+			}
+
+			let res = sqlx::query(
+				"
+				SELECT COUNT(*)
+				FROM jobs
+				WHERE owned_by = $1
+				AND (state = $2 OR state = $3);
+				",
+			)
+			.bind(i64::from(owned_by))
+			.bind(serde_json::to_string(&QueuedJobState::Queued).unwrap())
+			.bind(serde_json::to_string(&QueuedJobState::Running).unwrap())
+			.fetch_one(&mut *conn)
+			.await?;
+
+			if res.get::<i64, _>("count") + i64::from(n_jobs) > i64::from(limit) {
+				return Err(AddJobError::TooManyJobs { limit });
+			}
+		}
+
+		if let Some(limit) = limits.max_jobs_per_minute {
+			if n_jobs > limit {
+				return Err(AddJobError::BatchTooLarge { limit });
+			}
+
+			let res = sqlx::query(
+				"
+				SELECT COUNT(*)
+				FROM jobs
+				WHERE owned_by = $1
+				AND created_at > $2;
+				",
+			)
+			.bind(i64::from(owned_by))
+			.bind(now - time::Duration::MINUTE)
+			.fetch_one(&mut *conn)
+			.await?;
+
+			let excess = res.get::<i64, _>("count") + i64::from(n_jobs) - i64::from(limit);
+			if excess > 0 {
+				// The user may add these jobs once enough
+				// jobs leave this window. Since `n_jobs <= limit`,
+				// there are always at least `excess` jobs in it.
+				let res = sqlx::query(
+					"
+					SELECT created_at
+					FROM jobs
+					WHERE owned_by = $1
+					AND created_at > $2
+					ORDER BY created_at ASC
+					OFFSET $3
+					LIMIT 1;
+					",
+				)
+				.bind(i64::from(owned_by))
+				.bind(now - time::Duration::MINUTE)
+				.bind(excess - 1)
+				.fetch_one(&mut *conn)
+				.await?;
+
+				let created_at: OffsetDateTime = res.get("created_at");
+				let retry_after = created_at + time::Duration::MINUTE - now;
+				return Err(AddJobError::RateLimited {
+					limit,
+					retry_after: retry_after.try_into().unwrap_or(Duration::ZERO),
+				});
+			}
+		}
+
+		return Ok(());
+	}
+
+	/// Insert a new queued job
+	async fn insert_job(
+		conn: &mut PgConnection,
+		owned_by: UserId,
+		job: &NewJob<'_>,
+		batch_id: Option<&BatchId>,
+		now: OffsetDateTime,
+	) -> Result<(), sqlx::Error> {
+		sqlx::query(
 			"
-			SELECT id, owned_by, created_at, started_at, finished_at, state
-			FROM jobs
-			WHERE owned_by=$1
-			ORDER BY started_at DESC
-			OFFSET $2
-			LIMIT $3
+			INSERT INTO jobs (
+				id, created_at, owned_by, state,
+				pipeline, input, retry_policy, priority, batch_id,
+				pipeline_id
+			)
+			VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10);
 			",
 		)
+		.bind(job.job_id.as_str())
+		.bind(now)
 		.bind(i64::from(owned_by))
-		.bind(skip)
-		.bind(count)
-		.fetch_all(&mut *conn)
+		.bind(serde_json::to_string(&QueuedJobState::Queued).unwrap())
+		.bind(Json::from(job.pipeline))
+		.bind(Json::from(job.input))
+		.bind(job.retry_policy.map(Json::from))
+		.bind(job.priority.as_i16())
+		.bind(batch_id.map(|x| x.as_str()))
+		.bind(job.pipeline_id)
+		.execute(&mut *conn)
 		.await?;
 
-		let mut out = Vec::new();
-		for row in res {
-			out.push(QueuedJobInfoShort {
-				job_id: row.get::<&str, _>("id").into(),
-				owned_by: row.get::<i64, _>("owned_by").into(),
-				created_at: row.get("created_at"),
-				started_at: row.get("started_at"),
-				finished_at: row.get("finished_at"),
-				state: serde_json::from_str(row.get::<&str, _>("state")).unwrap(),
-			})
-		}
+		Self::notify_event(
+			conn,
+			&job.job_id,
+			owned_by,
+			batch_id,
+			QueuedJobStateKind::Queued,
+		)
+		.await?;
+
+		return Ok(());
+	}
 
+	/// Count jobs by state.
+	/// `rows` must contain `state_kind` and `count` columns.
+	fn count_states(rows: &[PgRow]) -> QueuedJobCounts {
 		let mut counts = QueuedJobCounts {
 			queued_jobs: 0,
 			running_jobs: 0,
 			successful_jobs: 0,
 			failed_jobs: 0,
 			build_errors: 0,
+			cancelled_jobs: 0,
 
 			total_jobs: 0,
 		};
 
-		let res = sqlx::query(
-			"
-			SELECT state, COUNT(*)
-			FROM jobs
-			WHERE owned_by=$1
-			GROUP BY state;
-			",
-		)
-		.bind(i64::from(owned_by))
-		.fetch_all(&mut *conn)
-		.await?;
-
-		for row in res {
-			let state: QueuedJobState = serde_json::from_str(row.get::<&str, _>("state")).unwrap();
+		for row in rows {
+			let state = QueuedJobStateKind::from_name(row.get("state_kind")).unwrap();
 			let n: i64 = row.get("count");
 
 			match state {
-				QueuedJobState::Queued => counts.queued_jobs += n,
-				QueuedJobState::Running => counts.running_jobs += n,
-				QueuedJobState::Success { .. } => counts.successful_jobs += n,
-				QueuedJobState::FailedRunning { .. } => counts.failed_jobs += n,
-				QueuedJobState::BuildError { .. } => counts.build_errors += n,
+				QueuedJobStateKind::Queued => counts.queued_jobs += n,
+				QueuedJobStateKind::Running => counts.running_jobs += n,
+				QueuedJobStateKind::Success => counts.successful_jobs += n,
+				QueuedJobStateKind::FailedRunning => counts.failed_jobs += n,
+				QueuedJobStateKind::BuildError => counts.build_errors += n,
+				QueuedJobStateKind::Cancelled => counts.cancelled_jobs += n,
 			}
 		}
 
@@ -175,7 +383,356 @@ impl JobQueueClient for PgJobQueueClient {
 			+ counts.running_jobs
 			+ counts.successful_jobs
 			+ counts.failed_jobs
-			+ counts.build_errors;
+			+ counts.build_errors
+			+ counts.cancelled_jobs;
+
+		return counts;
+	}
+
+	/// Read a [`QueuedJobInfoShort`] from a row.
+	/// `row` must contain all the columns in [`Self::JOB_SHORT_COLUMNS`].
+	fn job_short_from_row(row: &PgRow) -> QueuedJobInfoShort {
+		QueuedJobInfoShort {
+			job_id: row.get::<&str, _>("id").into(),
+			owned_by: row.get::<i64, _>("owned_by").into(),
+			pipeline_id: row.get("pipeline_id"),
+			created_at: row.get("created_at"),
+			started_at: row.get("started_at"),
+			finished_at: row.get("finished_at"),
+			state: serde_json::from_str(row.get::<&str, _>("state")).unwrap(),
+			attempt: u32::try_from(row.get::<i32, _>("attempt")).unwrap(),
+			next_attempt_at: row.get("next_attempt_at"),
+			priority: JobPriority::from_i16(row.get("priority")),
+		}
+	}
+
+	/// Read a [`QueuedJobInfo`] from a row that contains all columns of `jobs`
+	fn job_from_row(row: &PgRow) -> QueuedJobInfo {
+		QueuedJobInfo {
+			job_id: row.get::<&str, _>("id").into(),
+			owned_by: row.get::<i64, _>("owned_by").into(),
+			pipeline_id: row.get("pipeline_id"),
+			batch_id: row.get::<Option<&str>, _>("batch_id").map(|x| x.into()),
+			created_at: row.get("created_at"),
+			started_at: row.get("started_at"),
+			finished_at: row.get("finished_at"),
+			state: serde_json::from_str(row.get::<&str, _>("state")).unwrap(),
+			pipeline: row.get::<Json<PipelineJson>, _>("pipeline").0,
+			input: row
+				.get::<Json<BTreeMap<SmartString<LazyCompact>, AttrData>>, _>("input")
+				.0,
+			retry_policy: row
+				.get::<Option<Json<RetryPolicy>>, _>("retry_policy")
+				.map(|x| x.0),
+			attempt: u32::try_from(row.get::<i32, _>("attempt")).unwrap(),
+			next_attempt_at: row.get("next_attempt_at"),
+			priority: JobPriority::from_i16(row.get("priority")),
+		}
+	}
+
+	/// The columns we need to make a [`QueuedJobInfoShort`]
+	const JOB_SHORT_COLUMNS: &'static str = "
+		id, owned_by, pipeline_id, created_at, started_at, finished_at,
+		state, attempt, next_attempt_at, priority
+	";
+
+	/// Add a `WHERE` clause that selects the jobs `owned_by` has that match `filter`
+	fn push_job_filter(q: &mut QueryBuilder<'_, Postgres>, owned_by: UserId, filter: &JobFilter) {
+		q.push(" WHERE owned_by = ");
+		q.push_bind(i64::from(owned_by));
+
+		if !filter.states.is_empty() {
+			q.push(" AND state_kind = ANY(");
+			q.push_bind(
+				filter
+					.states
+					.iter()
+					.map(|x| x.as_str().to_owned())
+					.collect::<Vec<_>>(),
+			);
+			q.push(")");
+		}
+
+		if let Some(pipeline_id) = filter.pipeline_id {
+			q.push(" AND pipeline_id = ");
+			q.push_bind(pipeline_id);
+		}
+
+		if let Some(created_after) = filter.created_after {
+			q.push(" AND created_at >= ");
+			q.push_bind(created_after);
+		}
+
+		if let Some(created_before) = filter.created_before {
+			q.push(" AND created_at < ");
+			q.push_bind(created_before);
+		}
+
+		if let Some(prefix) = &filter.id_prefix {
+			// Escape LIKE's wildcards (and its escape character),
+			// so that they match literally.
+			let mut pattern = String::with_capacity(prefix.len() + 1);
+			for c in prefix.chars() {
+				if matches!(c, '\\' | '%' | '_') {
+					pattern.push('\\');
+				}
+				pattern.push(c);
+			}
+			pattern.push('%');
+
+			q.push(" AND id LIKE ");
+			q.push_bind(pattern);
+		}
+	}
+}
+
+#[async_trait]
+impl JobQueueClient for PgJobQueueClient {
+	/// Queue a new job
+	async fn add_job(
+		&self,
+		owned_by: UserId,
+		job: NewJob<'_>,
+		limits: &JobLimits,
+	) -> Result<QueuedJobId, AddJobError> {
+		// Start transaction
+		let mut conn = self.pool.acquire().await?;
+		let mut t = conn.begin().await?;
+		let now = OffsetDateTime::now_utc();
+
+		Self::check_limits(&mut t, owned_by, 1, limits, now).await?;
+		let res = Self::insert_job(&mut t, owned_by, &job, None, now).await;
+
+		if res.is_ok() {
+			// Wake up runners waiting for jobs.
+			// This is delivered when we commit.
+			sqlx::query("SELECT pg_notify($1, $2);")
+				.bind(JOB_READY_CHANNEL)
+				.bind(job.job_id.as_str())
+				.execute(&mut *t)
+				.await?;
+		}
+
+		t.commit().await?;
+
+		return match res {
+			Ok(()) => Ok(job.job_id),
+			Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+				Err(AddJobError::AlreadyExists)
+			}
+			Err(e) => Err(AddJobError::DbError(e)),
+		};
+	}
+
+	async fn add_jobs(
+		&self,
+		owned_by: UserId,
+		batch_id: &BatchId,
+		jobs: &[NewJob<'_>],
+		limits: &JobLimits,
+	) -> Result<(), AddJobError> {
+		// Start transaction
+		let mut conn = self.pool.acquire().await?;
+		let mut t = conn.begin().await?;
+		let now = OffsetDateTime::now_utc();
+
+		let n_jobs = u32::try_from(jobs.len()).unwrap_or(u32::MAX);
+		if n_jobs > MAX_BATCH_SIZE {
+			return Err(AddJobError::BatchTooLarge {
+				limit: MAX_BATCH_SIZE,
+			});
+		}
+
+		Self::check_limits(&mut t, owned_by, n_jobs, limits, now).await?;
+
+		// If anything below fails, we return early and drop `t`,
+		// which rolls back the whole batch.
+		let res =
+			sqlx::query("INSERT INTO batches (id, created_at, owned_by) VALUES ($1, $2, $3);")
+				.bind(batch_id.as_str())
+				.bind(now)
+				.bind(i64::from(owned_by))
+				.execute(&mut *t)
+				.await;
+
+		match res {
+			Ok(_) => {}
+			Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+				return Err(AddJobError::AlreadyExists)
+			}
+			Err(e) => return Err(e.into()),
+		}
+
+		for job in jobs {
+			match Self::insert_job(&mut t, owned_by, job, Some(batch_id), now).await {
+				Ok(()) => {}
+				Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+					return Err(AddJobError::AlreadyExists)
+				}
+				Err(e) => return Err(e.into()),
+			}
+		}
+
+		// One notification wakes all waiting runners,
+		// so we don't need to send one per job.
+		sqlx::query("SELECT pg_notify($1, $2);")
+			.bind(JOB_READY_CHANNEL)
+			.bind(batch_id.as_str())
+			.execute(&mut *t)
+			.await?;
+
+		t.commit().await?;
+
+		return Ok(());
+	}
+
+	async fn get_batch(&self, batch_id: &BatchId) -> Result<BatchInfo, GetBatchError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let res = sqlx::query("SELECT id, owned_by, created_at FROM batches WHERE id=$1;")
+			.bind(batch_id.as_str())
+			.fetch_one(&mut *conn)
+			.await;
+
+		let batch = match res {
+			Err(sqlx::Error::RowNotFound) => return Err(GetBatchError::NotFound),
+			Err(e) => return Err(e.into()),
+			Ok(row) => row,
+		};
+
+		let res = sqlx::query(
+			"
+			SELECT state_kind, COUNT(*)
+			FROM jobs
+			WHERE batch_id=$1
+			GROUP BY state_kind;
+			",
+		)
+		.bind(batch_id.as_str())
+		.fetch_all(&mut *conn)
+		.await?;
+
+		return Ok(BatchInfo {
+			batch_id: batch.get::<&str, _>("id").into(),
+			owned_by: batch.get::<i64, _>("owned_by").into(),
+			created_at: batch.get("created_at"),
+			counts: Self::count_states(&res),
+		});
+	}
+
+	async fn get_job_short(
+		&self,
+		job_id: &QueuedJobId,
+	) -> Result<QueuedJobInfoShort, GetJobShortError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let query = format!("SELECT {} FROM jobs WHERE id=$1;", Self::JOB_SHORT_COLUMNS);
+		let res = sqlx::query(&query)
+			.bind(job_id.as_str())
+			.fetch_one(&mut *conn)
+			.await;
+
+		return match res {
+			Err(sqlx::Error::RowNotFound) => Err(GetJobShortError::NotFound),
+			Err(e) => Err(e.into()),
+			Ok(res) => Ok(Self::job_short_from_row(&res)),
+		};
+	}
+
+	async fn get_job(&self, job_id: &QueuedJobId) -> Result<QueuedJobInfo, GetJobError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let res = sqlx::query("SELECT * FROM jobs WHERE id=$1;")
+			.bind(job_id.as_str())
+			.fetch_one(&mut *conn)
+			.await;
+
+		return match res {
+			Err(sqlx::Error::RowNotFound) => Err(GetJobError::NotFound),
+			Err(e) => Err(e.into()),
+			Ok(res) => Ok(Self::job_from_row(&res)),
+		};
+	}
+
+	async fn get_job_trace(
+		&self,
+		job_id: &QueuedJobId,
+	) -> Result<Vec<NodeTrace>, GetJobTraceError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let res = sqlx::query("SELECT trace FROM jobs WHERE id=$1")
+			.bind(job_id.as_str())
+			.fetch_one(&mut *conn)
+			.await;
+
+		return match res {
+			Err(sqlx::Error::RowNotFound) => Err(GetJobTraceError::NotFound),
+			Err(e) => Err(e.into()),
+			Ok(res) => Ok(res.get::<Json<Vec<NodeTrace>>, _>("trace").0),
+		};
+	}
+
+	async fn get_job_attempts(
+		&self,
+		job_id: &QueuedJobId,
+	) -> Result<Vec<JobAttempt>, GetJobAttemptsError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let res = sqlx::query("SELECT attempt_history FROM jobs WHERE id=$1")
+			.bind(job_id.as_str())
+			.fetch_one(&mut *conn)
+			.await;
+
+		return match res {
+			Err(sqlx::Error::RowNotFound) => Err(GetJobAttemptsError::NotFound),
+			Err(e) => Err(e.into()),
+			Ok(res) => Ok(res.get::<Json<Vec<JobAttempt>>, _>("attempt_history").0),
+		};
+	}
+
+	async fn get_user_jobs(
+		&self,
+		owned_by: UserId,
+		filter: &JobFilter,
+		sort_by: JobSortBy,
+		direction: SortDirection,
+		skip: i64,
+		count: i64,
+	) -> Result<QueuedJobInfoList, GetUserJobsError> {
+		let mut conn = self.pool.acquire().await?;
+
+		let sort_column = match sort_by {
+			JobSortBy::Created => "created_at",
+			JobSortBy::Started => "started_at",
+			JobSortBy::Finished => "finished_at",
+		};
+
+		let direction = match direction {
+			SortDirection::Ascending => "ASC",
+			SortDirection::Descending => "DESC",
+		};
+
+		let mut q = QueryBuilder::new(format!("SELECT {} FROM jobs", Self::JOB_SHORT_COLUMNS));
+		Self::push_job_filter(&mut q, owned_by, filter);
+		// Sort by id too, so that pages are stable
+		// when many jobs have the same timestamp.
+		q.push(format!(
+			" ORDER BY {sort_column} {direction} NULLS LAST, id {direction}"
+		));
+		q.push(" OFFSET ");
+		q.push_bind(skip);
+		q.push(" LIMIT ");
+		q.push_bind(count);
+
+		let res = q.build().fetch_all(&mut *conn).await?;
+		let out = res.iter().map(Self::job_short_from_row).collect();
+
+		let mut q = QueryBuilder::new("SELECT state_kind, COUNT(*) FROM jobs");
+		Self::push_job_filter(&mut q, owned_by, filter);
+		q.push(" GROUP BY state_kind");
+
+		let res = q.build().fetch_all(&mut *conn).await?;
+		let counts = Self::count_states(&res);
 
 		return Ok(QueuedJobInfoList {
 			skip,
@@ -184,138 +741,660 @@ impl JobQueueClient for PgJobQueueClient {
 		});
 	}
 
-	async fn get_queued_job(&self) -> Result<Option<QueuedJobInfo>, GetQueuedJobError> {
+	async fn get_queued_job(
+		&self,
+		lease_owner: &str,
+		lease_duration: Duration,
+		policy: &SchedulingPolicy,
+		limits: &JobLimits,
+	) -> Result<Option<QueuedJobInfo>, GetQueuedJobError> {
 		let mut conn = self.pool.acquire().await?;
-		let res = sqlx::query(
-			"
-			UPDATE jobs
-			SET state = $1, started_at = $2
-			WHERE id = (
-				SELECT id
+
+		// We pick a job first, and then try to take it.
+		// Another runner may take the job we picked before we do,
+		// if that happens, we pick again.
+		loop {
+			let now = OffsetDateTime::now_utc();
+
+			let res = if policy.fair || limits.max_running_per_user.is_some() {
+				// The best job of each user
+				sqlx::query(
+					"
+					SELECT DISTINCT ON (owned_by) id, owned_by, priority, created_at
+					FROM jobs
+					WHERE state = $1
+					AND (next_attempt_at IS NULL OR next_attempt_at <= $2)
+					ORDER BY owned_by, priority DESC, created_at ASC;
+					",
+				)
+			} else {
+				// The best job overall
+				sqlx::query(
+					"
+					SELECT id, owned_by, priority, created_at
+					FROM jobs
+					WHERE state = $1
+					AND (next_attempt_at IS NULL OR next_attempt_at <= $2)
+					ORDER BY priority DESC, created_at ASC
+					LIMIT 1;
+					",
+				)
+			}
+			.bind(serde_json::to_string(&QueuedJobState::Queued).unwrap())
+			.bind(now)
+			.fetch_all(&mut *conn)
+			.await?;
+
+			let candidates: Vec<JobCandidate> = res
+				.into_iter()
+				.map(|row| JobCandidate {
+					job_id: row.get::<&str, _>("id").into(),
+					owned_by: row.get::<i64, _>("owned_by").into(),
+					priority: JobPriority::from_i16(row.get("priority")),
+					created_at: row.get("created_at"),
+				})
+				.collect();
+
+			if candidates.is_empty() {
+				return Ok(None);
+			}
+
+			let res = sqlx::query(
+				"
+				SELECT owned_by, COUNT(*)
 				FROM jobs
-				WHERE state = $3
-				ORDER BY created_at ASC
-				FOR UPDATE SKIP LOCKED
-				LIMIT 1
+				WHERE state = $1
+				GROUP BY owned_by;
+				",
 			)
-			RETURNING *;
-			",
-		)
-		.bind(serde_json::to_string(&QueuedJobState::Running).unwrap())
-		.bind(OffsetDateTime::now_utc())
-		.bind(serde_json::to_string(&QueuedJobState::Queued).unwrap())
-		.fetch_one(&mut *conn)
-		.await;
+			.bind(serde_json::to_string(&QueuedJobState::Running).unwrap())
+			.fetch_all(&mut *conn)
+			.await?;
 
-		return match res {
-			Err(sqlx::Error::RowNotFound) => Ok(None),
-			Err(e) => Err(e.into()),
-			Ok(res) => Ok(Some(QueuedJobInfo {
-				job_id: res.get::<&str, _>("id").into(),
-				owned_by: res.get::<i64, _>("owned_by").into(),
-				created_at: res.get("created_at"),
-				started_at: res.get("started_at"),
-				finished_at: res.get("finished_at"),
-				state: serde_json::from_str(res.get::<&str, _>("state")).unwrap(),
-				pipeline: res.get::<sqlx::types::Json<PipelineJson>, _>("pipeline").0,
-				input: res
-					.get::<sqlx::types::Json<BTreeMap<SmartString<LazyCompact>, AttrData>>, _>(
-						"input",
+			let running: BTreeMap<UserId, u32> = res
+				.into_iter()
+				.map(|row| {
+					(
+						row.get::<i64, _>("owned_by").into(),
+						u32::try_from(row.get::<i64, _>("count")).unwrap(),
 					)
-					.0,
-			})),
-		};
+				})
+				.collect();
+
+			// These counts aren't locked, so they may be outdated.
+			// That's fine for picking a job, but we check
+			// `max_running_per_user` again below.
+			let picked = match policy.pick(&candidates, &running, limits.max_running_per_user) {
+				Some(x) => x,
+				None => return Ok(None),
+			};
+
+			let mut t = conn.begin().await?;
+
+			if let Some(limit) = limits.max_running_per_user {
+				// Only one runner may take a job of this user at once.
+				// Jobs that finish only lower this count, so it
+				// stays correct until we commit.
+				Self::lock_user(&mut t, lock::RUN_JOBS, picked.owned_by).await?;
+
+				let res = sqlx::query(
+					"
+					SELECT COUNT(*)
+					FROM jobs
+					WHERE owned_by = $1
+					AND state = $2;
+					",
+				)
+				.bind(i64::from(picked.owned_by))
+				.bind(serde_json::to_string(&QueuedJobState::Running).unwrap())
+				.fetch_one(&mut *t)
+				.await?;
+
+				// Someone else took a job of this user, try again
+				if res.get::<i64, _>("count") >= i64::from(limit) {
+					continue;
+				}
+			}
+
+			let res = sqlx::query(
+				"
+				UPDATE jobs
+				SET state = $1, started_at = $2, attempt = attempt + 1, next_attempt_at = NULL,
+				lease_owner = $4, lease_expires_at = $5
+				WHERE id = $6
+				AND state = $3
+				RETURNING *;
+				",
+			)
+			.bind(serde_json::to_string(&QueuedJobState::Running).unwrap())
+			.bind(now)
+			.bind(serde_json::to_string(&QueuedJobState::Queued).unwrap())
+			.bind(lease_owner)
+			.bind(now + lease_duration)
+			.bind(picked.job_id.as_str())
+			.fetch_one(&mut *t)
+			.await;
+
+			let job = match res {
+				// Someone else took this job, try again
+				Err(sqlx::Error::RowNotFound) => continue,
+				Err(e) => return Err(e.into()),
+				Ok(res) => Self::job_from_row(&res),
+			};
+
+			Self::notify_event(
+				&mut t,
+				&job.job_id,
+				job.owned_by,
+				job.batch_id.as_ref(),
+				QueuedJobStateKind::Running,
+			)
+			.await?;
+
+			t.commit().await?;
+
+			return Ok(Some(job));
+		}
 	}
 
 	async fn builderror_job(
 		&self,
 		job_id: &QueuedJobId,
+		lease_owner: &str,
 		error_message: &str,
 	) -> Result<(), BuildErrorJobError> {
+		let state = QueuedJobState::BuildError {
+			message: error_message.into(),
+		};
+
+		return match self
+			.finish_attempt(job_id, lease_owner, &state, Some(error_message), &[], None)
+			.await?
+		{
+			true => Ok(()),
+			false => Err(BuildErrorJobError::NotRunning),
+		};
+	}
+
+	async fn fail_job_run(
+		&self,
+		job_id: &QueuedJobId,
+		lease_owner: &str,
+		message: &str,
+		trace: &[NodeTrace],
+	) -> Result<(), FailJobError> {
+		let state = QueuedJobState::FailedRunning {
+			message: message.into(),
+		};
+
+		return match self
+			.finish_attempt(job_id, lease_owner, &state, Some(message), trace, None)
+			.await?
+		{
+			true => Ok(()),
+			false => Err(FailJobError::NotRunning),
+		};
+	}
+
+	async fn retry_job(
+		&self,
+		job_id: &QueuedJobId,
+		lease_owner: &str,
+		message: &str,
+		trace: &[NodeTrace],
+		next_attempt_at: OffsetDateTime,
+	) -> Result<(), RetryJobError> {
+		return match self
+			.finish_attempt(
+				job_id,
+				lease_owner,
+				&QueuedJobState::Queued,
+				Some(message),
+				trace,
+				Some(next_attempt_at),
+			)
+			.await?
+		{
+			true => Ok(()),
+			false => Err(RetryJobError::NotRunning),
+		};
+	}
+
+	async fn success_job(
+		&self,
+		job_id: &QueuedJobId,
+		lease_owner: &str,
+		trace: &[NodeTrace],
+		result: Transaction<'_, Postgres>,
+	) -> Result<(), SuccessJobError> {
 		let mut conn = self.pool.acquire().await?;
-		// RETURNING id is required, RowNotFound is always thrown if it is removed.
+		let mut t = conn.begin().await?;
+
+		// While we hold this lock, this job can't be cancelled or reaped.
 		let res = sqlx::query(
 			"
-			UPDATE jobs
-			SET state = $1, finished_at = $2
-			WHERE id = $3
-			AND state = $4
-			RETURNING id;
+			SELECT id, attempt, started_at, attempt_history
+			FROM jobs
+			WHERE id = $1
+			AND state = $2
+			AND lease_owner = $3
+			FOR UPDATE;
 			",
 		)
-		.bind(
-			serde_json::to_string(&QueuedJobState::BuildError {
-				message: error_message.into(),
-			})
-			.unwrap(),
-		)
-		.bind(OffsetDateTime::now_utc())
 		.bind(job_id.as_str())
 		.bind(serde_json::to_string(&QueuedJobState::Running).unwrap())
-		.fetch_one(&mut *conn)
+		.bind(lease_owner)
+		.fetch_one(&mut *t)
 		.await;
 
-		return match res {
-			Err(sqlx::Error::RowNotFound) => Err(BuildErrorJobError::NotRunning),
-			Err(e) => Err(e.into()),
-			Ok(_) => Ok(()),
+		let row = match res {
+			Err(sqlx::Error::RowNotFound) => return Err(SuccessJobError::NotRunning),
+			Err(e) => return Err(e.into()),
+			Ok(row) => row,
 		};
+
+		Self::end_attempt(&mut t, &row, &QueuedJobState::Success, None, trace, None).await?;
+
+		// If this fails, `t` is rolled back and the job stays `Running`.
+		result
+			.commit()
+			.await
+			.map_err(SuccessJobError::CommitFailed)?;
+
+		// The item db and the job queue can't share a transaction.
+		// If this fails, the job's changes are saved but it stays
+		// `Running` until it is reaped.
+		t.commit().await?;
+
+		return Ok(());
 	}
 
-	async fn fail_job_run(&self, job_id: &QueuedJobId, message: &str) -> Result<(), FailJobError> {
+	async fn cancel_job(&self, job_id: &QueuedJobId) -> Result<(), CancelJobError> {
 		let mut conn = self.pool.acquire().await?;
-		// RETURNING id is required, RowNotFound is always thrown if it is removed.
+		let mut t = conn.begin().await?;
+
+		let res = sqlx::query(
+			"
+			SELECT id, state, attempt, started_at, attempt_history
+			FROM jobs
+			WHERE id = $1
+			FOR UPDATE;
+			",
+		)
+		.bind(job_id.as_str())
+		.fetch_one(&mut *t)
+		.await;
+
+		let row = match res {
+			Err(sqlx::Error::RowNotFound) => return Err(CancelJobError::NotFound),
+			Err(e) => return Err(e.into()),
+			Ok(row) => row,
+		};
+
+		let state: QueuedJobState = serde_json::from_str(row.get::<&str, _>("state")).unwrap();
+		match state {
+			QueuedJobState::Queued => {}
+
+			// Running jobs have an attempt (and a lease) that we need to end
+			QueuedJobState::Running => {
+				Self::end_attempt(
+					&mut t,
+					&row,
+					&QueuedJobState::Cancelled,
+					Some("cancelled"),
+					&[],
+					None,
+				)
+				.await?;
+
+				t.commit().await?;
+				return Ok(());
+			}
+
+			_ => return Err(CancelJobError::AlreadyFinished),
+		}
+
 		let res = sqlx::query(
 			"
 			UPDATE jobs
 			SET state = $1, finished_at = $2
 			WHERE id = $3
-			AND state = $4
-			RETURNING id;
+			RETURNING owned_by, batch_id;
 			",
 		)
-		.bind(
-			serde_json::to_string(&QueuedJobState::FailedRunning {
-				message: message.into(),
-			})
-			.unwrap(),
-		)
+		.bind(serde_json::to_string(&QueuedJobState::Cancelled).unwrap())
 		.bind(OffsetDateTime::now_utc())
 		.bind(job_id.as_str())
-		.bind(serde_json::to_string(&QueuedJobState::Running).unwrap())
-		.fetch_one(&mut *conn)
-		.await;
+		.fetch_one(&mut *t)
+		.await?;
 
-		return match res {
-			Err(sqlx::Error::RowNotFound) => Err(FailJobError::NotRunning),
-			Err(e) => Err(e.into()),
-			Ok(_) => Ok(()),
-		};
+		Self::notify_event(
+			&mut t,
+			job_id,
+			res.get::<i64, _>("owned_by").into(),
+			res.get::<Option<&str>, _>("batch_id")
+				.map(BatchId::from)
+				.as_ref(),
+			QueuedJobStateKind::Cancelled,
+		)
+		.await?;
+
+		t.commit().await?;
+
+		return Ok(());
 	}
 
-	async fn success_job(&self, job_id: &QueuedJobId) -> Result<(), SuccessJobError> {
+	async fn heartbeat_job(
+		&self,
+		job_id: &QueuedJobId,
+		lease_owner: &str,
+		lease_duration: Duration,
+	) -> Result<(), HeartbeatJobError> {
 		let mut conn = self.pool.acquire().await?;
 		// RETURNING id is required, RowNotFound is always thrown if it is removed.
 		let res = sqlx::query(
 			"
 			UPDATE jobs
-			SET state = $1, finished_at = $2
-			WHERE id = $3
-			AND state = $4
+			SET lease_expires_at = $1
+			WHERE id = $2
+			AND state = $3
+			AND lease_owner = $4
 			RETURNING id;
 			",
 		)
-		.bind(serde_json::to_string(&QueuedJobState::Success).unwrap())
-		.bind(OffsetDateTime::now_utc())
+		.bind(OffsetDateTime::now_utc() + lease_duration)
 		.bind(job_id.as_str())
 		.bind(serde_json::to_string(&QueuedJobState::Running).unwrap())
+		.bind(lease_owner)
 		.fetch_one(&mut *conn)
 		.await;
 
 		return match res {
-			Err(sqlx::Error::RowNotFound) => Err(SuccessJobError::NotRunning),
+			Err(sqlx::Error::RowNotFound) => Err(HeartbeatJobError::LeaseLost),
 			Err(e) => Err(e.into()),
 			Ok(_) => Ok(()),
 		};
 	}
+
+	async fn reap_expired_jobs(&self) -> Result<Vec<QueuedJobId>, ReapJobsError> {
+		let mut conn = self.pool.acquire().await?;
+		let mut t = conn.begin().await?;
+
+		let res = sqlx::query(
+			"
+			SELECT id, attempt, started_at, attempt_history, retry_policy
+			FROM jobs
+			WHERE state = $1
+			AND lease_expires_at < $2
+			FOR UPDATE SKIP LOCKED;
+			",
+		)
+		.bind(serde_json::to_string(&QueuedJobState::Running).unwrap())
+		.bind(OffsetDateTime::now_utc())
+		.fetch_all(&mut *t)
+		.await?;
+
+		let message = "the runner running this job stopped responding";
+		let mut reaped = Vec::new();
+		for row in res {
+			let attempt = u32::try_from(row.get::<i32, _>("attempt")).unwrap();
+			let next_attempt_at = row
+				.get::<Option<Json<RetryPolicy>>, _>("retry_policy")
+				.and_then(|x| x.0.retry_after_lost(attempt))
+				.and_then(|x| OffsetDateTime::now_utc().checked_add(x.try_into().ok()?));
+
+			let state = match next_attempt_at {
+				Some(_) => QueuedJobState::Queued,
+				None => QueuedJobState::FailedRunning {
+					message: message.into(),
+				},
+			};
+
+			Self::end_attempt(&mut t, &row, &state, Some(message), &[], next_attempt_at).await?;
+			reaped.push(row.get::<&str, _>("id").into());
+		}
+
+		t.commit().await?;
+
+		return Ok(reaped);
+	}
+
+	async fn get_finished_jobs(
+		&self,
+		finished_after: OffsetDateTime,
+		after_job: Option<&QueuedJobId>,
+		count: i64,
+	) -> Result<Vec<FinishedJob>, GetFinishedJobsError> {
+		let mut conn = self.pool.acquire().await?;
+
+		// Every id is greater than the empty string
+		let res = sqlx::query(
+			"
+			SELECT id, owned_by, batch_id, state_kind, finished_at FROM jobs
+			WHERE finished_at IS NOT NULL AND (finished_at, id) > ($1, $2)
+			ORDER BY finished_at ASC, id ASC
+			LIMIT $3;
+			",
+		)
+		.bind(finished_after)
+		.bind(after_job.map(|x| x.as_str()).unwrap_or(""))
+		.bind(count)
+		.fetch_all(&mut *conn)
+		.await?;
+
+		return Ok(res
+			.iter()
+			.map(|row| FinishedJob {
+				event: JobEvent {
+					job_id: row.get::<&str, _>("id").into(),
+					owned_by: row.get::<i64, _>("owned_by").into(),
+					batch_id: row.get::<Option<&str>, _>("batch_id").map(|x| x.into()),
+					state: QueuedJobStateKind::from_name(row.get("state_kind")).unwrap(),
+				},
+				finished_at: row.get("finished_at"),
+			})
+			.collect());
+	}
+
+	async fn claim_expired_jobs(
+		&self,
+		retention: &JobRetention,
+		count: i64,
+	) -> Result<ExpiredJobs, GetExpiredJobsError> {
+		let mut t = self.pool.begin().await?;
+		if retention.keeps_everything() {
+			return Ok(ExpiredJobs {
+				jobs: Vec::new(),
+				t,
+			});
+		}
+
+		let now = OffsetDateTime::now_utc();
+
+		let mut q = QueryBuilder::new("SELECT * FROM jobs WHERE FALSE");
+		for (keep, states) in [
+			(retention.successful, &[QueuedJobStateKind::Success][..]),
+			(
+				retention.failed,
+				&[
+					QueuedJobStateKind::BuildError,
+					QueuedJobStateKind::FailedRunning,
+				][..],
+			),
+			(retention.cancelled, &[QueuedJobStateKind::Cancelled][..]),
+		] {
+			// If `keep` reaches past the start of time,
+			// nothing in this class has expired.
+			let cutoff = match keep
+				.and_then(|x| x.try_into().ok())
+				.and_then(|x| now.checked_sub(x))
+			{
+				Some(x) => x,
+				None => continue,
+			};
+
+			// Jobs waiting for a retry have no `finished_at`,
+			// and are never selected.
+			q.push(" OR (state_kind = ANY(");
+			q.push_bind(states.iter().map(|x| x.as_str()).collect::<Vec<_>>());
+			q.push(") AND finished_at < ");
+			q.push_bind(cutoff);
+			q.push(")");
+		}
+		q.push(" ORDER BY finished_at ASC LIMIT ");
+		q.push_bind(count);
+
+		// Locks are held until `t` ends, so these jobs
+		// can't be claimed by another cleanup task in the meantime.
+		q.push(" FOR UPDATE SKIP LOCKED;");
+
+		let res = q.build().fetch_all(&mut *t).await?;
+
+		let jobs = res
+			.iter()
+			.map(|row| ArchivedJob {
+				info: Self::job_from_row(row),
+				trace: row.get::<Json<Vec<NodeTrace>>, _>("trace").0,
+				attempts: row.get::<Json<Vec<JobAttempt>>, _>("attempt_history").0,
+			})
+			.collect();
+
+		return Ok(ExpiredJobs { jobs, t });
+	}
+
+	async fn delete_expired_jobs(&self, claim: ExpiredJobs) -> Result<u64, DeleteJobsError> {
+		let ExpiredJobs { jobs, mut t } = claim;
+		if jobs.is_empty() {
+			return Ok(0);
+		}
+
+		let ids: Vec<&str> = jobs.iter().map(|x| x.info.job_id.as_str()).collect();
+		let res = sqlx::query("DELETE FROM jobs WHERE id = ANY($1) RETURNING batch_id;")
+			.bind(&ids)
+			.fetch_all(&mut *t)
+			.await?;
+
+		let batches: Vec<&str> = res
+			.iter()
+			.filter_map(|row| row.get::<Option<&str>, _>("batch_id"))
+			.collect();
+
+		if !batches.is_empty() {
+			sqlx::query(
+				"
+				DELETE FROM batches
+				WHERE id = ANY($1)
+				AND NOT EXISTS (SELECT 1 FROM jobs WHERE jobs.batch_id = batches.id);
+				",
+			)
+			.bind(&batches)
+			.execute(&mut *t)
+			.await?;
+		}
+
+		t.commit().await?;
+
+		return Ok(u64::try_from(res.len()).unwrap());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Open the job queue at `COPPER_TEST_JOBQUEUE_DB`.
+	/// This should be an empty database, every test adds and claims jobs.
+	async fn client() -> PgJobQueueClient {
+		let Ok(addr) = std::env::var("COPPER_TEST_JOBQUEUE_DB") else {
+			panic!("COPPER_TEST_JOBQUEUE_DB must be set to run this test");
+		};
+		return PgJobQueueClient::open(&addr, true).await.unwrap();
+	}
+
+	/// Add a job with an empty pipeline, and claim it as `lease_owner`
+	async fn claim_new_job(client: &PgJobQueueClient, lease_owner: &str) -> QueuedJobId {
+		let pipeline: PipelineJson = serde_json::from_str(r#"{"nodes":{},"edges":{}}"#).unwrap();
+		let job_id: QueuedJobId =
+			format!("test-{}", OffsetDateTime::now_utc().unix_timestamp_nanos())
+				.as_str()
+				.into();
+
+		client
+			.add_job(
+				1.into(),
+				NewJob {
+					job_id: job_id.clone(),
+					pipeline_id: None,
+					pipeline: &pipeline,
+					input: &BTreeMap::new(),
+					retry_policy: None,
+					priority: JobPriority::default(),
+				},
+				&JobLimits::default(),
+			)
+			.await
+			.unwrap();
+
+		let job = client
+			.get_queued_job(
+				lease_owner,
+				Duration::from_secs(60),
+				&SchedulingPolicy::default(),
+				&JobLimits::default(),
+			)
+			.await
+			.unwrap()
+			.unwrap();
+		assert_eq!(job.job_id, job_id);
+
+		return job_id;
+	}
+
+	#[tokio::test]
+	#[ignore = "needs a database, see `client()`"]
+	async fn stale_owner_cannot_fail_job() {
+		let client = client().await;
+		let job_id = claim_new_job(&client, "runner-new").await;
+
+		let res = client
+			.fail_job_run(&job_id, "runner-old", "failed", &[])
+			.await;
+		assert!(matches!(res, Err(FailJobError::NotRunning)));
+
+		// The job's current runner still holds it
+		let job = client.get_job_short(&job_id).await.unwrap();
+		assert!(matches!(job.state, QueuedJobState::Running));
+		client
+			.heartbeat_job(&job_id, "runner-new", Duration::from_secs(60))
+			.await
+			.unwrap();
+
+		client
+			.fail_job_run(&job_id, "runner-new", "failed", &[])
+			.await
+			.unwrap();
+	}
+
+	#[tokio::test]
+	#[ignore = "needs a database, see `client()`"]
+	async fn cancel_running_job() {
+		let client = client().await;
+		let job_id = claim_new_job(&client, "runner").await;
+
+		client.cancel_job(&job_id).await.unwrap();
+
+		let job = client.get_job_short(&job_id).await.unwrap();
+		assert!(matches!(job.state, QueuedJobState::Cancelled));
+		assert!(job.finished_at.is_some());
+
+		let attempts = client.get_job_attempts(&job_id).await.unwrap();
+		assert_eq!(attempts.len(), 1);
+		assert_eq!(attempts[0].error.as_deref(), Some("cancelled"));
+
+		// The runner's lease is gone
+		let res = client
+			.heartbeat_job(&job_id, "runner", Duration::from_secs(60))
+			.await;
+		assert!(matches!(res, Err(HeartbeatJobError::LeaseLost)));
+	}
 }
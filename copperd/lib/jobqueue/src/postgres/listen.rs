@@ -0,0 +1,78 @@
+use sqlx::postgres::PgListener;
+use tracing::warn;
+
+use super::PgJobQueueClient;
+use crate::info::JobEvent;
+
+/// The channel we `NOTIFY` when a job may be ready to run
+pub(super) const JOB_READY_CHANNEL: &str = "copper_job_ready";
+
+/// The channel we `NOTIFY` when a job changes state.
+/// Payloads are json-encoded [`JobEvent`]s.
+pub(super) const JOB_EVENT_CHANNEL: &str = "copper_job_events";
+
+/// Receives a notification every time a job
+/// is added to the queue (or queued again).
+///
+/// Notifications are only a hint. They may be lost
+/// (e.g, if our connection drops), so anyone waiting
+/// for jobs should also poll the queue now and then.
+pub struct PgJobQueueListener {
+	listener: PgListener,
+}
+
+impl PgJobQueueListener {
+	/// Wait until a job may be ready to run.
+	/// Returns the id of the job that was queued.
+	pub async fn recv(&mut self) -> Result<String, sqlx::Error> {
+		let notification = self.listener.recv().await?;
+		return Ok(notification.payload().into());
+	}
+}
+
+/// Receives a [`JobEvent`] every time a job changes state.
+///
+/// Like [`PgJobQueueListener`], events may be lost
+/// if our connection drops.
+pub struct PgJobEventListener {
+	listener: PgListener,
+}
+
+impl PgJobEventListener {
+	/// Wait for the next job event
+	pub async fn recv(&mut self) -> Result<JobEvent, sqlx::Error> {
+		loop {
+			let notification = self.listener.recv().await?;
+			match serde_json::from_str(notification.payload()) {
+				Ok(event) => return Ok(event),
+				Err(error) => {
+					warn!(
+						message = "Ignoring malformed job event",
+						payload = notification.payload(),
+						?error
+					);
+				}
+			}
+		}
+	}
+}
+
+impl PgJobQueueClient {
+	/// Start listening for new jobs.
+	///
+	/// Each listener opens its own connection, so that listeners
+	/// never hold a connection from this client's pool.
+	pub async fn listen(&self) -> Result<PgJobQueueListener, sqlx::Error> {
+		let mut listener = PgListener::connect(&self.db_addr).await?;
+		listener.listen(JOB_READY_CHANNEL).await?;
+		return Ok(PgJobQueueListener { listener });
+	}
+
+	/// Start listening for job events.
+	/// Like [`PgJobQueueClient::listen`], this opens a new connection.
+	pub async fn listen_events(&self) -> Result<PgJobEventListener, sqlx::Error> {
+		let mut listener = PgListener::connect(&self.db_addr).await?;
+		listener.listen(JOB_EVENT_CHANNEL).await?;
+		return Ok(PgJobEventListener { listener });
+	}
+}
@@ -0,0 +1,23 @@
+use copper_migrate::Migration;
+use sqlx::Connection;
+
+pub(super) struct MigrationStep {}
+
+#[async_trait::async_trait]
+impl Migration for MigrationStep {
+	fn name(&self) -> &str {
+		"m_1_trace"
+	}
+
+	async fn up(&self, conn: &mut sqlx::PgConnection) -> Result<(), sqlx::Error> {
+		let mut t = conn.begin().await?;
+
+		sqlx::query("ALTER TABLE jobs ADD COLUMN trace JSONB NOT NULL DEFAULT '[]';")
+			.execute(&mut *t)
+			.await?;
+
+		t.commit().await?;
+
+		return Ok(());
+	}
+}
@@ -0,0 +1,35 @@
+use copper_migrate::Migration;
+use sqlx::Connection;
+
+pub(super) struct MigrationStep {}
+
+#[async_trait::async_trait]
+impl Migration for MigrationStep {
+	fn name(&self) -> &str {
+		"m_2_retry"
+	}
+
+	async fn up(&self, conn: &mut sqlx::PgConnection) -> Result<(), sqlx::Error> {
+		let mut t = conn.begin().await?;
+
+		sqlx::query(
+			"
+			ALTER TABLE jobs
+			ADD COLUMN retry_policy JSONB,
+			ADD COLUMN attempt INTEGER NOT NULL DEFAULT 0,
+			ADD COLUMN next_attempt_at TIMESTAMPTZ,
+			ADD COLUMN attempt_history JSONB NOT NULL DEFAULT '[]';
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		sqlx::query("CREATE INDEX idx_jobs_next_attempt_at on jobs(next_attempt_at);")
+			.execute(&mut *t)
+			.await?;
+
+		t.commit().await?;
+
+		return Ok(());
+	}
+}
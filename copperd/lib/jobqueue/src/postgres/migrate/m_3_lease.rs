@@ -0,0 +1,33 @@
+use copper_migrate::Migration;
+use sqlx::Connection;
+
+pub(super) struct MigrationStep {}
+
+#[async_trait::async_trait]
+impl Migration for MigrationStep {
+	fn name(&self) -> &str {
+		"m_3_lease"
+	}
+
+	async fn up(&self, conn: &mut sqlx::PgConnection) -> Result<(), sqlx::Error> {
+		let mut t = conn.begin().await?;
+
+		sqlx::query(
+			"
+			ALTER TABLE jobs
+			ADD COLUMN lease_owner TEXT,
+			ADD COLUMN lease_expires_at TIMESTAMPTZ;
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		sqlx::query("CREATE INDEX idx_jobs_lease_expires_at on jobs(lease_expires_at);")
+			.execute(&mut *t)
+			.await?;
+
+		t.commit().await?;
+
+		return Ok(());
+	}
+}
@@ -0,0 +1,34 @@
+use copper_migrate::Migration;
+use sqlx::Connection;
+
+pub(super) struct MigrationStep {}
+
+#[async_trait::async_trait]
+impl Migration for MigrationStep {
+	fn name(&self) -> &str {
+		"m_4_priority"
+	}
+
+	async fn up(&self, conn: &mut sqlx::PgConnection) -> Result<(), sqlx::Error> {
+		let mut t = conn.begin().await?;
+
+		// 1 is `JobPriority::Normal`
+		sqlx::query("ALTER TABLE jobs ADD COLUMN priority SMALLINT NOT NULL DEFAULT 1;")
+			.execute(&mut *t)
+			.await?;
+
+		// Used to find each user's best queued job
+		sqlx::query(
+			"
+			CREATE INDEX idx_jobs_queue_order
+			ON jobs(state, owned_by, priority DESC, created_at ASC);
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		t.commit().await?;
+
+		return Ok(());
+	}
+}
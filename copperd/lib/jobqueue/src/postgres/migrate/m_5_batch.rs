@@ -0,0 +1,44 @@
+use copper_migrate::Migration;
+use sqlx::Connection;
+
+pub(super) struct MigrationStep {}
+
+#[async_trait::async_trait]
+impl Migration for MigrationStep {
+	fn name(&self) -> &str {
+		"m_5_batch"
+	}
+
+	async fn up(&self, conn: &mut sqlx::PgConnection) -> Result<(), sqlx::Error> {
+		let mut t = conn.begin().await?;
+
+		sqlx::query(
+			"
+			CREATE TABLE batches (
+				id TEXT PRIMARY KEY,
+				created_at TIMESTAMPTZ NOT NULL,
+				owned_by BIGINT NOT NULL
+			);
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		sqlx::query(
+			"
+			ALTER TABLE jobs
+			ADD COLUMN batch_id TEXT REFERENCES batches(id) ON DELETE SET NULL;
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		sqlx::query("CREATE INDEX idx_jobs_batch_id on jobs(batch_id);")
+			.execute(&mut *t)
+			.await?;
+
+		t.commit().await?;
+
+		return Ok(());
+	}
+}
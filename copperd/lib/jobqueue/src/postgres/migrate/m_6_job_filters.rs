@@ -0,0 +1,44 @@
+use copper_migrate::Migration;
+use sqlx::Connection;
+
+pub(super) struct MigrationStep {}
+
+#[async_trait::async_trait]
+impl Migration for MigrationStep {
+	fn name(&self) -> &str {
+		"m_6_job_filters"
+	}
+
+	async fn up(&self, conn: &mut sqlx::PgConnection) -> Result<(), sqlx::Error> {
+		let mut t = conn.begin().await?;
+
+		// `state` is a json object tagged with the state's name.
+		// We keep that name in its own column, so we can filter on it.
+		sqlx::query(
+			"
+			ALTER TABLE jobs
+			ADD COLUMN state_kind TEXT GENERATED ALWAYS AS ((state::jsonb)->>'state') STORED,
+			ADD COLUMN pipeline_id BIGINT;
+			",
+		)
+		.execute(&mut *t)
+		.await?;
+
+		// Indices for job lists.
+		// Every list is restricted to one user, so `owned_by` comes first.
+		for query in [
+			"CREATE INDEX idx_jobs_list_created on jobs(owned_by, created_at);",
+			"CREATE INDEX idx_jobs_list_started on jobs(owned_by, started_at);",
+			"CREATE INDEX idx_jobs_list_finished on jobs(owned_by, finished_at);",
+			"CREATE INDEX idx_jobs_list_state on jobs(owned_by, state_kind, created_at);",
+			"CREATE INDEX idx_jobs_list_pipeline on jobs(owned_by, pipeline_id, created_at);",
+			"CREATE INDEX idx_jobs_list_id on jobs(owned_by, id text_pattern_ops);",
+		] {
+			sqlx::query(query).execute(&mut *t).await?;
+		}
+
+		t.commit().await?;
+
+		return Ok(());
+	}
+}
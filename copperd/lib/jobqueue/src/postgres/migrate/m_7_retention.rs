@@ -0,0 +1,19 @@
+use copper_migrate::Migration;
+
+pub(super) struct MigrationStep {}
+
+#[async_trait::async_trait]
+impl Migration for MigrationStep {
+	fn name(&self) -> &str {
+		"m_7_retention"
+	}
+
+	async fn up(&self, conn: &mut sqlx::PgConnection) -> Result<(), sqlx::Error> {
+		// Used to find finished jobs that have expired
+		sqlx::query("CREATE INDEX idx_jobs_finished on jobs(state_kind, finished_at);")
+			.execute(&mut *conn)
+			.await?;
+
+		return Ok(());
+	}
+}
@@ -0,0 +1,25 @@
+use copper_migrate::Migration;
+
+pub(super) struct MigrationStep {}
+
+#[async_trait::async_trait]
+impl Migration for MigrationStep {
+	fn name(&self) -> &str {
+		"m_8_finished"
+	}
+
+	async fn up(&self, conn: &mut sqlx::PgConnection) -> Result<(), sqlx::Error> {
+		// Used to page through finished jobs in the order they finished
+		sqlx::query(
+			"
+			CREATE INDEX idx_jobs_finished_at
+			ON jobs(finished_at, id)
+			WHERE finished_at IS NOT NULL;
+			",
+		)
+		.execute(&mut *conn)
+		.await?;
+
+		return Ok(());
+	}
+}
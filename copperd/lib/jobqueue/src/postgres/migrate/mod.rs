@@ -1,5 +1,23 @@
 use copper_migrate::Migration;
 
 mod m_0_init;
+mod m_1_trace;
+mod m_2_retry;
+mod m_3_lease;
+mod m_4_priority;
+mod m_5_batch;
+mod m_6_job_filters;
+mod m_7_retention;
+mod m_8_finished;
 
-pub const MIGRATE_STEPS: &[&'static dyn Migration] = &[&m_0_init::MigrationStep {}];
+pub const MIGRATE_STEPS: &[&'static dyn Migration] = &[
+	&m_0_init::MigrationStep {},
+	&m_1_trace::MigrationStep {},
+	&m_2_retry::MigrationStep {},
+	&m_3_lease::MigrationStep {},
+	&m_4_priority::MigrationStep {},
+	&m_5_batch::MigrationStep {},
+	&m_6_job_filters::MigrationStep {},
+	&m_7_retention::MigrationStep {},
+	&m_8_finished::MigrationStep {},
+];
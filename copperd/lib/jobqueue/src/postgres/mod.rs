@@ -7,8 +7,11 @@ use thiserror::Error;
 use tracing::info;
 
 mod client;
+mod listen;
 mod migrate;
 
+pub use listen::{PgJobEventListener, PgJobQueueListener};
+
 #[derive(Debug, Error)]
 /// An error we may encounter when connecting to postgres
 pub enum PgJobQueueOpenError {
@@ -29,6 +32,10 @@ pub enum PgJobQueueOpenError {
 /// A database client for Postgres
 pub struct PgJobQueueClient {
 	pool: PgPool,
+
+	/// The address of our database.
+	/// Listeners open their own connections with this.
+	db_addr: String,
 }
 
 impl PgJobQueueClient {
@@ -59,6 +66,9 @@ impl PgJobQueueClient {
 			.connect(db_addr)
 			.await?;
 
-		Ok(Self { pool })
+		Ok(Self {
+			pool,
+			db_addr: db_addr.into(),
+		})
 	}
 }
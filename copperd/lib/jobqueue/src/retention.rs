@@ -0,0 +1,26 @@
+//! How long finished jobs are kept
+
+use std::time::Duration;
+
+/// How long finished jobs are kept before they may be deleted.
+/// `None` means "keep forever".
+///
+/// Each duration is measured from the time a job finished.
+#[derive(Debug, Clone, Default)]
+pub struct JobRetention {
+	/// How long to keep `Success` jobs
+	pub successful: Option<Duration>,
+
+	/// How long to keep `BuildError` and `FailedRunning` jobs
+	pub failed: Option<Duration>,
+
+	/// How long to keep `Cancelled` jobs
+	pub cancelled: Option<Duration>,
+}
+
+impl JobRetention {
+	/// Returns true if this policy never expires any jobs
+	pub fn keeps_everything(&self) -> bool {
+		self.successful.is_none() && self.failed.is_none() && self.cancelled.is_none()
+	}
+}
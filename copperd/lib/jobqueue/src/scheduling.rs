@@ -0,0 +1,276 @@
+//! Decide which queued job runs next
+
+use copper_itemdb::UserId;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::BTreeMap};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use crate::id::QueuedJobId;
+
+/// A job's priority.
+///
+/// Priorities order the jobs of one user. Runners are shared
+/// between users by [`SchedulingPolicy`], so a high-priority job
+/// can't jump ahead of users that are using less than their share.
+#[derive(
+	Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize, ToSchema,
+)]
+pub enum JobPriority {
+	Low,
+	#[default]
+	Normal,
+	High,
+}
+
+impl JobPriority {
+	/// The value we store in the database.
+	/// Larger values run first.
+	pub fn as_i16(&self) -> i16 {
+		match self {
+			Self::Low => 0,
+			Self::Normal => 1,
+			Self::High => 2,
+		}
+	}
+
+	/// Get a priority from the value we store in the database
+	pub fn from_i16(value: i16) -> Self {
+		match value {
+			i16::MIN..=0 => Self::Low,
+			1 => Self::Normal,
+			2..=i16::MAX => Self::High,
+		}
+	}
+}
+
+/// A job that could run next.
+/// This is the best queued job of one user.
+#[derive(Debug, Clone)]
+pub struct JobCandidate {
+	/// This job's id
+	pub job_id: QueuedJobId,
+
+	/// The user that owns this job
+	pub owned_by: UserId,
+
+	/// This job's priority
+	pub priority: JobPriority,
+
+	/// When this job was created
+	pub created_at: OffsetDateTime,
+}
+
+/// How we choose which queued job runs next
+#[derive(Debug, Clone)]
+pub struct SchedulingPolicy {
+	/// If true, share runners fairly between users.
+	///
+	/// If false, always run the queued job with the highest
+	/// priority, oldest first, no matter who owns it.
+	pub fair: bool,
+
+	/// The share of runners each user gets, relative to other users.
+	/// A user with weight 2 may run twice as many jobs as a user with weight 1.
+	pub user_weights: BTreeMap<UserId, u32>,
+
+	/// The weight of users that aren't in `user_weights`
+	pub default_weight: u32,
+}
+
+impl Default for SchedulingPolicy {
+	fn default() -> Self {
+		Self {
+			fair: true,
+			user_weights: BTreeMap::new(),
+			default_weight: 1,
+		}
+	}
+}
+
+impl SchedulingPolicy {
+	/// Get the weight of the given user.
+	/// This is never zero.
+	pub fn weight(&self, user: UserId) -> u32 {
+		self.user_weights
+			.get(&user)
+			.copied()
+			.unwrap_or(self.default_weight)
+			.max(1)
+	}
+
+	/// Pick the job that should run next.
+	///
+	/// `candidates` should contain the best queued job of each user
+	/// (or, if this policy isn't fair, the best queued job overall).
+	/// `running` is the number of jobs each user is running right now.
+	///
+	/// We pick the user that is using the smallest share of their
+	/// weight, so that a user with a huge backlog can't starve others.
+	/// Ties are broken by priority, and then by age.
+	///
+	/// Returns `None` if every candidate's owner is already running
+	/// `max_running_per_user` jobs.
+	pub fn pick<'a>(
+		&self,
+		candidates: &'a [JobCandidate],
+		running: &BTreeMap<UserId, u32>,
+		max_running_per_user: Option<u32>,
+	) -> Option<&'a JobCandidate> {
+		let load = |x: &JobCandidate| -> (u64, u64) {
+			(
+				u64::from(running.get(&x.owned_by).copied().unwrap_or(0)),
+				u64::from(self.weight(x.owned_by)),
+			)
+		};
+
+		let under_limit = |x: &&JobCandidate| match max_running_per_user {
+			None => true,
+			Some(max) => running.get(&x.owned_by).copied().unwrap_or(0) < max,
+		};
+
+		return candidates.iter().filter(under_limit).min_by(|a, b| {
+			let by_load = if self.fair {
+				// Compare running_a / weight_a with running_b / weight_b
+				let (ra, wa) = load(a);
+				let (rb, wb) = load(b);
+				(ra * wb).cmp(&(rb * wa))
+			} else {
+				Ordering::Equal
+			};
+
+			by_load
+				.then(b.priority.cmp(&a.priority))
+				.then(a.created_at.cmp(&b.created_at))
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use time::Duration;
+
+	fn candidate(job_id: &str, user: i64, priority: JobPriority, age_secs: i64) -> JobCandidate {
+		JobCandidate {
+			job_id: job_id.into(),
+			owned_by: user.into(),
+			priority,
+			created_at: OffsetDateTime::UNIX_EPOCH + Duration::days(1)
+				- Duration::seconds(age_secs),
+		}
+	}
+
+	#[test]
+	fn small_job_not_blocked_by_backlog() {
+		let policy = SchedulingPolicy::default();
+
+		// User 1 queued a huge import long ago, and is running four of its jobs.
+		// User 2 just queued one small job.
+		let candidates = vec![
+			candidate("bulk", 1, JobPriority::Normal, 10_000),
+			candidate("small", 2, JobPriority::Normal, 1),
+		];
+		let running = BTreeMap::from([(1.into(), 4)]);
+
+		let picked = policy.pick(&candidates, &running, None).unwrap();
+		assert_eq!(picked.job_id, "small".into());
+	}
+
+	#[test]
+	fn unfair_runs_oldest() {
+		let policy = SchedulingPolicy {
+			fair: false,
+			..Default::default()
+		};
+
+		let candidates = vec![
+			candidate("bulk", 1, JobPriority::Normal, 10_000),
+			candidate("small", 2, JobPriority::Normal, 1),
+		];
+		let running = BTreeMap::from([(1.into(), 4)]);
+
+		let picked = policy.pick(&candidates, &running, None).unwrap();
+		assert_eq!(picked.job_id, "bulk".into());
+	}
+
+	#[test]
+	fn round_robin_between_idle_users() {
+		let policy = SchedulingPolicy::default();
+
+		// Simulate a queue where user 1 has many jobs and user 2 has a few,
+		// and check that runners are handed out alternately.
+		let mut queued: BTreeMap<i64, u32> = BTreeMap::from([(1, 1000), (2, 3)]);
+		let mut running: BTreeMap<UserId, u32> = BTreeMap::new();
+		let mut order = Vec::new();
+
+		for _ in 0..6 {
+			let candidates: Vec<JobCandidate> = queued
+				.iter()
+				.filter(|(_, n)| **n > 0)
+				.map(|(user, _)| candidate(&format!("{user}"), *user, JobPriority::Normal, *user))
+				.collect();
+
+			let picked = policy.pick(&candidates, &running, None).unwrap();
+			let user: i64 = picked.owned_by.into();
+			*queued.get_mut(&user).unwrap() -= 1;
+			*running.entry(picked.owned_by).or_default() += 1;
+			order.push(user);
+		}
+
+		assert_eq!(order, vec![2, 1, 2, 1, 2, 1]);
+	}
+
+	#[test]
+	fn weights_change_share() {
+		let policy = SchedulingPolicy {
+			user_weights: BTreeMap::from([(1.into(), 3)]),
+			..Default::default()
+		};
+
+		let candidates = vec![
+			candidate("heavy", 1, JobPriority::Normal, 100),
+			candidate("light", 2, JobPriority::Normal, 1),
+		];
+
+		// User 1 may run three jobs for every one of user 2's.
+		let running = BTreeMap::from([(1.into(), 2), (2.into(), 1)]);
+		let picked = policy.pick(&candidates, &running, None).unwrap();
+		assert_eq!(picked.job_id, "heavy".into());
+
+		let running = BTreeMap::from([(1.into(), 3), (2.into(), 0)]);
+		let picked = policy.pick(&candidates, &running, None).unwrap();
+		assert_eq!(picked.job_id, "light".into());
+	}
+
+	#[test]
+	fn running_limit_skips_user() {
+		let policy = SchedulingPolicy {
+			fair: false,
+			..Default::default()
+		};
+
+		let candidates = vec![candidate("bulk", 1, JobPriority::Normal, 10_000)];
+
+		let running = BTreeMap::from([(1.into(), 2)]);
+		assert!(policy.pick(&candidates, &running, Some(2)).is_none());
+		assert!(policy.pick(&candidates, &running, None).is_some());
+
+		let running = BTreeMap::from([(1.into(), 1)]);
+		assert!(policy.pick(&candidates, &running, Some(2)).is_some());
+	}
+
+	#[test]
+	fn priority_breaks_ties() {
+		let policy = SchedulingPolicy::default();
+
+		let candidates = vec![
+			candidate("old", 1, JobPriority::Normal, 100),
+			candidate("urgent", 2, JobPriority::High, 1),
+		];
+		let running = BTreeMap::new();
+
+		let picked = policy.pick(&candidates, &running, None).unwrap();
+		assert_eq!(picked.job_id, "urgent".into());
+	}
+}
@@ -1,11 +1,22 @@
+use serde::Serialize;
 use smartstring::{LazyCompact, SmartString};
 use std::collections::BTreeMap;
 use thiserror::Error;
+use utoipa::ToSchema;
 
-use super::{Node, NodeParameterSpec};
+use super::{
+	Node, NodeParameterError, NodeParameterSpec, NodeParameterValue, NodePorts, RunNodeError,
+};
+use crate::helpers::NodeParameters;
 
 pub trait NodeBuilder: Send + Sync {
 	fn build<'ctx>(&self) -> Box<dyn Node<'ctx>>;
+
+	/// Describe the ports a node with the given parameters has.
+	///
+	/// This is used to check pipelines before they run,
+	/// so it must not do any expensive work.
+	fn ports(&self, params: NodeParameters) -> Result<NodePorts, RunNodeError>;
 }
 
 pub const INPUT_NODE_TYPE: &str = "Input";
@@ -23,8 +34,32 @@ struct RegisteredNode {
 	/// A method that constructs a new node of this type with the provided parameters.
 	builder: Box<dyn NodeBuilder>,
 
+	/// Human-readable documentation for this node
+	docs: String,
+
 	/// The parameters this node takes
-	_parameters: BTreeMap<SmartString<LazyCompact>, NodeParameterSpec>,
+	parameters: BTreeMap<SmartString<LazyCompact>, NodeParameterSpec>,
+}
+
+/// A description of a node type we've registered
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct NodeInfo {
+	/// This node's type, as used in pipeline json
+	#[schema(value_type = String)]
+	pub node_type: SmartString<LazyCompact>,
+
+	/// Human-readable documentation for this node
+	pub docs: String,
+
+	/// The parameters this node takes
+	#[schema(value_type = BTreeMap<String, NodeParameterSpec>)]
+	pub parameters: BTreeMap<SmartString<LazyCompact>, NodeParameterSpec>,
+
+	/// The ports this node has if it is given no parameters.
+	///
+	/// This is `None` if this node can't determine its ports without parameters.
+	/// Use [`NodeDispatcher::node_ports`] to get the ports of a node with parameters.
+	pub ports: Option<NodePorts>,
 }
 
 /// A factory struct that constructs pipeline nodes
@@ -43,10 +78,14 @@ impl NodeDispatcher {
 	/// Register a new node type.
 	///
 	/// - `type_name` must be a new node type, we'll return an error if it already exists.
+	/// - `docs` is a human-readable description of this node.
+	/// - `parameters` describes the parameters this node takes.
+	///   Pipelines are checked against this spec before they are run.
 	/// - `init_node` is a method that constructs a new node of the given type with the provided parameters.
 	pub fn register_node(
 		&mut self,
 		type_name: &str,
+		docs: &str,
 		parameters: BTreeMap<SmartString<LazyCompact>, NodeParameterSpec>,
 		builder: Box<dyn NodeBuilder>,
 	) -> Result<(), RegisterNodeError> {
@@ -58,7 +97,8 @@ impl NodeDispatcher {
 			type_name.into(),
 			RegisteredNode {
 				builder,
-				_parameters: parameters,
+				docs: docs.into(),
+				parameters,
 			},
 		);
 
@@ -69,6 +109,68 @@ impl NodeDispatcher {
 		return self.nodes.contains_key(node_name);
 	}
 
+	/// Describe all node types we've registered
+	pub fn describe_nodes(&self) -> Vec<NodeInfo> {
+		return self
+			.nodes
+			.iter()
+			.map(|(node_type, node)| NodeInfo {
+				node_type: node_type.clone(),
+				docs: node.docs.clone(),
+				parameters: node.parameters.clone(),
+				ports: node.builder.ports(BTreeMap::new().into()).ok(),
+			})
+			.collect();
+	}
+
+	/// Check the given parameters against the spec of node type `node_type`.
+	/// Returns `None` if this node type doesn't exist.
+	pub fn check_params(
+		&self,
+		node_type: &str,
+		params: &BTreeMap<SmartString<LazyCompact>, NodeParameterValue>,
+	) -> Option<Result<(), NodeParameterError>> {
+		let node = self.nodes.get(node_type)?;
+
+		for (name, spec) in &node.parameters {
+			match params.get(name) {
+				Some(value) => {
+					if let Err(e) = spec.param_type.check(name, value) {
+						return Some(Err(e));
+					}
+				}
+				None => {
+					if !spec.is_optional {
+						return Some(Err(NodeParameterError::MissingParameter {
+							parameter: name.clone(),
+						}));
+					}
+				}
+			}
+		}
+
+		if let Some(name) = params.keys().find(|x| !node.parameters.contains_key(*x)) {
+			return Some(Err(NodeParameterError::UnexpectedParameter {
+				parameter: name.clone(),
+			}));
+		}
+
+		return Some(Ok(()));
+	}
+
+	/// Get the ports of a node of type `node_type` with the given parameters.
+	/// Returns `None` if this node type doesn't exist.
+	pub fn node_ports(
+		&self,
+		node_type: &str,
+		params: &BTreeMap<SmartString<LazyCompact>, NodeParameterValue>,
+	) -> Option<Result<NodePorts, RunNodeError>> {
+		return self
+			.nodes
+			.get(node_type)
+			.map(|node| node.builder.ports(params.clone().into()));
+	}
+
 	pub fn init_node<'ctx>(&self, node_type: &str) -> Option<Box<dyn Node<'ctx>>> {
 		if let Some(node) = self.nodes.get(node_type) {
 			return Some(node.builder.build());
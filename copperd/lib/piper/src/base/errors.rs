@@ -1,7 +1,9 @@
+use serde::{Deserialize, Serialize};
 use smartstring::{LazyCompact, SmartString};
 use std::{error::Error, sync::Arc};
 use thiserror::Error;
 use tokio::task::JoinError;
+use utoipa::ToSchema;
 
 use super::{NodeId, PortName};
 
@@ -69,6 +71,10 @@ pub enum RunNodeError {
 	#[error("i/o error")]
 	IoError(#[from] Arc<std::io::Error>),
 
+	/// An error while talking to the object store
+	#[error("object store error")]
+	ObjectStoreError(Arc<dyn Error + Sync + Send + 'static>),
+
 	/// An arbitrary error
 	#[error("generic error")]
 	Other(#[from] Arc<dyn Error + Sync + Send + 'static>),
@@ -77,6 +83,10 @@ pub enum RunNodeError {
 	#[error("error while joining task")]
 	NodeTaskJoinError(#[from] Arc<JoinError>),
 
+	/// This job was cancelled while it was running
+	#[error("job was cancelled")]
+	Cancelled,
+
 	/// One output port got input twice
 	#[error("node {node_id} ({node_type}) sent data to output {port} twice.")]
 	OutputPortSetTwice {
@@ -86,6 +96,52 @@ pub enum RunNodeError {
 	},
 }
 
+/// A broad category of [`RunNodeError`]s that may go away
+/// if we run the same job again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum RunNodeErrorKind {
+	/// An error in the item db
+	Database,
+
+	/// An error while talking to the object store
+	ObjectStore,
+
+	/// A generic I/O error
+	Io,
+
+	/// An arbitrary error returned by a node
+	Other,
+}
+
+impl RunNodeError {
+	/// Get the kind of this error.
+	///
+	/// Returns `None` if this error can never go away
+	/// if we try again (e.g, an error in the pipeline definition).
+	pub fn kind(&self) -> Option<RunNodeErrorKind> {
+		return match self {
+			Self::DbError(_) => Some(RunNodeErrorKind::Database),
+			Self::ObjectStoreError(_) => Some(RunNodeErrorKind::ObjectStore),
+			Self::IoError(_) => Some(RunNodeErrorKind::Io),
+			Self::Other(_) => Some(RunNodeErrorKind::Other),
+
+			Self::UnexpectedParameter { .. }
+			| Self::BadParameterType { .. }
+			| Self::MissingParameter { .. }
+			| Self::BadParameterOther { .. }
+			| Self::MissingInput { .. }
+			| Self::RequiredInputNull { .. }
+			| Self::UnrecognizedInput { .. }
+			| Self::BadInputType { .. }
+			| Self::UnrecognizedOutput { .. }
+			| Self::NotAuthorized { .. }
+			| Self::NodeTaskJoinError(_)
+			| Self::Cancelled
+			| Self::OutputPortSetTwice { .. } => None,
+		};
+	}
+}
+
 impl From<sqlx::Error> for RunNodeError {
 	fn from(value: sqlx::Error) -> Self {
 		Self::DbError(Arc::new(value))
@@ -104,6 +160,43 @@ impl From<JoinError> for RunNodeError {
 	}
 }
 
+/// An error we encounter when a node's parameters
+/// don't match the spec it was registered with
+#[derive(Debug, Clone, Error)]
+pub enum NodeParameterError {
+	/// A required parameter wasn't provided
+	#[error("missing parameter `{parameter}`")]
+	MissingParameter { parameter: SmartString<LazyCompact> },
+
+	/// We got a parameter this node doesn't take
+	#[error("unexpected parameter `{parameter}`")]
+	UnexpectedParameter { parameter: SmartString<LazyCompact> },
+
+	/// A parameter had an unexpected type
+	#[error("bad type for parameter `{parameter}`")]
+	BadType { parameter: SmartString<LazyCompact> },
+
+	/// A parameter had the right type, but an invalid value
+	/// (e.g, a string that isn't one of an enum's variants)
+	#[error("invalid value `{value}` for parameter `{parameter}`")]
+	BadValue {
+		parameter: SmartString<LazyCompact>,
+		value: SmartString<LazyCompact>,
+	},
+}
+
+impl NodeParameterError {
+	/// The name of the parameter this error is about
+	pub fn parameter(&self) -> &SmartString<LazyCompact> {
+		match self {
+			Self::MissingParameter { parameter }
+			| Self::UnexpectedParameter { parameter }
+			| Self::BadType { parameter }
+			| Self::BadValue { parameter, .. } => parameter,
+		}
+	}
+}
+
 /// An error we encounter while running a node
 #[derive(Debug, Error)]
 pub enum ProcessSignalError {
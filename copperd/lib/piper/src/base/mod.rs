@@ -12,5 +12,8 @@ pub use param::*;
 mod labels;
 pub use labels::*;
 
+mod ports;
+pub use ports::*;
+
 mod dispatcher;
 pub use dispatcher::*;
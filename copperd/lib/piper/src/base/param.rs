@@ -3,7 +3,23 @@ use smartstring::{LazyCompact, SmartString};
 use std::collections::{BTreeMap, BTreeSet};
 use utoipa::ToSchema;
 
+use super::NodeParameterError;
+
+/// The names of the types of pipeline data
+/// a [`NodeParameterType::DataType`] may take.
+const DATA_TYPE_NAMES: [&str; 7] = [
+	"Text",
+	"Integer",
+	"Float",
+	"Boolean",
+	"Hash",
+	"Blob",
+	"Reference",
+];
+
 /// The types of node parameters we accept
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "type")]
 pub enum NodeParameterType {
 	/// A type of pipeline data
 	DataType,
@@ -11,12 +27,16 @@ pub enum NodeParameterType {
 	/// A yes or a no
 	Boolean,
 
+	/// An integer
+	Integer,
+
 	/// A plain string
 	String,
 
 	/// One of many predefined strings
 	Enum {
 		/// The values this enum can take
+		#[schema(value_type = Vec<String>)]
 		variants: BTreeSet<SmartString<LazyCompact>>,
 	},
 
@@ -31,6 +51,80 @@ pub enum NodeParameterType {
 		/// The type of item this map holds
 		value_type: Box<NodeParameterType>,
 	},
+
+	/// A parameter that may have any of the given types
+	Union {
+		/// The types this parameter may have
+		types: Vec<NodeParameterType>,
+	},
+}
+
+impl NodeParameterType {
+	/// Make sure `value` is a valid instance of this type.
+	/// `parameter` is the name of the parameter we're checking, used in errors.
+	pub fn check(
+		&self,
+		parameter: &str,
+		value: &NodeParameterValue,
+	) -> Result<(), NodeParameterError> {
+		match (self, value) {
+			(Self::Boolean, NodeParameterValue::Boolean(_))
+			| (Self::Integer, NodeParameterValue::Integer(_))
+			| (Self::String, NodeParameterValue::String(_)) => return Ok(()),
+
+			(Self::DataType, NodeParameterValue::String(s)) => {
+				if DATA_TYPE_NAMES.contains(&s.as_str()) {
+					return Ok(());
+				}
+
+				return Err(NodeParameterError::BadValue {
+					parameter: parameter.into(),
+					value: s.clone(),
+				});
+			}
+
+			(Self::Enum { variants }, NodeParameterValue::String(s)) => {
+				if variants.contains(s) {
+					return Ok(());
+				}
+
+				return Err(NodeParameterError::BadValue {
+					parameter: parameter.into(),
+					value: s.clone(),
+				});
+			}
+
+			(Self::List { item_type }, NodeParameterValue::List(items)) => {
+				for item in items {
+					item_type.check(parameter, item)?;
+				}
+				return Ok(());
+			}
+
+			(Self::Map { value_type }, NodeParameterValue::Map(map)) => {
+				for value in map.values() {
+					value_type.check(parameter, value)?;
+				}
+				return Ok(());
+			}
+
+			(Self::Union { types }, _) => {
+				if types.iter().any(|t| t.check(parameter, value).is_ok()) {
+					return Ok(());
+				}
+
+				return Err(NodeParameterError::BadType {
+					parameter: parameter.into(),
+				});
+			}
+
+			_ => {
+				return Err(NodeParameterError::BadType {
+					parameter: parameter.into(),
+				})
+			}
+		}
+	}
 }
 
 /// The types of node parameters we accept
@@ -58,6 +152,7 @@ pub enum NodeParameterValue {
 }
 
 /// A description of one parameter a node accepts
+#[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct NodeParameterSpec {
 	/// The type of this parameter
 	pub param_type: NodeParameterType,
@@ -65,3 +160,75 @@ pub struct NodeParameterSpec {
 	/// If true, this parameter is optional
 	pub is_optional: bool,
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn string(s: &str) -> NodeParameterValue {
+		NodeParameterValue::String(s.into())
+	}
+
+	fn enum_type(variants: &[&str]) -> NodeParameterType {
+		NodeParameterType::Enum {
+			variants: variants.iter().map(|x| (*x).into()).collect(),
+		}
+	}
+
+	#[test]
+	fn checks_integers() {
+		let t = NodeParameterType::Integer;
+		assert!(t.check("p", &NodeParameterValue::Integer(-3)).is_ok());
+
+		for value in [string("3"), NodeParameterValue::Boolean(true)] {
+			assert!(matches!(
+				t.check("p", &value),
+				Err(NodeParameterError::BadType { parameter }) if parameter == "p"
+			));
+		}
+	}
+
+	#[test]
+	fn checks_enums() {
+		let t = enum_type(&["md5", "sha256"]);
+		assert!(t.check("p", &string("md5")).is_ok());
+		assert!(matches!(
+			t.check("p", &string("crc32")),
+			Err(NodeParameterError::BadValue { value, .. }) if value == "crc32"
+		));
+	}
+
+	#[test]
+	fn union_tries_every_type() {
+		let t = NodeParameterType::Union {
+			types: vec![NodeParameterType::Integer, enum_type(&["auto"])],
+		};
+
+		assert!(t.check("p", &NodeParameterValue::Integer(7)).is_ok());
+		assert!(t.check("p", &string("auto")).is_ok());
+
+		// A value no member accepts is a type error,
+		// even if one member only rejected its value.
+		for value in [string("manual"), NodeParameterValue::Boolean(false)] {
+			assert!(matches!(
+				t.check("p", &value),
+				Err(NodeParameterError::BadType { parameter }) if parameter == "p"
+			));
+		}
+	}
+
+	#[test]
+	fn checks_nested_values() {
+		let t = NodeParameterType::List {
+			item_type: Box::new(NodeParameterType::Union {
+				types: vec![NodeParameterType::Integer, NodeParameterType::DataType],
+			}),
+		};
+
+		let ok = NodeParameterValue::List(vec![NodeParameterValue::Integer(1), string("Text")]);
+		assert!(t.check("p", &ok).is_ok());
+
+		let bad = NodeParameterValue::List(vec![NodeParameterValue::Integer(1), string("Txt")]);
+		assert!(t.check("p", &bad).is_err());
+	}
+}
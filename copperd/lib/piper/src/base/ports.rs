@@ -0,0 +1,65 @@
+use serde::Serialize;
+use smartstring::{LazyCompact, SmartString};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+use super::PortName;
+use crate::data::PipeDataStub;
+
+/// The type of data a node port carries
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(tag = "port_type", content = "value")]
+pub enum NodePortType {
+	/// This port carries exactly this type of data
+	Exact(PipeDataStub),
+
+	/// This port accepts (or produces) data of any type
+	Any,
+
+	/// A type variable.
+	///
+	/// All ports of one node that use the same generic name
+	/// must carry the same type. The concrete type is inferred
+	/// from the edges connected to this node's inputs.
+	#[schema(value_type = String)]
+	Generic(SmartString<LazyCompact>),
+}
+
+/// A description of one input port
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct NodeInputSpec {
+	/// The type of data this port accepts
+	pub port_type: NodePortType,
+
+	/// If true, this port may be left unconnected
+	pub is_optional: bool,
+}
+
+/// The input ports a node accepts
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "inputs_type", content = "ports")]
+pub enum NodeInputs {
+	/// This node has exactly these inputs
+	#[schema(value_type = BTreeMap<String, NodeInputSpec>)]
+	Fixed(BTreeMap<PortName, NodeInputSpec>),
+
+	/// This node's inputs can only be determined when it runs
+	/// (for example, they depend on the contents of the item db).
+	///
+	/// Any connection is accepted at build time, and the node
+	/// checks its input itself.
+	Dynamic,
+}
+
+/// A description of all of a node's ports
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct NodePorts {
+	/// This node's inputs
+	pub inputs: NodeInputs,
+
+	/// This node's outputs.
+	///
+	/// A node may leave any output empty when it runs.
+	#[schema(value_type = BTreeMap<String, NodePortType>)]
+	pub outputs: BTreeMap<PortName, NodePortType>,
+}
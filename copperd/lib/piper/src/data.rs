@@ -1,7 +1,9 @@
-use copper_itemdb::{AttrData, ClassId, ItemId};
+use copper_itemdb::{AttrData, AttrDataStub, ClassId, ItemId};
 use copper_util::HashType;
+use serde::{Deserialize, Serialize};
 use smartstring::{LazyCompact, SmartString};
 use std::fmt::Debug;
+use utoipa::ToSchema;
 
 use crate::helpers::processor::BytesProcessorBuilder;
 
@@ -55,6 +57,143 @@ pub enum PipeData {
 	},
 }
 
+impl PipeData {
+	/// Convert this data instance to its type
+	pub fn as_stub(&self) -> PipeDataStub {
+		match self {
+			Self::Blob { .. } => PipeDataStub::Blob,
+			Self::Boolean { .. } => PipeDataStub::Boolean,
+			Self::Text { .. } => PipeDataStub::Text,
+
+			Self::Float {
+				is_non_negative, ..
+			} => PipeDataStub::Float {
+				is_non_negative: *is_non_negative,
+			},
+
+			Self::Integer {
+				is_non_negative, ..
+			} => PipeDataStub::Integer {
+				is_non_negative: *is_non_negative,
+			},
+
+			Self::Hash { hash_type, .. } => PipeDataStub::Hash {
+				hash_type: *hash_type,
+			},
+
+			Self::Reference { class, .. } => PipeDataStub::Reference { class: *class },
+		}
+	}
+
+	/// Convert this data so that it may be sent to a port of type `stub`.
+	/// Returns `None` if this data doesn't fit that port.
+	///
+	/// Ports only check `is_non_negative` at runtime (see [`PipeDataStub::same_kind`]),
+	/// so integers and floats are accepted if their value is valid.
+	pub fn conform_to(self, stub: PipeDataStub) -> Option<Self> {
+		return match (self, stub) {
+			(Self::Integer { value, .. }, PipeDataStub::Integer { is_non_negative }) => {
+				if is_non_negative && value < 0 {
+					None
+				} else {
+					Some(Self::Integer {
+						value,
+						is_non_negative,
+					})
+				}
+			}
+
+			(Self::Float { value, .. }, PipeDataStub::Float { is_non_negative }) => {
+				if is_non_negative && value < 0.0 {
+					None
+				} else {
+					Some(Self::Float {
+						value,
+						is_non_negative,
+					})
+				}
+			}
+
+			(x, stub) => {
+				if x.as_stub() == stub {
+					Some(x)
+				} else {
+					None
+				}
+			}
+		};
+	}
+}
+
+/// The type of a piece of pipeline data.
+/// Each of these corresponds to a variant of [`PipeData`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type")]
+pub enum PipeDataStub {
+	/// Plain text
+	Text,
+
+	/// Binary data, in any format
+	Blob,
+
+	/// An integer
+	Integer {
+		/// If true, this integer must be non-negative
+		is_non_negative: bool,
+	},
+
+	/// A float
+	Float {
+		/// If true, this float must be non-negative
+		is_non_negative: bool,
+	},
+
+	/// A boolean
+	Boolean,
+
+	/// A checksum
+	Hash {
+		/// The type of this hash
+		hash_type: HashType,
+	},
+
+	/// A reference to an item
+	Reference {
+		/// The class we reference
+		#[schema(value_type = i64)]
+		class: ClassId,
+	},
+}
+
+impl PipeDataStub {
+	/// Do `self` and `other` describe the same kind of data?
+	///
+	/// This is used to check edges when a pipeline is built.
+	/// Integers and floats match regardless of `is_non_negative`,
+	/// since we can only check that when we see a value.
+	pub fn same_kind(&self, other: &Self) -> bool {
+		return match (self, other) {
+			(Self::Integer { .. }, Self::Integer { .. }) => true,
+			(Self::Float { .. }, Self::Float { .. }) => true,
+			(a, b) => a == b,
+		};
+	}
+}
+
+impl From<AttrDataStub> for PipeDataStub {
+	fn from(value: AttrDataStub) -> Self {
+		return match value {
+			AttrDataStub::Text => Self::Text,
+			AttrDataStub::Blob => Self::Blob,
+			AttrDataStub::Boolean => Self::Boolean,
+			AttrDataStub::Integer { is_non_negative } => Self::Integer { is_non_negative },
+			AttrDataStub::Float { is_non_negative } => Self::Float { is_non_negative },
+			AttrDataStub::Hash { hash_type } => Self::Hash { hash_type },
+			AttrDataStub::Reference { class } => Self::Reference { class },
+		};
+	}
+}
+
 impl TryFrom<AttrData> for PipeData {
 	type Error = ();
 
@@ -38,6 +38,26 @@ impl BytesProcessorBuilder {
 		return self;
 	}
 
+	/// The type of data this builder produces,
+	/// if we can tell without reading any data.
+	pub fn known_mime(&self) -> Option<&MimeType> {
+		match &self.source {
+			RawBytesSource::Array { mime, .. } if self.builders.is_empty() => Some(mime),
+			_ => None,
+		}
+	}
+
+	/// The number of bytes this builder produces,
+	/// if we can tell without reading any data.
+	pub fn known_size(&self) -> Option<u64> {
+		match &self.source {
+			RawBytesSource::Array { data, .. } if self.builders.is_empty() => {
+				Some(data.len().try_into().unwrap())
+			}
+			_ => None,
+		}
+	}
+
 	pub async fn build(&self, ctx: &CopperContext<'_>) -> Result<BytesProcessor, RunNodeError> {
 		let mut tasks = JoinSet::new();
 		let max_buffer_size = ctx.stream_fragment_size;
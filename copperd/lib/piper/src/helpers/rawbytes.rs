@@ -42,7 +42,7 @@ impl RawBytesSourceReader {
 				ctx.objectstore_client
 					.create_reader(&bucket, &key)
 					.await
-					.map_err(|e| RunNodeError::Other(Arc::new(e)))?,
+					.map_err(|e| RunNodeError::ObjectStoreError(Arc::new(e)))?,
 			),
 		});
 	}
@@ -72,7 +72,7 @@ impl RawBytesSourceReader {
 				let l = reader
 					.read(&mut read_buf)
 					.await
-					.map_err(|e| RunNodeError::Other(Arc::new(e)))?;
+					.map_err(|e| RunNodeError::ObjectStoreError(Arc::new(e)))?;
 
 				read_buf.truncate(l);
 				return Ok(Some(Arc::new(read_buf)));
@@ -1,4 +1,7 @@
-use crate::base::{NodeId, NodeParameterValue, PortName};
+use crate::{
+	base::{NodeId, NodeParameterValue, PortName},
+	retry::RetryPolicy,
+};
 use serde::{Deserialize, Serialize};
 use smartstring::{LazyCompact, SmartString};
 use std::{collections::BTreeMap, fmt::Debug};
@@ -16,6 +19,13 @@ pub struct PipelineJson {
 	/// Edges in this pipeline
 	#[schema(value_type = BTreeMap<String, EdgeJson>)]
 	pub edges: BTreeMap<SmartString<LazyCompact>, EdgeJson>,
+
+	/// How jobs that run this pipeline are retried if they fail.
+	/// If this is `None`, jobs are never retried.
+	///
+	/// A request to run this pipeline may override this policy.
+	#[serde(default)]
+	pub retry: Option<RetryPolicy>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
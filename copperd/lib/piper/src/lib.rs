@@ -2,6 +2,9 @@ pub mod base;
 pub mod data;
 pub mod helpers;
 pub mod json;
+pub mod retry;
+pub mod trace;
+pub mod validate;
 
 use copper_itemdb::{client::ItemdbClient, UserId};
 use copper_util::s3client::S3Client;
@@ -9,6 +12,7 @@ use smartstring::{LazyCompact, SmartString};
 use sqlx::{Postgres, Transaction};
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
 
 pub struct CopperContext<'a> {
 	/// The index of the runner running this job
@@ -43,4 +47,11 @@ pub struct CopperContext<'a> {
 	/// This makes sure pipelines are atomic.
 	/// We `.commit()` only if the pipeline runs successfully.
 	pub item_db_transaction: Mutex<Transaction<'a, Postgres>>,
+
+	/// Cancelled when this job should stop.
+	///
+	/// The job is checked for cancellation between nodes,
+	/// long-running nodes may also watch this token themselves.
+	/// A cancelled job is never committed.
+	pub cancel: CancellationToken,
 }
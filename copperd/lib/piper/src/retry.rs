@@ -0,0 +1,260 @@
+//! Policies for retrying failed jobs
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::base::{RunNodeError, RunNodeErrorKind};
+
+/// How long to wait between attempts of a job
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type")]
+pub enum RetryBackoff {
+	/// Always wait the same amount of time
+	Fixed {
+		/// How long to wait, in seconds
+		delay_secs: u64,
+	},
+
+	/// Double the delay after every attempt
+	Exponential {
+		/// How long to wait after the first attempt, in seconds
+		initial_delay_secs: u64,
+
+		/// Never wait longer than this many seconds
+		max_delay_secs: u64,
+	},
+}
+
+impl RetryBackoff {
+	/// How long to wait after the given attempt failed.
+	/// The first attempt is attempt 1.
+	///
+	/// This is never longer than [`RetryPolicy::MAX_DELAY_SECS`].
+	pub fn delay(&self, attempt: u32) -> Duration {
+		let secs = match self {
+			Self::Fixed { delay_secs } => *delay_secs,
+			Self::Exponential {
+				initial_delay_secs,
+				max_delay_secs,
+			} => initial_delay_secs
+				.saturating_mul(2u64.saturating_pow(attempt.saturating_sub(1)))
+				.min(*max_delay_secs),
+		};
+
+		return Duration::from_secs(secs.min(RetryPolicy::MAX_DELAY_SECS));
+	}
+
+	/// The longest delay this backoff may produce, in seconds
+	fn max_delay_secs(&self) -> u64 {
+		return match self {
+			Self::Fixed { delay_secs } => *delay_secs,
+			Self::Exponential {
+				initial_delay_secs,
+				max_delay_secs,
+			} => (*initial_delay_secs).max(*max_delay_secs),
+		};
+	}
+}
+
+/// An error we can encounter when checking a [`RetryPolicy`]
+#[derive(Debug, Error)]
+pub enum RetryPolicyError {
+	/// This policy allows too many attempts
+	#[error("jobs may be attempted at most {max} times")]
+	TooManyAttempts { max: u32 },
+
+	/// This policy waits too long between attempts
+	#[error("retry delays may be at most {max} seconds")]
+	DelayTooLong { max: u64 },
+}
+
+/// Describes when and how a failed job should be run again
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RetryPolicy {
+	/// The maximum number of times we'll run a job,
+	/// including the first attempt. `0` and `1` never retry.
+	pub max_attempts: u32,
+
+	/// How long to wait between attempts
+	pub backoff: RetryBackoff,
+
+	/// The kinds of errors we retry after.
+	/// Any other error fails the job immediately.
+	#[serde(default = "RetryPolicy::default_retry_on")]
+	pub retry_on: Vec<RunNodeErrorKind>,
+}
+
+impl RetryPolicy {
+	/// The most attempts a policy may allow
+	pub const MAX_ATTEMPTS: u32 = 100;
+
+	/// The longest a policy may wait between attempts, in seconds (30 days)
+	pub const MAX_DELAY_SECS: u64 = 30 * 24 * 60 * 60;
+
+	/// Make sure this policy is within our limits.
+	/// Policies are checked wherever users provide them.
+	pub fn check(&self) -> Result<(), RetryPolicyError> {
+		if self.max_attempts > Self::MAX_ATTEMPTS {
+			return Err(RetryPolicyError::TooManyAttempts {
+				max: Self::MAX_ATTEMPTS,
+			});
+		}
+
+		if self.backoff.max_delay_secs() > Self::MAX_DELAY_SECS {
+			return Err(RetryPolicyError::DelayTooLong {
+				max: Self::MAX_DELAY_SECS,
+			});
+		}
+
+		return Ok(());
+	}
+
+	fn default_retry_on() -> Vec<RunNodeErrorKind> {
+		vec![
+			RunNodeErrorKind::Database,
+			RunNodeErrorKind::ObjectStore,
+			RunNodeErrorKind::Io,
+		]
+	}
+
+	/// Decide if a job should run again after its
+	/// `attempt`th attempt failed with `error`.
+	///
+	/// Returns how long we should wait before the next attempt,
+	/// or `None` if this job should not be retried.
+	pub fn retry_after(&self, attempt: u32, error: &RunNodeError) -> Option<Duration> {
+		let kind = error.kind()?;
+		if !self.retry_on.contains(&kind) {
+			return None;
+		}
+
+		return self.retry_after_lost(attempt);
+	}
+
+	/// Like [`RetryPolicy::retry_after`], but for attempts that were lost
+	/// without an error (e.g, because the runner running them crashed).
+	/// These are always retried if we have attempts left.
+	pub fn retry_after_lost(&self, attempt: u32) -> Option<Duration> {
+		if attempt >= self.max_attempts {
+			return None;
+		}
+
+		return Some(self.backoff.delay(attempt));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+
+	use super::*;
+
+	fn policy(max_attempts: u32, backoff: RetryBackoff) -> RetryPolicy {
+		RetryPolicy {
+			max_attempts,
+			backoff,
+			retry_on: RetryPolicy::default_retry_on(),
+		}
+	}
+
+	#[test]
+	fn fixed_delay() {
+		let b = RetryBackoff::Fixed { delay_secs: 5 };
+		for attempt in [1, 2, 10, u32::MAX] {
+			assert_eq!(b.delay(attempt), Duration::from_secs(5));
+		}
+	}
+
+	#[test]
+	fn exponential_delay() {
+		let b = RetryBackoff::Exponential {
+			initial_delay_secs: 3,
+			max_delay_secs: 20,
+		};
+
+		let delays: Vec<u64> = (0..7).map(|x| b.delay(x).as_secs()).collect();
+		assert_eq!(delays, [3, 3, 6, 12, 20, 20, 20]);
+		assert_eq!(b.delay(u32::MAX), Duration::from_secs(20));
+	}
+
+	#[test]
+	fn delay_is_capped() {
+		let b = RetryBackoff::Exponential {
+			initial_delay_secs: u64::MAX,
+			max_delay_secs: u64::MAX,
+		};
+		let max = Duration::from_secs(RetryPolicy::MAX_DELAY_SECS);
+
+		assert_eq!(b.delay(u32::MAX), max);
+		assert_eq!(
+			RetryBackoff::Fixed {
+				delay_secs: u64::MAX
+			}
+			.delay(1),
+			max
+		);
+	}
+
+	#[test]
+	fn retries_until_out_of_attempts() {
+		let p = policy(3, RetryBackoff::Fixed { delay_secs: 1 });
+		let error = RunNodeError::from(std::io::Error::other("test"));
+
+		assert_eq!(p.retry_after(1, &error), Some(Duration::from_secs(1)));
+		assert_eq!(p.retry_after(2, &error), Some(Duration::from_secs(1)));
+		assert_eq!(p.retry_after(3, &error), None);
+		assert_eq!(policy(0, p.backoff.clone()).retry_after(1, &error), None);
+	}
+
+	#[test]
+	fn retries_only_some_errors() {
+		let p = policy(3, RetryBackoff::Fixed { delay_secs: 1 });
+
+		// Not in `retry_on`
+		let error = RunNodeError::Other(Arc::new(std::io::Error::other("test")));
+		assert_eq!(p.retry_after(1, &error), None);
+
+		// Never retried
+		let error = RunNodeError::NotAuthorized {
+			message: "test".into(),
+		};
+		assert_eq!(p.retry_after(1, &error), None);
+		assert_eq!(p.retry_after(1, &RunNodeError::Cancelled), None);
+
+		// Lost attempts don't have an error
+		assert_eq!(p.retry_after_lost(1), Some(Duration::from_secs(1)));
+		assert_eq!(p.retry_after_lost(3), None);
+	}
+
+	#[test]
+	fn checks_limits() {
+		let ok = policy(
+			RetryPolicy::MAX_ATTEMPTS,
+			RetryBackoff::Fixed {
+				delay_secs: RetryPolicy::MAX_DELAY_SECS,
+			},
+		);
+		assert!(ok.check().is_ok());
+
+		let attempts = policy(RetryPolicy::MAX_ATTEMPTS + 1, ok.backoff.clone());
+		assert!(matches!(
+			attempts.check(),
+			Err(RetryPolicyError::TooManyAttempts { .. })
+		));
+
+		let delay = policy(
+			3,
+			RetryBackoff::Exponential {
+				initial_delay_secs: 1,
+				max_delay_secs: u64::MAX,
+			},
+		);
+		assert!(matches!(
+			delay.check(),
+			Err(RetryPolicyError::DelayTooLong { .. })
+		));
+	}
+}
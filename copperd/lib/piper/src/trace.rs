@@ -0,0 +1,107 @@
+//! Records of what happened while a pipeline ran
+
+use copper_util::MimeType;
+use serde::{Deserialize, Serialize};
+use smartstring::{LazyCompact, SmartString};
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+
+use crate::{
+	base::{NodeId, PortName},
+	data::{PipeData, PipeDataStub},
+};
+
+/// The state of one node in a pipeline run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "status")]
+pub enum NodeTraceStatus {
+	/// This node never started
+	NotStarted,
+
+	/// This node was running, and was stopped
+	/// because another node failed or the job was cancelled.
+	Aborted,
+
+	/// This node finished successfully
+	Success,
+
+	/// This node failed
+	Failed {
+		/// The error this node returned
+		message: String,
+	},
+}
+
+/// A summary of a piece of data a node produced.
+/// We never store the data itself.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PipeDataSummary {
+	/// The type of this data
+	pub data_type: PipeDataStub,
+
+	/// The size of this data in bytes, if it is easy to get
+	pub size: Option<u64>,
+
+	/// The mime type of this data, if it is a blob and it is easy to get
+	#[schema(value_type = Option<String>)]
+	pub mime: Option<MimeType>,
+}
+
+impl From<&PipeData> for PipeDataSummary {
+	fn from(value: &PipeData) -> Self {
+		let (size, mime) = match value {
+			PipeData::Text { value } => (Some(value.len().try_into().unwrap()), None),
+			PipeData::Hash { data, .. } => (Some(data.len().try_into().unwrap()), None),
+			PipeData::Blob { source } => (source.known_size(), source.known_mime().cloned()),
+			_ => (None, None),
+		};
+
+		return Self {
+			data_type: value.as_stub(),
+			size,
+			mime,
+		};
+	}
+}
+
+/// A record of one node in a pipeline run
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NodeTrace {
+	/// The node's id
+	#[schema(value_type = String)]
+	pub node_id: NodeId,
+
+	/// This node's type
+	#[schema(value_type = String)]
+	pub node_type: SmartString<LazyCompact>,
+
+	/// When this node started running
+	#[schema(value_type = Option<String>)]
+	pub started_at: Option<OffsetDateTime>,
+
+	/// When this node stopped running
+	#[schema(value_type = Option<String>)]
+	pub finished_at: Option<OffsetDateTime>,
+
+	/// What happened to this node
+	pub status: NodeTraceStatus,
+
+	/// The data this node produced on each of its output ports
+	#[schema(value_type = BTreeMap<String, PipeDataSummary>)]
+	pub outputs: BTreeMap<PortName, PipeDataSummary>,
+}
+
+impl NodeTrace {
+	/// Make a trace for a node that hasn't started yet
+	pub fn new(node_id: NodeId, node_type: SmartString<LazyCompact>) -> Self {
+		return Self {
+			node_id,
+			node_type,
+			started_at: None,
+			finished_at: None,
+			status: NodeTraceStatus::NotStarted,
+			outputs: BTreeMap::new(),
+		};
+	}
+}
@@ -0,0 +1,763 @@
+//! Static checks for pipelines
+
+use serde::Serialize;
+use smartstring::{LazyCompact, SmartString};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::{
+	base::{
+		NodeDispatcher, NodeId, NodeInputs, NodeParameterValue, NodePortType, NodePorts, PortName,
+		INPUT_NODE_TYPE,
+	},
+	data::PipeDataStub,
+	json::{EdgeJson, PipelineJson},
+};
+
+/// A problem we found while checking a pipeline.
+/// Most problems are tied to the node or edge that caused them.
+#[derive(Debug, Clone, Error, Serialize, ToSchema)]
+#[serde(tag = "type")]
+pub enum PipelineProblem {
+	/// An edge references a node that doesn't exist
+	#[error("edge `{edge}` references a node `{node}` that doesn't exist")]
+	NoNode {
+		#[schema(value_type = String)]
+		edge: SmartString<LazyCompact>,
+		#[schema(value_type = String)]
+		node: NodeId,
+	},
+
+	/// A node has a type we don't know about
+	#[error("node `{node}` has an invalid type `{node_type}`")]
+	BadNodeType {
+		#[schema(value_type = String)]
+		node: NodeId,
+		#[schema(value_type = String)]
+		node_type: SmartString<LazyCompact>,
+	},
+
+	/// A node is part of a cycle
+	#[error("node `{node}` is part of a cycle")]
+	InCycle {
+		#[schema(value_type = String)]
+		node: NodeId,
+	},
+
+	/// An input node doesn't have a valid `input_name` parameter
+	#[error("input node `{node}` is invalid")]
+	InvalidInputNode {
+		#[schema(value_type = String)]
+		node: NodeId,
+	},
+
+	/// A node's parameters don't match its spec
+	#[error("invalid parameters for node `{node}`: {message}")]
+	InvalidParameter {
+		#[schema(value_type = String)]
+		node: NodeId,
+		#[schema(value_type = String)]
+		parameter: SmartString<LazyCompact>,
+		message: String,
+	},
+
+	/// We couldn't get a node's ports from its parameters
+	#[error("could not get the ports of node `{node}`: {message}")]
+	BadPorts {
+		#[schema(value_type = String)]
+		node: NodeId,
+		message: String,
+	},
+
+	/// An edge is connected to an input port that doesn't exist
+	#[error("edge `{edge}` is connected to node `{node}`, which has no input port `{port}`")]
+	UnknownInputPort {
+		#[schema(value_type = String)]
+		edge: SmartString<LazyCompact>,
+		#[schema(value_type = String)]
+		node: NodeId,
+		#[schema(value_type = String)]
+		port: PortName,
+	},
+
+	/// An edge is connected to an output port that doesn't exist
+	#[error("edge `{edge}` is connected to node `{node}`, which has no output port `{port}`")]
+	UnknownOutputPort {
+		#[schema(value_type = String)]
+		edge: SmartString<LazyCompact>,
+		#[schema(value_type = String)]
+		node: NodeId,
+		#[schema(value_type = String)]
+		port: PortName,
+	},
+
+	/// An input port is connected to more than one edge
+	#[error("edge `{edge}` is connected to input port `{port}` of node `{node}`, which is already connected")]
+	InputConnectedTwice {
+		#[schema(value_type = String)]
+		edge: SmartString<LazyCompact>,
+		#[schema(value_type = String)]
+		node: NodeId,
+		#[schema(value_type = String)]
+		port: PortName,
+	},
+
+	/// A required input port isn't connected to anything
+	#[error("required input port `{port}` of node `{node}` is not connected")]
+	UnconnectedInput {
+		#[schema(value_type = String)]
+		node: NodeId,
+		#[schema(value_type = String)]
+		port: PortName,
+	},
+
+	/// An edge connects two ports with different types
+	#[error("edge `{edge}` sends {got:?} to input port `{port}` of node `{node}`, which expects {expected:?}")]
+	TypeMismatch {
+		#[schema(value_type = String)]
+		edge: SmartString<LazyCompact>,
+		#[schema(value_type = String)]
+		node: NodeId,
+		#[schema(value_type = String)]
+		port: PortName,
+		expected: PipeDataStub,
+		got: PipeDataStub,
+	},
+
+	/// This pipeline's retry policy is invalid
+	#[error("invalid retry policy: {message}")]
+	InvalidRetryPolicy { message: String },
+}
+
+/// What we know about the outputs of a node we've checked
+enum NodeOutputs {
+	/// We don't know this node's outputs (e.g, it has an invalid type).
+	/// Edges starting at this node are not checked.
+	Unknown,
+
+	/// Every output port carries data of this type.
+	/// This is used for input nodes.
+	All(Option<PipeDataStub>),
+
+	/// This node has exactly these output ports.
+	/// `None` means that a port's type is unknown, and cannot be checked.
+	Ports(BTreeMap<PortName, Option<PipeDataStub>>),
+}
+
+impl PipelineJson {
+	/// Check this pipeline for problems, returning all problems we find.
+	/// A pipeline with no problems should build successfully in piper.
+	///
+	/// `input_types` maps the names of pipeline inputs to the types of the
+	/// values we'll provide. These are only known when a job is started;
+	/// if an input is missing here, we use the input node's `input_type` parameter.
+	pub fn validate(
+		&self,
+		dispatcher: &NodeDispatcher,
+		input_types: &BTreeMap<SmartString<LazyCompact>, PipeDataStub>,
+	) -> Vec<PipelineProblem> {
+		let mut problems = Vec::new();
+
+		if let Some(Err(error)) = self.retry.as_ref().map(|x| x.check()) {
+			problems.push(PipelineProblem::InvalidRetryPolicy {
+				message: format!("{error}"),
+			});
+		}
+
+		//
+		// MARK: Nodes
+		//
+
+		// The ports of every node we could get ports for.
+		// Input nodes are not included.
+		let mut node_ports: BTreeMap<&NodeId, NodePorts> = BTreeMap::new();
+
+		// The type of data each input node provides
+		let mut input_node_types: BTreeMap<&NodeId, Option<PipeDataStub>> = BTreeMap::new();
+
+		for (node_id, node) in &self.nodes {
+			if node.node_type == INPUT_NODE_TYPE {
+				match node.params.get("input_name") {
+					Some(NodeParameterValue::String(input_name)) => {
+						let t = input_types
+							.get(input_name)
+							.copied()
+							.or_else(|| Self::input_node_type(&node.params));
+						input_node_types.insert(node_id, t);
+					}
+					_ => problems.push(PipelineProblem::InvalidInputNode {
+						node: node_id.clone(),
+					}),
+				}
+				continue;
+			}
+
+			match dispatcher.check_params(&node.node_type, &node.params) {
+				None => {
+					problems.push(PipelineProblem::BadNodeType {
+						node: node_id.clone(),
+						node_type: node.node_type.clone(),
+					});
+					continue;
+				}
+
+				Some(Err(error)) => {
+					problems.push(PipelineProblem::InvalidParameter {
+						node: node_id.clone(),
+						parameter: error.parameter().clone(),
+						message: format!("{error}"),
+					});
+					continue;
+				}
+
+				Some(Ok(())) => {}
+			}
+
+			match dispatcher
+				.node_ports(&node.node_type, &node.params)
+				.unwrap()
+			{
+				Ok(ports) => {
+					node_ports.insert(node_id, ports);
+				}
+				Err(error) => problems.push(PipelineProblem::BadPorts {
+					node: node_id.clone(),
+					message: format!("{error}"),
+				}),
+			}
+		}
+
+		//
+		// MARK: Edges
+		//
+
+		// Edges that connect two nodes that exist
+		let mut edges: Vec<(&SmartString<LazyCompact>, &EdgeJson)> = Vec::new();
+		for (edge_id, edge) in &self.edges {
+			let mut is_valid = true;
+			for node in [&edge.source.node, &edge.target.node] {
+				if !self.nodes.contains_key(node) {
+					problems.push(PipelineProblem::NoNode {
+						edge: edge_id.clone(),
+						node: node.clone(),
+					});
+					is_valid = false;
+				}
+			}
+
+			if is_valid {
+				edges.push((edge_id, edge));
+			}
+		}
+
+		//
+		// MARK: Cycles
+		//
+
+		let order = match self.topological_order(&edges) {
+			Ok(order) => order,
+			Err(in_cycle) => {
+				// We can't infer types in a graph with cycles,
+				// so we stop here.
+				problems.extend(
+					in_cycle
+						.into_iter()
+						.map(|node| PipelineProblem::InCycle { node: node.clone() }),
+				);
+				return problems;
+			}
+		};
+
+		//
+		// MARK: Ports & types
+		//
+
+		let mut outputs: HashMap<&NodeId, NodeOutputs> = HashMap::new();
+		for node_id in order {
+			let empty_inputs = NodeInputs::Fixed(BTreeMap::new());
+			let inputs = if self.nodes.get(node_id).unwrap().node_type == INPUT_NODE_TYPE {
+				outputs.insert(
+					node_id,
+					match input_node_types.get(node_id) {
+						Some(t) => NodeOutputs::All(*t),
+						None => NodeOutputs::Unknown,
+					},
+				);
+
+				// Input nodes never have inputs
+				&empty_inputs
+			} else {
+				match node_ports.get(node_id) {
+					Some(ports) => &ports.inputs,
+					None => {
+						// This node is invalid, and we've already
+						// made a problem for it. Don't check its edges.
+						outputs.insert(node_id, NodeOutputs::Unknown);
+						continue;
+					}
+				}
+			};
+
+			// The types we've inferred for this node's generic ports
+			let mut generics: BTreeMap<SmartString<LazyCompact>, PipeDataStub> = BTreeMap::new();
+			let mut connected_inputs = BTreeSet::new();
+
+			for (edge_id, edge) in edges.iter().filter(|(_, e)| &e.target.node == node_id) {
+				// Record this input before checking the edge's source,
+				// so a bad edge doesn't also leave its input "unconnected".
+				if !connected_inputs.insert(&edge.target.port) {
+					problems.push(PipelineProblem::InputConnectedTwice {
+						edge: (*edge_id).clone(),
+						node: node_id.clone(),
+						port: edge.target.port.clone(),
+					});
+					continue;
+				}
+
+				let got = match outputs.get(&edge.source.node).unwrap() {
+					NodeOutputs::Unknown => None,
+					NodeOutputs::All(t) => *t,
+					NodeOutputs::Ports(ports) => match ports.get(&edge.source.port) {
+						Some(t) => *t,
+						None => {
+							problems.push(PipelineProblem::UnknownOutputPort {
+								edge: (*edge_id).clone(),
+								node: edge.source.node.clone(),
+								port: edge.source.port.clone(),
+							});
+							continue;
+						}
+					},
+				};
+
+				let expected = match inputs {
+					// Nodes with dynamic inputs check their inputs themselves
+					NodeInputs::Dynamic => continue,
+					NodeInputs::Fixed(inputs) => match inputs.get(&edge.target.port) {
+						Some(x) => &x.port_type,
+						None => {
+							problems.push(PipelineProblem::UnknownInputPort {
+								edge: (*edge_id).clone(),
+								node: node_id.clone(),
+								port: edge.target.port.clone(),
+							});
+							continue;
+						}
+					},
+				};
+
+				let got = match got {
+					Some(x) => x,
+					None => continue,
+				};
+
+				let expected = match expected {
+					NodePortType::Any => continue,
+					NodePortType::Exact(x) => *x,
+					NodePortType::Generic(name) => *generics.entry(name.clone()).or_insert(got),
+				};
+
+				// Non-negative numbers are checked when the pipeline runs
+				if !expected.same_kind(&got) {
+					problems.push(PipelineProblem::TypeMismatch {
+						edge: (*edge_id).clone(),
+						node: node_id.clone(),
+						port: edge.target.port.clone(),
+						expected,
+						got,
+					});
+				}
+			}
+
+			if let NodeInputs::Fixed(inputs) = inputs {
+				for (port, spec) in inputs {
+					if !spec.is_optional && !connected_inputs.contains(port) {
+						problems.push(PipelineProblem::UnconnectedInput {
+							node: node_id.clone(),
+							port: port.clone(),
+						});
+					}
+				}
+			}
+
+			if let Some(ports) = node_ports.get(node_id) {
+				let resolved = ports
+					.outputs
+					.iter()
+					.map(|(port, port_type)| {
+						let t = match port_type {
+							NodePortType::Exact(x) => Some(*x),
+							NodePortType::Any => None,
+							NodePortType::Generic(name) => generics.get(name).copied(),
+						};
+						(port.clone(), t)
+					})
+					.collect();
+
+				outputs.insert(node_id, NodeOutputs::Ports(resolved));
+			}
+		}
+
+		return problems;
+	}
+
+	/// Guess the type of data an input node provides from its `input_type` parameter.
+	/// Returns `None` if that parameter doesn't fully determine a type.
+	fn input_node_type(
+		params: &BTreeMap<SmartString<LazyCompact>, NodeParameterValue>,
+	) -> Option<PipeDataStub> {
+		return match params.get("input_type") {
+			Some(NodeParameterValue::String(s)) => match s.as_str() {
+				"Text" => Some(PipeDataStub::Text),
+				"Blob" => Some(PipeDataStub::Blob),
+				"Boolean" => Some(PipeDataStub::Boolean),
+				_ => None,
+			},
+			_ => None,
+		};
+	}
+
+	/// Sort this pipeline's nodes so that every node comes after all nodes it depends on.
+	/// `edges` must only contain edges between nodes that exist.
+	///
+	/// If this pipeline has cycles, returns the nodes that are part of a cycle.
+	fn topological_order<'a>(
+		&'a self,
+		edges: &[(&SmartString<LazyCompact>, &'a EdgeJson)],
+	) -> Result<Vec<&'a NodeId>, Vec<&'a NodeId>> {
+		let mut in_degree: BTreeMap<&NodeId, usize> = self.nodes.keys().map(|x| (x, 0)).collect();
+		for (_, edge) in edges {
+			*in_degree.get_mut(&edge.target.node).unwrap() += 1;
+		}
+
+		let mut ready: VecDeque<&NodeId> = in_degree
+			.iter()
+			.filter(|(_, d)| **d == 0)
+			.map(|(x, _)| *x)
+			.collect();
+
+		let mut order = Vec::new();
+		while let Some(node) = ready.pop_front() {
+			order.push(node);
+			for (_, edge) in edges.iter().filter(|(_, e)| &e.source.node == node) {
+				let d = in_degree.get_mut(&edge.target.node).unwrap();
+				*d -= 1;
+				if *d == 0 {
+					ready.push_back(&edge.target.node);
+				}
+			}
+		}
+
+		if order.len() == self.nodes.len() {
+			return Ok(order);
+		}
+
+		// Nodes we couldn't sort are either part of a cycle
+		// or depend on one. Remove those that only depend on a cycle.
+		let mut remaining: BTreeSet<&NodeId> =
+			self.nodes.keys().filter(|x| !order.contains(x)).collect();
+
+		loop {
+			let dead_ends: Vec<&NodeId> = remaining
+				.iter()
+				.filter(|node| {
+					!edges.iter().any(|(_, e)| {
+						&&e.source.node == *node && remaining.contains(&e.target.node)
+					})
+				})
+				.copied()
+				.collect();
+
+			if dead_ends.is_empty() {
+				break;
+			}
+
+			for node in dead_ends {
+				remaining.remove(node);
+			}
+		}
+
+		return Err(remaining.into_iter().collect());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use async_trait::async_trait;
+
+	use super::*;
+	use crate::{
+		base::{Node, NodeBuilder, NodeInputSpec, RunNodeError, ThisNodeInfo},
+		data::PipeData,
+		helpers::NodeParameters,
+		CopperContext,
+	};
+
+	/// A node that is only checked, never run
+	struct TestNode {
+		inputs: Vec<(&'static str, NodePortType, bool)>,
+		outputs: Vec<(&'static str, NodePortType)>,
+	}
+
+	struct NeverRuns {}
+
+	#[async_trait]
+	impl<'ctx> Node<'ctx> for NeverRuns {
+		async fn run(
+			&self,
+			_ctx: &CopperContext<'ctx>,
+			_this_node: ThisNodeInfo,
+			_params: NodeParameters,
+			_input: BTreeMap<PortName, Option<PipeData>>,
+		) -> Result<BTreeMap<PortName, PipeData>, RunNodeError> {
+			unreachable!()
+		}
+	}
+
+	impl NodeBuilder for TestNode {
+		fn build<'ctx>(&self) -> Box<dyn Node<'ctx>> {
+			return Box::new(NeverRuns {});
+		}
+
+		fn ports(&self, _params: NodeParameters) -> Result<NodePorts, RunNodeError> {
+			return Ok(NodePorts {
+				inputs: NodeInputs::Fixed(
+					self.inputs
+						.iter()
+						.map(|(name, port_type, is_optional)| {
+							(
+								PortName::from(name.to_string()),
+								NodeInputSpec {
+									port_type: port_type.clone(),
+									is_optional: *is_optional,
+								},
+							)
+						})
+						.collect(),
+				),
+				outputs: self
+					.outputs
+					.iter()
+					.map(|(name, port_type)| (PortName::from(name.to_string()), port_type.clone()))
+					.collect(),
+			});
+		}
+	}
+
+	fn integer(is_non_negative: bool) -> NodePortType {
+		return NodePortType::Exact(PipeDataStub::Integer { is_non_negative });
+	}
+
+	fn dispatcher() -> NodeDispatcher {
+		let mut d = NodeDispatcher::new();
+		let nodes = [
+			(
+				"Number",
+				TestNode {
+					inputs: vec![],
+					outputs: vec![("out", integer(false))],
+				},
+			),
+			(
+				"Text",
+				TestNode {
+					inputs: vec![],
+					outputs: vec![("out", NodePortType::Exact(PipeDataStub::Text))],
+				},
+			),
+			(
+				"Count",
+				TestNode {
+					inputs: vec![("in", integer(true), false)],
+					outputs: vec![("out", integer(true))],
+				},
+			),
+			(
+				"Pass",
+				TestNode {
+					inputs: vec![("in", NodePortType::Generic("T".into()), false)],
+					outputs: vec![("out", NodePortType::Generic("T".into()))],
+				},
+			),
+		];
+
+		for (name, node) in nodes {
+			d.register_node(name, "", BTreeMap::new(), Box::new(node))
+				.unwrap();
+		}
+		return d;
+	}
+
+	/// Make a pipeline from `(id, type)` nodes
+	/// and `(source node, source port, target node, target port)` edges.
+	/// Edges are named `e0`, `e1`, ...
+	fn pipeline(nodes: &[(&str, &str)], edges: &[(&str, &str, &str, &str)]) -> PipelineJson {
+		let nodes: serde_json::Map<_, _> = nodes
+			.iter()
+			.map(|(id, node_type)| {
+				(
+					id.to_string(),
+					serde_json::json!({
+						"node_type": node_type,
+						"position": { "x": 0.0, "y": 0.0 },
+					}),
+				)
+			})
+			.collect();
+
+		let edges: serde_json::Map<_, _> = edges
+			.iter()
+			.enumerate()
+			.map(|(i, (source, source_port, target, target_port))| {
+				(
+					format!("e{i}"),
+					serde_json::json!({
+						"source": { "node": source, "port": source_port },
+						"target": { "node": target, "port": target_port },
+					}),
+				)
+			})
+			.collect();
+
+		return serde_json::from_value(serde_json::json!({ "nodes": nodes, "edges": edges }))
+			.unwrap();
+	}
+
+	fn check(p: &PipelineJson) -> Vec<PipelineProblem> {
+		return p.validate(&dispatcher(), &BTreeMap::new());
+	}
+
+	#[test]
+	fn accepts_valid_pipeline() {
+		let p = pipeline(
+			&[("n", "Number"), ("p", "Pass"), ("c", "Count")],
+			&[("n", "out", "p", "in"), ("p", "out", "c", "in")],
+		);
+		assert!(check(&p).is_empty());
+	}
+
+	#[test]
+	fn finds_cycles() {
+		// `c` depends on the cycle, but isn't part of it
+		let p = pipeline(
+			&[("a", "Pass"), ("b", "Pass"), ("c", "Count")],
+			&[
+				("a", "out", "b", "in"),
+				("b", "out", "a", "in"),
+				("b", "out", "c", "in"),
+			],
+		);
+
+		let in_cycle: Vec<String> = check(&p)
+			.into_iter()
+			.map(|x| match x {
+				PipelineProblem::InCycle { node } => node.to_string(),
+				x => panic!("unexpected problem {x:?}"),
+			})
+			.collect();
+		assert_eq!(in_cycle, ["a", "b"]);
+	}
+
+	#[test]
+	fn finds_unknown_nodes() {
+		let p = pipeline(
+			&[("n", "Number"), ("x", "NotANode")],
+			&[("n", "out", "missing", "in")],
+		);
+		let problems = check(&p);
+
+		assert_eq!(problems.len(), 2, "{problems:?}");
+		assert!(problems.iter().any(|x| matches!(
+			x,
+			PipelineProblem::BadNodeType { node, .. } if node.to_string() == "x"
+		)));
+		assert!(problems.iter().any(|x| matches!(
+			x,
+			PipelineProblem::NoNode { node, .. } if node.to_string() == "missing"
+		)));
+	}
+
+	#[test]
+	fn finds_unknown_output_port() {
+		let p = pipeline(
+			&[("n", "Number"), ("c", "Count")],
+			&[("n", "nope", "c", "in")],
+		);
+		let problems = check(&p);
+
+		// `c.in` has an edge, so it isn't also unconnected
+		assert_eq!(problems.len(), 1, "{problems:?}");
+		assert!(matches!(
+			&problems[0],
+			PipelineProblem::UnknownOutputPort { port, .. } if port.to_string() == "nope"
+		));
+	}
+
+	#[test]
+	fn finds_unknown_input_port() {
+		let p = pipeline(
+			&[("n", "Number"), ("c", "Count")],
+			&[("n", "out", "c", "nope")],
+		);
+		let problems = check(&p);
+
+		assert_eq!(problems.len(), 2, "{problems:?}");
+		assert!(matches!(
+			&problems[0],
+			PipelineProblem::UnknownInputPort { port, .. } if port.to_string() == "nope"
+		));
+		assert!(matches!(
+			&problems[1],
+			PipelineProblem::UnconnectedInput { port, .. } if port.to_string() == "in"
+		));
+	}
+
+	#[test]
+	fn finds_type_mismatch() {
+		// Through a generic node, too
+		let p = pipeline(
+			&[("t", "Text"), ("p", "Pass"), ("c", "Count")],
+			&[("t", "out", "p", "in"), ("p", "out", "c", "in")],
+		);
+		let problems = check(&p);
+
+		assert_eq!(problems.len(), 1, "{problems:?}");
+		assert!(matches!(
+			&problems[0],
+			PipelineProblem::TypeMismatch {
+				got: PipeDataStub::Text,
+				expected: PipeDataStub::Integer { .. },
+				..
+			}
+		));
+	}
+
+	#[test]
+	fn finds_unconnected_input() {
+		let p = pipeline(&[("c", "Count")], &[]);
+		let problems = check(&p);
+
+		assert_eq!(problems.len(), 1, "{problems:?}");
+		assert!(matches!(
+			&problems[0],
+			PipelineProblem::UnconnectedInput { node, port }
+				if node.to_string() == "c" && port.to_string() == "in"
+		));
+	}
+
+	#[test]
+	fn finds_input_connected_twice() {
+		let p = pipeline(
+			&[("a", "Number"), ("b", "Number"), ("c", "Count")],
+			&[("a", "out", "c", "in"), ("b", "out", "c", "in")],
+		);
+		let problems = check(&p);
+
+		assert_eq!(problems.len(), 1, "{problems:?}");
+		assert!(matches!(
+			&problems[0],
+			PipelineProblem::InputConnectedTwice { .. }
+		));
+	}
+}
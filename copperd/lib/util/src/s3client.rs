@@ -90,6 +90,20 @@ impl<E: std::error::Error + 'static + Send + Sync, R: std::fmt::Debug + 'static
 	}
 }
 
+#[derive(Debug, Error)]
+pub enum S3PutObjectError {
+	#[error("sdk error")]
+	SdkError(#[from] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl<E: std::error::Error + 'static + Send + Sync, R: std::fmt::Debug + 'static + Send + Sync>
+	From<SdkError<E, R>> for S3PutObjectError
+{
+	fn from(value: SdkError<E, R>) -> Self {
+		Self::SdkError(Box::new(value))
+	}
+}
+
 #[derive(Debug, Error)]
 pub enum S3CreateBucketError {
 	#[error("sdk error")]
@@ -217,6 +231,27 @@ impl<'a> S3Client {
 		});
 	}
 
+	/// Upload a whole object at once.
+	/// Use [`Self::create_multipart_upload`] for large objects.
+	pub async fn put_object(
+		&'a self,
+		bucket: &str,
+		key: &str,
+		mime: MimeType,
+		data: Vec<u8>,
+	) -> Result<(), S3PutObjectError> {
+		self.client
+			.put_object()
+			.bucket(bucket)
+			.key(key)
+			.content_type(&mime)
+			.body(ByteStream::from(data))
+			.send()
+			.await?;
+
+		return Ok(());
+	}
+
 	pub async fn delete_object(
 		&'a self,
 		bucket: &str,
@@ -0,0 +1,16 @@
+//! Every node copper provides, registered in one place.
+//!
+//! Both piper (which runs nodes) and edged (which checks pipelines)
+//! must know about the same nodes, so they should both use this crate.
+
+use copper_piper::base::{NodeDispatcher, RegisterNodeError};
+
+/// Register every node we provide into the given dispatcher.
+/// On failure, also returns the name of the module we couldn't register.
+pub fn register_all(
+	dispatcher: &mut NodeDispatcher,
+) -> Result<(), (&'static str, RegisterNodeError)> {
+	nodes_basic::register(dispatcher).map_err(|e| ("basic", e))?;
+	nodes_audiofile::nodes::register(dispatcher).map_err(|e| ("audiofile", e))?;
+	return Ok(());
+}
@@ -1,8 +1,11 @@
 use crate::flac::proc::pictures::FlacPictureReader;
 use async_trait::async_trait;
 use copper_piper::{
-	base::{Node, NodeBuilder, PortName, RunNodeError, ThisNodeInfo},
-	data::PipeData,
+	base::{
+		Node, NodeBuilder, NodeInputSpec, NodeInputs, NodePortType, NodePorts, PortName,
+		RunNodeError, ThisNodeInfo,
+	},
+	data::{PipeData, PipeDataStub},
 	helpers::{processor::BytesProcessorBuilder, rawbytes::RawBytesSource, NodeParameters},
 	CopperContext,
 };
@@ -15,6 +18,24 @@ impl NodeBuilder for ExtractCovers {
 	fn build<'ctx>(&self) -> Box<dyn Node<'ctx>> {
 		Box::new(Self {})
 	}
+
+	fn ports(&self, params: NodeParameters) -> Result<NodePorts, RunNodeError> {
+		params.err_if_not_empty()?;
+
+		return Ok(NodePorts {
+			inputs: NodeInputs::Fixed(BTreeMap::from([(
+				PortName::new("data"),
+				NodeInputSpec {
+					port_type: NodePortType::Exact(PipeDataStub::Blob),
+					is_optional: false,
+				},
+			)])),
+			outputs: BTreeMap::from([(
+				PortName::new("cover_data"),
+				NodePortType::Exact(PipeDataStub::Blob),
+			)]),
+		});
+	}
 }
 
 // Inputs: "data" - Bytes
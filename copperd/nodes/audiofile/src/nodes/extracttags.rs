@@ -4,8 +4,11 @@ use crate::{
 };
 use async_trait::async_trait;
 use copper_piper::{
-	base::{Node, NodeBuilder, NodeParameterValue, PortName, RunNodeError, ThisNodeInfo},
-	data::PipeData,
+	base::{
+		Node, NodeBuilder, NodeInputSpec, NodeInputs, NodeParameterValue, NodePortType, NodePorts,
+		PortName, RunNodeError, ThisNodeInfo,
+	},
+	data::{PipeData, PipeDataStub},
 	helpers::NodeParameters,
 	CopperContext,
 };
@@ -15,10 +18,62 @@ use tracing::{debug, trace};
 /// Extract tags from audio metadata
 pub struct ExtractTags {}
 
+impl ExtractTags {
+	/// Get the tags we should extract from this node's parameters.
+	/// Each tag is sent to the output port with the same name.
+	fn pop_tags(params: &mut NodeParameters) -> Result<BTreeMap<PortName, TagType>, RunNodeError> {
+		let mut tags: BTreeMap<PortName, TagType> = BTreeMap::new();
+		let val = params.pop_val("tags")?;
+
+		match val {
+			NodeParameterValue::List(list) => {
+				for t in list {
+					match t {
+						NodeParameterValue::String(s) => {
+							tags.insert(PortName::new(s.as_str()), s.as_str().into());
+						}
+						_ => {
+							return Err(RunNodeError::BadParameterType {
+								parameter: "tags".into(),
+							})
+						}
+					}
+				}
+			}
+			_ => {
+				return Err(RunNodeError::BadParameterType {
+					parameter: "tags".into(),
+				})
+			}
+		};
+
+		return Ok(tags);
+	}
+}
+
 impl NodeBuilder for ExtractTags {
 	fn build<'ctx>(&self) -> Box<dyn Node<'ctx>> {
 		Box::new(Self {})
 	}
+
+	fn ports(&self, mut params: NodeParameters) -> Result<NodePorts, RunNodeError> {
+		let tags = Self::pop_tags(&mut params)?;
+		params.err_if_not_empty()?;
+
+		return Ok(NodePorts {
+			inputs: NodeInputs::Fixed(BTreeMap::from([(
+				PortName::new("data"),
+				NodeInputSpec {
+					port_type: NodePortType::Exact(PipeDataStub::Blob),
+					is_optional: false,
+				},
+			)])),
+			outputs: tags
+				.into_keys()
+				.map(|port| (port, NodePortType::Exact(PipeDataStub::Text)))
+				.collect(),
+		});
+	}
 }
 
 // Inputs: "data" - Bytes
@@ -36,34 +91,7 @@ impl<'ctx> Node<'ctx> for ExtractTags {
 		// Extract parameters
 		//
 
-		let tags = {
-			let mut tags: BTreeMap<PortName, TagType> = BTreeMap::new();
-			let val = params.pop_val("tags")?;
-
-			match val {
-				NodeParameterValue::List(list) => {
-					for t in list {
-						match t {
-							NodeParameterValue::String(s) => {
-								tags.insert(PortName::new(s.as_str()), s.as_str().into());
-							}
-							_ => {
-								return Err(RunNodeError::BadParameterType {
-									parameter: "tags".into(),
-								})
-							}
-						}
-					}
-				}
-				_ => {
-					return Err(RunNodeError::BadParameterType {
-						parameter: "tags".into(),
-					})
-				}
-			};
-
-			tags
-		};
+		let tags = Self::pop_tags(&mut params)?;
 
 		params.err_if_not_empty()?;
 
@@ -1,5 +1,5 @@
 //! Pipeline nodes for processing audio files
-use copper_piper::base::{NodeDispatcher, RegisterNodeError};
+use copper_piper::base::{NodeDispatcher, NodeParameterSpec, NodeParameterType, RegisterNodeError};
 use std::collections::BTreeMap;
 
 mod extractcovers;
@@ -11,6 +11,7 @@ pub fn register(dispatcher: &mut NodeDispatcher) -> Result<(), RegisterNodeError
 	dispatcher
 		.register_node(
 			"StripTags",
+			"Remove all metadata from a flac file.",
 			BTreeMap::new(),
 			Box::new(striptags::StripTags {}),
 		)
@@ -19,6 +20,7 @@ pub fn register(dispatcher: &mut NodeDispatcher) -> Result<(), RegisterNodeError
 	dispatcher
 		.register_node(
 			"ExtractCovers",
+			"Extract the first cover image from a flac file, if there is one.",
 			BTreeMap::new(),
 			Box::new(extractcovers::ExtractCovers {}),
 		)
@@ -27,7 +29,16 @@ pub fn register(dispatcher: &mut NodeDispatcher) -> Result<(), RegisterNodeError
 	dispatcher
 		.register_node(
 			"ExtractTags",
-			BTreeMap::new(),
+			"Read metadata tags from a flac file. Each tag is sent to the output with the same name.",
+			BTreeMap::from([(
+				"tags".into(),
+				NodeParameterSpec {
+					param_type: NodeParameterType::List {
+						item_type: Box::new(NodeParameterType::String),
+					},
+					is_optional: false,
+				},
+			)]),
 			Box::new(extracttags::ExtractTags {}),
 		)
 		.unwrap();
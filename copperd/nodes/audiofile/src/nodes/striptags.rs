@@ -3,8 +3,11 @@
 use crate::flac::proc::metastrip::FlacMetaStrip;
 use async_trait::async_trait;
 use copper_piper::{
-	base::{Node, NodeBuilder, NodeId, PortName, RunNodeError, ThisNodeInfo},
-	data::PipeData,
+	base::{
+		Node, NodeBuilder, NodeId, NodeInputSpec, NodeInputs, NodePortType, NodePorts, PortName,
+		RunNodeError, ThisNodeInfo,
+	},
+	data::{PipeData, PipeDataStub},
 	helpers::{
 		processor::{StreamProcessor, StreamProcessorBuilder},
 		NodeParameters,
@@ -24,6 +27,24 @@ impl NodeBuilder for StripTags {
 	fn build<'ctx>(&self) -> Box<dyn Node<'ctx>> {
 		Box::new(Self {})
 	}
+
+	fn ports(&self, params: NodeParameters) -> Result<NodePorts, RunNodeError> {
+		params.err_if_not_empty()?;
+
+		return Ok(NodePorts {
+			inputs: NodeInputs::Fixed(BTreeMap::from([(
+				PortName::new("data"),
+				NodeInputSpec {
+					port_type: NodePortType::Exact(PipeDataStub::Blob),
+					is_optional: false,
+				},
+			)])),
+			outputs: BTreeMap::from([(
+				PortName::new("out"),
+				NodePortType::Exact(PipeDataStub::Blob),
+			)]),
+		});
+	}
 }
 
 // Input: "data" - Blob
@@ -7,8 +7,11 @@ use copper_itemdb::{
 	AttrData, AttributeInfo,
 };
 use copper_piper::{
-	base::{Node, NodeBuilder, PortName, RunNodeError, ThisNodeInfo},
-	data::PipeData,
+	base::{
+		Node, NodeBuilder, NodeInputs, NodePortType, NodePorts, PortName, RunNodeError,
+		ThisNodeInfo,
+	},
+	data::{PipeData, PipeDataStub},
 	helpers::NodeParameters,
 	CopperContext,
 };
@@ -29,12 +32,45 @@ enum OnUniqueViolation {
 	Select,
 }
 
+impl OnUniqueViolation {
+	fn pop_from(params: &mut NodeParameters) -> Result<Self, RunNodeError> {
+		return match params.pop_str("on_unique_violation")?.as_str() {
+			"fail" => Ok(Self::Fail),
+			"select" => Ok(Self::Select),
+
+			x => Err(RunNodeError::BadParameterOther {
+				parameter: "on_unique_violation".into(),
+				message: format!("Invalid value `{x}`, expected one of [`fail`, `select`]"),
+			}),
+		};
+	}
+}
+
 pub struct AddItem {}
 
 impl NodeBuilder for AddItem {
 	fn build<'ctx>(&self) -> Box<dyn Node<'ctx>> {
 		Box::new(Self {})
 	}
+
+	fn ports(&self, mut params: NodeParameters) -> Result<NodePorts, RunNodeError> {
+		let class = params.pop_int("class")?;
+		params.pop_int("dataset")?;
+		OnUniqueViolation::pop_from(&mut params)?;
+		params.err_if_not_empty()?;
+
+		return Ok(NodePorts {
+			// Our inputs are this class' attributes,
+			// which we can only get from the item db.
+			inputs: NodeInputs::Dynamic,
+			outputs: BTreeMap::from([(
+				PortName::new("new_item"),
+				NodePortType::Exact(PipeDataStub::Reference {
+					class: class.into(),
+				}),
+			)]),
+		});
+	}
 }
 
 // Inputs: depends on class
@@ -98,17 +134,7 @@ impl<'ctx> Node<'ctx> for AddItem {
 			};
 		};
 
-		let on_unique_violation = match params.pop_str("on_unique_violation")?.as_str() {
-			"fail" => OnUniqueViolation::Fail,
-			"select" => OnUniqueViolation::Select,
-
-			x => {
-				return Err(RunNodeError::BadParameterOther {
-					parameter: "on_unique_violation".into(),
-					message: format!("Invalid value `{x}`, expected one of [`fail`, `select`]"),
-				})
-			}
-		};
+		let on_unique_violation = OnUniqueViolation::pop_from(&mut params)?;
 
 		params.err_if_not_empty()?;
 
@@ -152,20 +178,20 @@ impl<'ctx> Node<'ctx> for AddItem {
 							reader.mime().clone(),
 						)
 						.await
-						.map_err(|e| RunNodeError::Other(Arc::new(e)))?;
+						.map_err(|e| RunNodeError::ObjectStoreError(Arc::new(e)))?;
 
 					while let Some(data) = reader.next_fragment().await? {
 						upload
 							.upload_part(&data, part_counter)
 							.await
-							.map_err(|e| RunNodeError::Other(Arc::new(e)))?;
+							.map_err(|e| RunNodeError::ObjectStoreError(Arc::new(e)))?;
 						part_counter += 1;
 					}
 
 					upload
 						.finish()
 						.await
-						.map_err(|e| RunNodeError::Other(Arc::new(e)))?;
+						.map_err(|e| RunNodeError::ObjectStoreError(Arc::new(e)))?;
 
 					let attr = attributes.get_mut(&port).unwrap();
 
@@ -177,14 +203,11 @@ impl<'ctx> Node<'ctx> for AddItem {
 
 				Some(x) => {
 					let attr = attributes.get_mut(&port).unwrap();
-					let as_attr: AttrData = match x.try_into() {
-						Ok(x) => x,
-						Err(_) => return Err(RunNodeError::BadInputType { port }),
-					};
-
-					if as_attr.as_stub() != attr.0.data_type {
-						return Err(RunNodeError::BadInputType { port });
-					}
+					let as_attr: AttrData =
+						match x.conform_to(attr.0.data_type.into()).map(|x| x.try_into()) {
+							Some(Ok(x)) => x,
+							_ => return Err(RunNodeError::BadInputType { port }),
+						};
 
 					attr.1 = Some(as_attr);
 				}
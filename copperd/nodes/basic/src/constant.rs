@@ -1,6 +1,9 @@
 use async_trait::async_trait;
 use copper_piper::{
-	base::{Node, NodeBuilder, NodeParameterValue, PortName, RunNodeError, ThisNodeInfo},
+	base::{
+		Node, NodeBuilder, NodeInputs, NodeParameterValue, NodePortType, NodePorts, PortName,
+		RunNodeError, ThisNodeInfo,
+	},
 	data::PipeData,
 	helpers::NodeParameters,
 	CopperContext,
@@ -9,10 +12,41 @@ use std::collections::BTreeMap;
 
 pub struct Constant {}
 
+impl Constant {
+	/// Get the value this node produces from its parameters
+	fn pop_value(params: &mut NodeParameters) -> Result<PipeData, RunNodeError> {
+		let val = params.pop_val("value")?;
+
+		return Ok(match val {
+			NodeParameterValue::String(value) => PipeData::Text { value },
+			NodeParameterValue::Boolean(value) => PipeData::Boolean { value },
+			NodeParameterValue::Integer(value) => PipeData::Integer {
+				value,
+				is_non_negative: false,
+			},
+			_ => {
+				return Err(RunNodeError::BadParameterType {
+					parameter: "value".into(),
+				})
+			}
+		});
+	}
+}
+
 impl NodeBuilder for Constant {
 	fn build<'ctx>(&self) -> Box<dyn Node<'ctx>> {
 		Box::new(Self {})
 	}
+
+	fn ports(&self, mut params: NodeParameters) -> Result<NodePorts, RunNodeError> {
+		let value = Self::pop_value(&mut params)?;
+		params.err_if_not_empty()?;
+
+		return Ok(NodePorts {
+			inputs: NodeInputs::Fixed(BTreeMap::new()),
+			outputs: BTreeMap::from([(PortName::new("out"), NodePortType::Exact(value.as_stub()))]),
+		});
+	}
 }
 
 #[async_trait]
@@ -27,24 +61,7 @@ impl<'ctx> Node<'ctx> for Constant {
 		//
 		// Extract parameters
 		//
-		let value = {
-			let val = params.pop_val("value")?;
-
-			match val {
-				NodeParameterValue::String(value) => PipeData::Text { value },
-				NodeParameterValue::Boolean(value) => PipeData::Boolean { value },
-				NodeParameterValue::Integer(value) => PipeData::Integer {
-					value,
-					is_non_negative: false,
-				},
-				_ => {
-					return Err(RunNodeError::BadParameterType {
-						parameter: "value".into(),
-					})
-				}
-			}
-		};
-
+		let value = Self::pop_value(&mut params)?;
 		params.err_if_not_empty()?;
 
 		//
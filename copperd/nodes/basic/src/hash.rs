@@ -1,7 +1,10 @@
 use async_trait::async_trait;
 use copper_piper::{
-	base::{Node, NodeBuilder, PortName, RunNodeError, ThisNodeInfo},
-	data::PipeData,
+	base::{
+		Node, NodeBuilder, NodeInputSpec, NodeInputs, NodePortType, NodePorts, PortName,
+		RunNodeError, ThisNodeInfo,
+	},
+	data::{PipeData, PipeDataStub},
 	helpers::NodeParameters,
 	CopperContext,
 };
@@ -75,10 +78,48 @@ impl HashComputer {
 
 pub struct Hash {}
 
+impl Hash {
+	/// Get the type of hash we should compute from this node's parameters
+	fn pop_hash_type(params: &mut NodeParameters) -> Result<HashType, RunNodeError> {
+		let s = params.pop_str("hash_type")?;
+		return Ok(match s.as_str() {
+			"MD5" => HashType::MD5,
+			"SHA256" => HashType::SHA256,
+			"SHA512" => HashType::SHA512,
+
+			x => {
+				return Err(RunNodeError::BadParameterOther {
+					parameter: "hash_type".into(),
+					message: format!("Invalid hash type `{x}`"),
+				})
+			}
+		});
+	}
+}
+
 impl NodeBuilder for Hash {
 	fn build<'ctx>(&self) -> Box<dyn Node<'ctx>> {
 		Box::new(Self {})
 	}
+
+	fn ports(&self, mut params: NodeParameters) -> Result<NodePorts, RunNodeError> {
+		let hash_type = Self::pop_hash_type(&mut params)?;
+		params.err_if_not_empty()?;
+
+		return Ok(NodePorts {
+			inputs: NodeInputs::Fixed(BTreeMap::from([(
+				PortName::new("data"),
+				NodeInputSpec {
+					port_type: NodePortType::Exact(PipeDataStub::Blob),
+					is_optional: false,
+				},
+			)])),
+			outputs: BTreeMap::from([(
+				PortName::new("hash"),
+				NodePortType::Exact(PipeDataStub::Hash { hash_type }),
+			)]),
+		});
+	}
 }
 
 // Inputs: "data", Bytes
@@ -95,22 +136,7 @@ impl<'ctx> Node<'ctx> for Hash {
 		//
 		// Extract parameters
 		//
-		let hash_type: HashType = {
-			let s = params.pop_str("hash_type")?;
-			match s.as_str() {
-				"MD5" => HashType::MD5,
-				"SHA256" => HashType::SHA256,
-				"SHA512" => HashType::SHA512,
-
-				x => {
-					return Err(RunNodeError::BadParameterOther {
-						parameter: "hash_type".into(),
-						message: format!("Invalid hash type `{x}`"),
-					})
-				}
-			}
-		};
-
+		let hash_type = Self::pop_hash_type(&mut params)?;
 		params.err_if_not_empty()?;
 
 		//
@@ -1,6 +1,9 @@
 use async_trait::async_trait;
 use copper_piper::{
-	base::{Node, NodeBuilder, PortName, RunNodeError, ThisNodeInfo},
+	base::{
+		Node, NodeBuilder, NodeInputSpec, NodeInputs, NodePortType, NodePorts, PortName,
+		RunNodeError, ThisNodeInfo,
+	},
 	data::PipeData,
 	helpers::NodeParameters,
 	CopperContext,
@@ -13,6 +16,31 @@ impl NodeBuilder for IfNone {
 	fn build<'ctx>(&self) -> Box<dyn Node<'ctx>> {
 		Box::new(Self {})
 	}
+
+	fn ports(&self, params: NodeParameters) -> Result<NodePorts, RunNodeError> {
+		params.err_if_not_empty()?;
+
+		let t = NodePortType::Generic("T".into());
+		return Ok(NodePorts {
+			inputs: NodeInputs::Fixed(BTreeMap::from([
+				(
+					PortName::new("data"),
+					NodeInputSpec {
+						port_type: t.clone(),
+						is_optional: false,
+					},
+				),
+				(
+					PortName::new("ifnone"),
+					NodeInputSpec {
+						port_type: t.clone(),
+						is_optional: false,
+					},
+				),
+			])),
+			outputs: BTreeMap::from([(PortName::new("out"), t)]),
+		});
+	}
 }
 
 // Inputs:
@@ -38,12 +66,8 @@ impl<'ctx> Node<'ctx> for IfNone {
 		// Extract input
 		//
 
-		// Note that we do not have enough information to catch type errors here.
-		// We cannot check if the type of `data` matches the type of `ifnone`.
-		// This may not be a problem (UI prevents this, and you deserve confusion if
-		// you're hand-crafting json), but it would be nice to catch this anyway.
-		// TODO: possible solutions are static type analysis (in build()) or
-		// a typed `None` pipeline data container.
+		// We don't check that `data` and `ifnone` have the same type here,
+		// that is done when the pipeline is built (see `ports()`).
 
 		// We need data right away, so await it now
 		let data = match input.remove(&PortName::new("data")) {
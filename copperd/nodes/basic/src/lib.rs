@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
 
-use copper_piper::base::{NodeDispatcher, RegisterNodeError};
+use copper_piper::base::{NodeDispatcher, NodeParameterSpec, NodeParameterType, RegisterNodeError};
 
 mod additem;
 mod constant;
@@ -9,10 +9,77 @@ mod ifnone;
 
 /// Register all nodes in this module into the given runner.
 pub fn register(dispatcher: &mut NodeDispatcher) -> Result<(), RegisterNodeError> {
-	dispatcher.register_node("IfNone", BTreeMap::new(), Box::new(ifnone::IfNone {}))?;
-	dispatcher.register_node("Hash", BTreeMap::new(), Box::new(hash::Hash {}))?;
-	dispatcher.register_node("Constant", BTreeMap::new(), Box::new(constant::Constant {}))?;
-	dispatcher.register_node("AddItem", BTreeMap::new(), Box::new(additem::AddItem {}))?;
+	dispatcher.register_node(
+		"IfNone",
+		"Pass `data` through to `out`. If `data` is empty, pass `ifnone` instead.",
+		BTreeMap::new(),
+		Box::new(ifnone::IfNone {}),
+	)?;
+
+	dispatcher.register_node(
+		"Hash",
+		"Compute a checksum of a blob.",
+		BTreeMap::from([(
+			"hash_type".into(),
+			NodeParameterSpec {
+				param_type: NodeParameterType::Enum {
+					variants: ["MD5".into(), "SHA256".into(), "SHA512".into()].into(),
+				},
+				is_optional: false,
+			},
+		)]),
+		Box::new(hash::Hash {}),
+	)?;
+
+	dispatcher.register_node(
+		"Constant",
+		"Produce a constant value.",
+		BTreeMap::from([(
+			"value".into(),
+			NodeParameterSpec {
+				param_type: NodeParameterType::Union {
+					types: vec![
+						NodeParameterType::String,
+						NodeParameterType::Boolean,
+						NodeParameterType::Integer,
+					],
+				},
+				is_optional: false,
+			},
+		)]),
+		Box::new(constant::Constant {}),
+	)?;
+
+	dispatcher.register_node(
+		"AddItem",
+		"Create a new item in a class. This node's inputs are the class' attributes.",
+		BTreeMap::from([
+			(
+				"class".into(),
+				NodeParameterSpec {
+					param_type: NodeParameterType::Integer,
+					is_optional: false,
+				},
+			),
+			(
+				"dataset".into(),
+				NodeParameterSpec {
+					param_type: NodeParameterType::Integer,
+					is_optional: false,
+				},
+			),
+			(
+				"on_unique_violation".into(),
+				NodeParameterSpec {
+					param_type: NodeParameterType::Enum {
+						variants: ["fail".into(), "select".into()].into(),
+					},
+					is_optional: false,
+				},
+			),
+		]),
+		Box::new(additem::AddItem {}),
+	)?;
 
 	return Ok(());
 }